@@ -1,3 +1,4 @@
+pub mod models;
 pub mod pool;
 pub mod queries;
 pub use pool::{init_db, init_redis, Db, RedisConn};
@@ -0,0 +1,549 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Circuit breaker thresholds, stored per partner in `partners.circuit_breaker`.
+/// Missing fields fall back to the defaults below.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Opt-in per partner, like hedging.
+    pub enabled: bool,
+    /// Consecutive failed attempts that trip the breaker.
+    pub consecutive_failures: u32,
+    /// Failure ratio (0.0..=1.0) over the window that trips the breaker.
+    pub error_rate: f64,
+    /// Minimum attempts in the window before `error_rate` is considered.
+    pub min_requests: u32,
+    pub window_secs: u64,
+    /// How long the breaker stays open before letting a probe through.
+    pub open_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            consecutive_failures: 5,
+            error_rate: 0.5,
+            min_requests: 20,
+            window_secs: 60,
+            open_secs: 30,
+        }
+    }
+}
+
+/// Request hedging, stored per partner in `partners.hedging`.
+/// Only idempotent requests are hedged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HedgingConfig {
+    pub enabled: bool,
+    /// Fixed hedge delay. When unset, the partner's observed p95 latency is used.
+    pub delay_ms: Option<u64>,
+    /// Used until enough latency samples exist to derive a p95.
+    pub fallback_delay_ms: u64,
+    /// Clamp for the p95-derived delay.
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Send the hedge with a different upstream credential of the same partner, if one exists.
+    pub alternate_credential: bool,
+    /// Send the hedge to this base URL instead of `partners.base_url`.
+    pub base_url: Option<String>,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: None,
+            fallback_delay_ms: 250,
+            min_delay_ms: 20,
+            max_delay_ms: 2_000,
+            alternate_credential: false,
+            base_url: None,
+        }
+    }
+}
+
+/// Traffic mirroring, stored per partner in `partners.mirror`. A sample of requests is
+/// replayed against a shadow partner after the primary answered; the client never sees
+/// the shadow's response and mirrored calls are not metered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    pub enabled: bool,
+    /// Partner the copies are sent to; its own base URL, TLS settings and credential apply.
+    pub shadow_partner: Option<String>,
+    /// Share of requests mirrored (0.0..=1.0).
+    pub sample_rate: f64,
+    /// Time allowed for the whole shadow call, body included.
+    pub timeout_ms: u64,
+    /// Also mirror non-idempotent requests (POST, PUT, PATCH, DELETE).
+    pub include_writes: bool,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shadow_partner: None,
+            sample_rate: 0.0,
+            timeout_ms: 10_000,
+            include_writes: false,
+        }
+    }
+}
+
+/// How the gateway picks among a partner's upstream endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointStrategy {
+    /// Always prefer the first healthy endpoint in list order.
+    #[default]
+    Ordered,
+    /// Spread traffic across healthy endpoints proportionally to `weight`.
+    Weighted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartnerEndpoint {
+    pub url: String,
+    /// Only used by the weighted strategy. 0 = fallback only.
+    #[serde(default = "default_endpoint_weight")]
+    pub weight: u32,
+}
+
+fn default_endpoint_weight() -> u32 {
+    1
+}
+
+/// Upstream origins, stored per partner in `partners.upstream_endpoints`.
+/// When `endpoints` is empty the partner's `base_url` is the only origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamEndpoints {
+    pub strategy: EndpointStrategy,
+    pub endpoints: Vec<PartnerEndpoint>,
+    /// How long an endpoint is skipped after a connection error or 5xx.
+    pub unhealthy_secs: u64,
+}
+
+impl Default for UpstreamEndpoints {
+    fn default() -> Self {
+        Self {
+            strategy: EndpointStrategy::Ordered,
+            endpoints: Vec::new(),
+            unhealthy_secs: 30,
+        }
+    }
+}
+
+/// How an upstream credential is presented to the partner, stored per credential in
+/// `upstream_credentials.auth`. The secret itself is always `header_value`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "scheme", rename_all = "snake_case")]
+pub enum UpstreamAuth {
+    /// `{header_name}: {header_value}`.
+    #[default]
+    Header,
+    /// `Authorization: Bearer {header_value}`.
+    Bearer,
+    /// `Authorization: Basic base64({username}:{header_value})`.
+    Basic { username: String },
+    /// `?{param}={header_value}` appended to the upstream URL.
+    QueryParam { param: String },
+    /// HMAC-SHA256 signature keyed by `header_value`.
+    Hmac(HmacAuth),
+    /// OAuth2 client-credentials grant; `header_value` is the client secret and the
+    /// access token is sent as `Authorization: Bearer`.
+    Oauth2ClientCredentials(OAuth2ClientCredentials),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// Request signing: the signature covers `{timestamp}{METHOD}{path?query}{body}`
+/// (Coinbase-style; Binance-style keys use the same payload with a hex signature).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HmacAuth {
+    /// Public API key sent alongside the signature, under `api_key_header`.
+    pub api_key: Option<String>,
+    pub api_key_header: String,
+    pub signature_header: String,
+    pub timestamp_header: String,
+    /// Milliseconds since the epoch instead of seconds.
+    pub timestamp_millis: bool,
+    pub encoding: SignatureEncoding,
+}
+
+impl Default for HmacAuth {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            api_key_header: "X-Api-Key".to_string(),
+            signature_header: "X-Signature".to_string(),
+            timestamp_header: "X-Timestamp".to_string(),
+            timestamp_millis: false,
+            encoding: SignatureEncoding::Hex,
+        }
+    }
+}
+
+/// Where the client id/secret go in the token request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuthStyle {
+    /// `client_id` / `client_secret` form fields.
+    #[default]
+    Body,
+    /// HTTP Basic on the token request.
+    Basic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2ClientCredentials {
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub client_auth: ClientAuthStyle,
+    /// Tokens are refreshed this long before they expire.
+    #[serde(default = "default_refresh_skew_secs")]
+    pub refresh_skew_secs: u64,
+}
+
+fn default_refresh_skew_secs() -> u64 {
+    60
+}
+
+/// Where a PEM document (certificate, key, CA bundle) is read from.
+/// Private keys must come from `env` or `file`; they are never stored inline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum PemSource {
+    Inline { pem: String },
+    Env { var: String },
+    File { path: String },
+}
+
+/// TLS settings for talking to a partner, stored in `partners.tls`.
+/// Partners with any setting here get their own HTTP client.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartnerTls {
+    /// Client certificate chain (PEM) for mutual TLS; requires `client_key`.
+    pub client_cert: Option<PemSource>,
+    pub client_key: Option<PemSource>,
+    /// Extra trusted CA certificates (PEM).
+    pub ca_bundle: Option<PemSource>,
+    /// Trust only `ca_bundle`, not the public web PKI roots.
+    pub ca_only: bool,
+    /// Accepted SHA-256 fingerprints (hex) of the partner's leaf certificate. Empty = no pinning.
+    pub pinned_sha256: Vec<String>,
+}
+
+impl PartnerTls {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A credential's own mTLS client identity, stored in `upstream_credentials.client_identity`.
+/// Requests sent with the credential present it instead of the partner's; the partner's
+/// trust settings (`ca_bundle`, `ca_only`, `pinned_sha256`) still apply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientIdentity {
+    pub client_cert: PemSource,
+    pub client_key: PemSource,
+}
+
+/// Header handling per partner, stored in `partners.header_rules`. Header names are
+/// case-insensitive. `host`, `cookie`, `proxy-*` and hop-by-hop headers are never forwarded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderRules {
+    /// When set, only these client request headers are forwarded.
+    pub request_allow: Option<Vec<String>>,
+    /// Client request headers never forwarded.
+    pub request_deny: Vec<String>,
+    /// Static headers added to every upstream request (replacing any client value).
+    pub request_inject: BTreeMap<String, String>,
+    /// Forward the client's own `Authorization` header (e.g. a partner-issued sub-token).
+    /// A credential that sets `Authorization` itself still takes precedence.
+    pub forward_authorization: bool,
+    /// Partner response headers removed before the client sees them.
+    pub response_strip: Vec<String>,
+    /// Partner response headers renamed (partner name -> client name).
+    pub response_rename: BTreeMap<String, String>,
+    /// Pass the partner's `Set-Cookie` headers through to the client.
+    pub forward_set_cookie: bool,
+}
+
+/// Correlation headers sent to a partner, stored in `partners.forwarding`. All off by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForwardingHeaders {
+    /// `X-Forwarded-For` / `X-Forwarded-Proto` / `X-Forwarded-Host`.
+    pub x_forwarded: bool,
+    /// RFC 7239 `Forwarded`.
+    pub forwarded: bool,
+    /// Header carrying RelayKey's request id (e.g. `X-Request-Id`, `X-Correlation-Id`).
+    pub request_id_header: Option<String>,
+    /// W3C `traceparent`, continuing the client's trace when it sent one.
+    pub traceparent: bool,
+}
+
+/// Per-route behavior on a policy, stored in `policies.routes`. The first rule whose
+/// partner, method and path pattern match a request applies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteRule {
+    /// Partner name. None = any partner.
+    pub partner: Option<String>,
+    /// Upstream path, same glob syntax as `endpoint_allowlist` (`/v1/prices/*`).
+    pub path: String,
+    /// Uppercase methods. Empty = any method.
+    pub methods: Vec<String>,
+    /// Response caching for this route (GET only).
+    pub cache: Option<RouteCache>,
+    /// Share one upstream call between identical concurrent requests (GET/HEAD only).
+    pub coalesce: Option<RouteCoalesce>,
+    /// JSON Schema the request body must satisfy before it is forwarded.
+    pub request_schema: Option<serde_json::Value>,
+    /// Partner API operation (OpenAPI `operationId`); recorded on usage events.
+    pub operation_id: Option<String>,
+    /// Body and header rewrites between the client and the partner.
+    pub transform: Option<RouteTransform>,
+}
+
+/// Declarative rewrites for a route. Body operations apply to JSON bodies; string values
+/// and header templates may reference `{customer_id}`, `{virtual_key_id}`,
+/// `{virtual_key_name}`, `{environment}` and `{tags}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteTransform {
+    /// Applied, in order, to the request body before it is sent to the partner.
+    pub request: Vec<JsonOp>,
+    /// Applied, in order, to the partner's response body before it reaches the client.
+    pub response: Vec<JsonOp>,
+    /// Headers set on the upstream request (name -> template).
+    pub request_headers: BTreeMap<String, String>,
+    /// Larger responses can't be rewritten and are refused rather than passed through.
+    pub max_response_bytes: usize,
+}
+
+impl Default for RouteTransform {
+    fn default() -> Self {
+        Self {
+            request: Vec::new(),
+            response: Vec::new(),
+            request_headers: BTreeMap::new(),
+            max_response_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// One edit of a JSON document, addressed by JSON pointer (`/customer/tenant_id`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JsonOp {
+    /// Set a value, creating missing parent objects.
+    Set { path: String, value: serde_json::Value },
+    /// Remove a value if present.
+    Remove { path: String },
+    /// Move a value if present.
+    Rename { from: String, to: String },
+}
+
+/// Response cache settings for a route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteCache {
+    /// TTL when the partner sends no `Cache-Control` freshness (`s-maxage` / `max-age`).
+    /// None = only cache what the partner marks cacheable.
+    pub ttl_secs: Option<u64>,
+    /// Use `ttl_secs` even when the partner's `Cache-Control` says otherwise.
+    pub ignore_cache_control: bool,
+    /// Request headers that are part of the cache key (e.g. `accept-language`).
+    pub vary_headers: Vec<String>,
+    /// Keep entries per customer. Turn off only for data that is identical for everyone.
+    pub per_customer: bool,
+    /// Larger responses are passed through but not cached.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RouteCache {
+    fn default() -> Self {
+        Self {
+            ttl_secs: None,
+            ignore_cache_control: false,
+            vary_headers: Vec::new(),
+            per_customer: true,
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Single-flight settings for a route. Requests are identical when partner, method,
+/// path, query and `vary_headers` match (and the customer, unless `per_customer` is off).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteCoalesce {
+    pub vary_headers: Vec<String>,
+    pub per_customer: bool,
+    /// Larger responses go to the first caller only; the others are forwarded themselves.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RouteCoalesce {
+    fn default() -> Self {
+        Self {
+            vary_headers: Vec::new(),
+            per_customer: true,
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// JSON-RPC mode for a partner, stored in `partners.jsonrpc`. When enabled, POST bodies
+/// are parsed as JSON-RPC (single calls or batches) and checked per method. Method
+/// patterns are exact names or prefixes ending in `*` (`debug_*`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonRpcConfig {
+    pub enabled: bool,
+    /// When set, only these methods may be called.
+    pub allow_methods: Option<Vec<String>>,
+    /// Methods never forwarded (checked before `allow_methods`).
+    pub deny_methods: Vec<String>,
+    /// Cost units per method (pattern -> units). Unlisted methods cost `default_cost`.
+    pub method_costs: BTreeMap<String, u32>,
+    pub default_cost: u32,
+    /// Most calls accepted in one batch. None = unlimited.
+    pub max_batch_size: Option<usize>,
+}
+
+impl Default for JsonRpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_methods: None,
+            deny_methods: Vec::new(),
+            method_costs: BTreeMap::new(),
+            default_cost: 1,
+            max_batch_size: None,
+        }
+    }
+}
+
+/// GraphQL mode for a partner, stored in `partners.graphql`. When enabled, every request
+/// document is parsed and checked before it is forwarded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphQlConfig {
+    pub enabled: bool,
+    /// Allowed operation types (`query`, `mutation`, `subscription`). None = all.
+    pub allow_operation_types: Option<Vec<String>>,
+    /// Allowed top-level fields per operation type. Types not listed are unrestricted.
+    pub allow_fields: BTreeMap<String, Vec<String>>,
+    /// Allow `__schema` / `__type` queries.
+    pub allow_introspection: bool,
+    /// Deepest field nesting allowed. None = unlimited.
+    pub max_depth: Option<usize>,
+    /// Most fields selected in one operation (fragments expanded). None = unlimited.
+    pub max_complexity: Option<usize>,
+    /// Only accept documents whose SHA-256 (hex) is in `persisted_hashes`.
+    pub persisted_only: bool,
+    pub persisted_hashes: Vec<String>,
+}
+
+/// Masking applied to partner responses for keys on a policy, stored in
+/// `policies.redaction`. Empty = responses are relayed as they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionProfile {
+    /// JSON values to mask, JSONPath style: `$.document.number`, `$.people[*].dob`, `$..iban`.
+    pub json_paths: Vec<String>,
+    /// Regular expressions masked wherever they match (JSON strings, text bodies, SSE events).
+    pub patterns: Vec<String>,
+    /// Built-in patterns by name: `iban`, `ssn`, `email`, `card_number`.
+    pub builtin: Vec<String>,
+    /// Replacement for masked values.
+    pub mask: String,
+    /// Larger (non-stream) responses can't be checked and are refused.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RedactionProfile {
+    fn default() -> Self {
+        Self {
+            json_paths: Vec::new(),
+            patterns: Vec::new(),
+            builtin: Vec::new(),
+            mask: "[REDACTED]".to_string(),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl RedactionProfile {
+    pub fn is_active(&self) -> bool {
+        !self.json_paths.is_empty() || !self.patterns.is_empty() || !self.builtin.is_empty()
+    }
+}
+
+/// What keeps a caller on the same side of a canary split.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanarySticky {
+    #[default]
+    Customer,
+    Key,
+}
+
+/// Canary routing on a policy, stored in `policies.canaries`: `percent` of the customers
+/// (or keys) calling `partner` are served by `alternate` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryRule {
+    /// Partner named in the proxy path.
+    pub partner: String,
+    /// Partner that serves the canary share.
+    pub alternate: String,
+    /// 0.0..=100.0
+    pub percent: f64,
+    pub sticky: CanarySticky,
+    /// Error rate (0.0..=1.0) of the alternate within `window_secs` that rolls the
+    /// canary back to 0%.
+    pub max_error_rate: f64,
+    /// Attempts needed in a window before its error rate counts.
+    pub min_requests: u32,
+    pub window_secs: u64,
+    /// Set when the canary was rolled back automatically.
+    pub rolled_back_at: Option<DateTime<Utc>>,
+}
+
+impl Default for CanaryRule {
+    fn default() -> Self {
+        Self {
+            partner: String::new(),
+            alternate: String::new(),
+            percent: 0.0,
+            sticky: CanarySticky::Customer,
+            max_error_rate: 0.2,
+            min_requests: 20,
+            window_secs: 300,
+            rolled_back_at: None,
+        }
+    }
+}
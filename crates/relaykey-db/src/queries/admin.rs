@@ -1,7 +1,13 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool};
 use uuid::Uuid;
-use crate::queries::virtual_keys::VirtualKeyRow;
+use crate::models::{
+    CircuitBreakerConfig, ForwardingHeaders, GraphQlConfig, HeaderRules, HedgingConfig,
+    JsonRpcConfig, MirrorConfig, PartnerTls, UpstreamEndpoints,
+};
+use crate::queries::virtual_keys::{PartnerRow, VirtualKeyRow};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_virtual_key(
     db: &PgPool,
     name: &str,
@@ -59,7 +65,8 @@ pub async fn list_virtual_keys(
         rps_burst,
         monthly_quota,
         policy_id, 
-        created_at
+        created_at,
+        sandbox_mode
         FROM virtual_keys
         ORDER BY created_at DESC
         "#
@@ -67,3 +74,146 @@ pub async fn list_virtual_keys(
     .fetch_all(db)
     .await
 }
+
+/// Set (or clear, with None) a key's sandbox mode. False = no such key.
+pub async fn update_virtual_key_sandbox_mode(
+    db: &PgPool,
+    id: Uuid,
+    sandbox_mode: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE virtual_keys
+        SET sandbox_mode = $2
+        WHERE id = $1
+        "#,
+        id,
+        sandbox_mode
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Create a partner, or update the origin settings of an existing one (by name).
+/// `tls` and `hedging` None leave an existing partner's settings as they are.
+pub async fn upsert_partner(
+    db: &PgPool,
+    name: &str,
+    base_url: &str,
+    internal: bool,
+    upstream_endpoints: &UpstreamEndpoints,
+    tls: Option<&PartnerTls>,
+    hedging: Option<&HedgingConfig>,
+) -> Result<Uuid, sqlx::Error> {
+    let rec = sqlx::query!(
+        r#"
+        INSERT INTO partners (name, base_url, internal, upstream_endpoints, tls, hedging)
+        VALUES ($1, $2, $3, $4, COALESCE($5, '{}'::jsonb), COALESCE($6, '{}'::jsonb))
+        ON CONFLICT (name) DO UPDATE
+        SET base_url = EXCLUDED.base_url,
+            internal = EXCLUDED.internal,
+            upstream_endpoints = EXCLUDED.upstream_endpoints,
+            tls = COALESCE($5, partners.tls),
+            hedging = COALESCE($6, partners.hedging)
+        RETURNING id
+        "#,
+        name,
+        base_url,
+        internal,
+        Json(upstream_endpoints) as _,
+        tls.map(Json) as _,
+        hedging.map(Json) as _,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(rec.id)
+}
+
+pub async fn list_partners(db: &PgPool) -> Result<Vec<PartnerRow>, sqlx::Error> {
+    sqlx::query_as!(
+        PartnerRow,
+        r#"
+        SELECT
+            id,
+            name,
+            base_url,
+            internal,
+            circuit_breaker as "circuit_breaker: Json<CircuitBreakerConfig>",
+            idempotency_header,
+            hedging as "hedging: Json<HedgingConfig>",
+            upstream_endpoints as "upstream_endpoints: Json<UpstreamEndpoints>",
+            tls as "tls: Json<PartnerTls>",
+            header_rules as "header_rules: Json<HeaderRules>",
+            forwarding as "forwarding: Json<ForwardingHeaders>",
+            jsonrpc as "jsonrpc: Json<JsonRpcConfig>",
+            graphql as "graphql: Json<GraphQlConfig>",
+            mirror as "mirror: Json<MirrorConfig>"
+        FROM partners
+        ORDER BY name
+        "#
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[derive(Debug, Clone)]
+pub struct PartnerOpenApiSpecRow {
+    pub partner_id: Uuid,
+    pub spec: serde_json::Value,
+    pub title: Option<String>,
+    pub spec_version: Option<String>,
+    pub operations: i32,
+    pub imported_at: DateTime<Utc>,
+}
+
+/// Store a partner's OpenAPI document, replacing any earlier import.
+pub async fn upsert_partner_openapi_spec(
+    db: &PgPool,
+    partner_id: Uuid,
+    spec: &serde_json::Value,
+    title: Option<&str>,
+    spec_version: Option<&str>,
+    operations: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO partner_openapi_specs (partner_id, spec, title, spec_version, operations)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (partner_id) DO UPDATE
+        SET spec = EXCLUDED.spec,
+            title = EXCLUDED.title,
+            spec_version = EXCLUDED.spec_version,
+            operations = EXCLUDED.operations,
+            imported_at = now()
+        "#,
+        partner_id,
+        spec,
+        title,
+        spec_version,
+        operations
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_partner_openapi_spec(
+    db: &PgPool,
+    partner_id: Uuid,
+) -> Result<Option<PartnerOpenApiSpecRow>, sqlx::Error> {
+    sqlx::query_as!(
+        PartnerOpenApiSpecRow,
+        r#"
+        SELECT partner_id, spec, title, spec_version, operations, imported_at
+        FROM partner_openapi_specs
+        WHERE partner_id = $1
+        "#,
+        partner_id
+    )
+    .fetch_optional(db)
+    .await
+}
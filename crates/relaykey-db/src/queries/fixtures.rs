@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// A response served to keys in mock mode (see `partner_fixtures`).
+#[derive(Debug, Clone)]
+pub struct FixtureRow {
+    pub id: Uuid,
+    pub partner_name: String,
+    pub method: String,
+    pub path: String,
+    /// None matches any query.
+    pub query: Option<String>,
+    /// None matches any body.
+    pub body_sha256: Option<String>,
+    pub status: i32,
+    pub headers: Json<BTreeMap<String, String>>,
+    pub body: Vec<u8>,
+    /// Render `{customer_id}`-style placeholders in the body and header values.
+    pub template: bool,
+    /// `recorded` or `canned`.
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A fixture to store; `source` is set by the function used.
+#[derive(Debug, Clone)]
+pub struct NewFixture {
+    pub partner_name: String,
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body_sha256: Option<String>,
+    pub status: i32,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+    pub template: bool,
+}
+
+/// A partner's fixtures, optionally for one method only, newest first.
+pub async fn list_fixtures(
+    db: &PgPool,
+    partner_name: &str,
+    method: Option<&str>,
+) -> Result<Vec<FixtureRow>, sqlx::Error> {
+    sqlx::query_as!(
+        FixtureRow,
+        r#"
+        SELECT
+            id,
+            partner_name,
+            method,
+            path,
+            query,
+            body_sha256,
+            status,
+            headers as "headers: Json<BTreeMap<String, String>>",
+            body,
+            template,
+            source,
+            created_at
+        FROM partner_fixtures
+        WHERE partner_name = $1
+          AND ($2::text IS NULL OR method = $2)
+        ORDER BY created_at DESC
+        "#,
+        partner_name,
+        method
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn insert_canned_fixture(db: &PgPool, f: &NewFixture) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO partner_fixtures
+            (partner_name, method, path, query, body_sha256, status, headers, body, template, source)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'canned')
+        RETURNING id
+        "#,
+        f.partner_name,
+        f.method,
+        f.path,
+        f.query,
+        f.body_sha256,
+        f.status,
+        Json(&f.headers) as _,
+        f.body,
+        f.template
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Store a recorded response, replacing the one recorded earlier for the same request.
+pub async fn upsert_recorded_fixture(db: &PgPool, f: &NewFixture) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM partner_fixtures
+        WHERE source = 'recorded'
+          AND partner_name = $1
+          AND method = $2
+          AND path = $3
+          AND query IS NOT DISTINCT FROM $4
+          AND body_sha256 IS NOT DISTINCT FROM $5
+        "#,
+        f.partner_name,
+        f.method,
+        f.path,
+        f.query,
+        f.body_sha256
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO partner_fixtures
+            (partner_name, method, path, query, body_sha256, status, headers, body, template, source)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, false, 'recorded')
+        RETURNING id
+        "#,
+        f.partner_name,
+        f.method,
+        f.path,
+        f.query,
+        f.body_sha256,
+        f.status,
+        Json(&f.headers) as _,
+        f.body
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// False = no such fixture for this partner.
+pub async fn delete_fixture(db: &PgPool, partner_name: &str, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM partner_fixtures
+        WHERE id = $1 AND partner_name = $2
+        "#,
+        id,
+        partner_name
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -19,6 +19,37 @@ pub struct UsageRollupRow {
     pub status_3xx: i64,
     pub status_4xx: i64,
     pub status_5xx: i64,
+
+    pub response_bytes: i64,
+    /// Answered from the response cache (included in total_requests, not forwarded).
+    pub cache_hits: i64,
+    /// Answered from sandbox fixtures (included in total_requests, not forwarded).
+    pub mocked_requests: i64,
+    /// JSON-RPC cost units of forwarded requests.
+    pub cost_units: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationRollupRow {
+    pub day: NaiveDate,
+    pub customer_id: Uuid,
+    pub virtual_key_id: Uuid,
+    pub partner_name: String,
+
+    pub operation_id: String,
+    pub calls: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcMethodRollupRow {
+    pub day: NaiveDate,
+    pub customer_id: Uuid,
+    pub virtual_key_id: Uuid,
+    pub partner_name: String,
+
+    pub method: String,
+    pub calls: i64,
+    pub cost_units: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -62,7 +93,11 @@ pub async fn rollup_usage_daily(
           status_2xx,
           status_3xx,
           status_4xx,
-          status_5xx
+          status_5xx,
+          response_bytes,
+          cache_hits,
+          mocked_requests,
+          cost_units
         )
         SELECT
           date_trunc('day', ue.ts)::date AS day,
@@ -79,7 +114,12 @@ pub async fn rollup_usage_daily(
           count(*) FILTER (WHERE ue.status_code BETWEEN 200 AND 299)::bigint AS status_2xx,
           count(*) FILTER (WHERE ue.status_code BETWEEN 300 AND 399)::bigint AS status_3xx,
           count(*) FILTER (WHERE ue.status_code BETWEEN 400 AND 499)::bigint AS status_4xx,
-          count(*) FILTER (WHERE ue.status_code BETWEEN 500 AND 599)::bigint AS status_5xx
+          count(*) FILTER (WHERE ue.status_code BETWEEN 500 AND 599)::bigint AS status_5xx,
+
+          coalesce(sum(ue.response_bytes), 0)::bigint AS response_bytes,
+          count(*) FILTER (WHERE ue.cache_hit = true)::bigint AS cache_hits,
+          count(*) FILTER (WHERE ue.mocked = true)::bigint AS mocked_requests,
+          coalesce(sum(ue.cost_units) FILTER (WHERE ue.forwarded = true), 0)::bigint AS cost_units
         FROM usage_events ue
         JOIN virtual_keys vk ON vk.id = ue.virtual_key_id
         WHERE ue.ts >= $1 AND ue.ts < $2
@@ -93,7 +133,101 @@ pub async fn rollup_usage_daily(
           status_2xx = EXCLUDED.status_2xx,
           status_3xx = EXCLUDED.status_3xx,
           status_4xx = EXCLUDED.status_4xx,
-          status_5xx = EXCLUDED.status_5xx
+          status_5xx = EXCLUDED.status_5xx,
+          response_bytes = EXCLUDED.response_bytes,
+          cache_hits = EXCLUDED.cache_hits,
+          mocked_requests = EXCLUDED.mocked_requests,
+          cost_units = EXCLUDED.cost_units
+        "#,
+        from,
+        to
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Roll up forwarded JSON-RPC calls per method into rpc_method_rollup_daily for [from, to)
+pub async fn rollup_rpc_method_daily(
+    db: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO rpc_method_rollup_daily (
+          day,
+          customer_id,
+          virtual_key_id,
+          partner_name,
+          method,
+          calls,
+          cost_units
+        )
+        SELECT
+          date_trunc('day', ue.ts)::date AS day,
+          vk.customer_id AS customer_id,
+          ue.virtual_key_id AS virtual_key_id,
+          ue.partner_name AS partner_name,
+          c.method AS method,
+
+          count(*)::bigint AS calls,
+          coalesce(sum(c.cost), 0)::bigint AS cost_units
+        FROM usage_events ue
+        JOIN virtual_keys vk ON vk.id = ue.virtual_key_id
+        CROSS JOIN LATERAL unnest(ue.rpc_methods, ue.rpc_method_costs) AS c(method, cost)
+        WHERE ue.ts >= $1 AND ue.ts < $2
+          AND ue.forwarded = true
+          AND ue.rpc_methods IS NOT NULL
+        GROUP BY 1,2,3,4,5
+        ON CONFLICT (day, customer_id, virtual_key_id, partner_name, method)
+        DO UPDATE SET
+          calls = EXCLUDED.calls,
+          cost_units = EXCLUDED.cost_units
+        "#,
+        from,
+        to
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Roll up forwarded requests per partner operation into operation_rollup_daily for [from, to)
+pub async fn rollup_operation_daily(
+    db: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO operation_rollup_daily (
+          day,
+          customer_id,
+          virtual_key_id,
+          partner_name,
+          operation_id,
+          calls
+        )
+        SELECT
+          date_trunc('day', ue.ts)::date AS day,
+          vk.customer_id AS customer_id,
+          ue.virtual_key_id AS virtual_key_id,
+          ue.partner_name AS partner_name,
+          ue.operation_id AS "operation_id!",
+
+          count(*)::bigint AS calls
+        FROM usage_events ue
+        JOIN virtual_keys vk ON vk.id = ue.virtual_key_id
+        WHERE ue.ts >= $1 AND ue.ts < $2
+          AND ue.forwarded = true
+          AND ue.operation_id IS NOT NULL
+        GROUP BY 1,2,3,4,5
+        ON CONFLICT (day, customer_id, virtual_key_id, partner_name, operation_id)
+        DO UPDATE SET
+          calls = EXCLUDED.calls
         "#,
         from,
         to
@@ -194,7 +328,11 @@ pub async fn query_usage_rollup(
           status_2xx,
           status_3xx,
           status_4xx,
-          status_5xx
+          status_5xx,
+          response_bytes,
+          cache_hits,
+          mocked_requests,
+          cost_units
         FROM usage_rollup_daily
         WHERE day >= $1
           AND day < $2
@@ -247,4 +385,76 @@ pub async fn query_error_rollup(
     )
     .fetch_all(db)
     .await
-}
\ No newline at end of file
+}
+pub async fn query_rpc_method_rollup(
+    db: &PgPool,
+    from_day: NaiveDate,
+    to_day: NaiveDate,
+    customer_id: Option<Uuid>,
+    virtual_key_id: Option<Uuid>,
+    partner_name: Option<&str>,
+) -> Result<Vec<RpcMethodRollupRow>, sqlx::Error> {
+    sqlx::query_as!(
+        RpcMethodRollupRow,
+        r#"
+        SELECT
+          day,
+          customer_id,
+          virtual_key_id,
+          partner_name,
+          method,
+          calls,
+          cost_units
+        FROM rpc_method_rollup_daily
+        WHERE day >= $1
+          AND day < $2
+          AND ($3::uuid IS NULL OR customer_id = $3)
+          AND ($4::uuid IS NULL OR virtual_key_id = $4)
+          AND ($5::text IS NULL OR partner_name = $5)
+        ORDER BY day DESC, cost_units DESC
+        "#,
+        from_day,
+        to_day,
+        customer_id,
+        virtual_key_id,
+        partner_name
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn query_operation_rollup(
+    db: &PgPool,
+    from_day: NaiveDate,
+    to_day: NaiveDate,
+    customer_id: Option<Uuid>,
+    virtual_key_id: Option<Uuid>,
+    partner_name: Option<&str>,
+) -> Result<Vec<OperationRollupRow>, sqlx::Error> {
+    sqlx::query_as!(
+        OperationRollupRow,
+        r#"
+        SELECT
+          day,
+          customer_id,
+          virtual_key_id,
+          partner_name,
+          operation_id,
+          calls
+        FROM operation_rollup_daily
+        WHERE day >= $1
+          AND day < $2
+          AND ($3::uuid IS NULL OR customer_id = $3)
+          AND ($4::uuid IS NULL OR virtual_key_id = $4)
+          AND ($5::text IS NULL OR partner_name = $5)
+        ORDER BY day DESC, calls DESC
+        "#,
+        from_day,
+        to_day,
+        customer_id,
+        virtual_key_id,
+        partner_name
+    )
+    .fetch_all(db)
+    .await
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Outcome of one mirrored request, as stored in `mirror_comparisons`.
+#[derive(Debug, Clone)]
+pub struct MirrorComparison {
+    pub partner_name: String,
+    pub shadow_partner: String,
+    pub virtual_key_id: Uuid,
+    pub customer_id: Uuid,
+    pub method: String,
+    pub path: String,
+
+    pub primary_status: i32,
+    pub primary_latency_ms: i32,
+    pub primary_body_sha256: Option<String>,
+
+    pub shadow_status: Option<i32>,
+    pub shadow_latency_ms: Option<i32>,
+    pub shadow_body_sha256: Option<String>,
+
+    pub error: Option<String>,
+}
+
+pub async fn insert_mirror_comparison(db: &PgPool, c: &MirrorComparison) -> Result<(), sqlx::Error> {
+    let status_match = c.shadow_status == Some(c.primary_status);
+    let body_match = c.primary_body_sha256.is_some() && c.shadow_body_sha256 == c.primary_body_sha256;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO mirror_comparisons (
+            partner_name, shadow_partner, virtual_key_id, customer_id, method, path,
+            primary_status, primary_latency_ms, primary_body_sha256,
+            shadow_status, shadow_latency_ms, shadow_body_sha256,
+            status_match, body_match, error
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        "#,
+        c.partner_name,
+        c.shadow_partner,
+        c.virtual_key_id,
+        c.customer_id,
+        c.method,
+        c.path,
+        c.primary_status,
+        c.primary_latency_ms,
+        c.primary_body_sha256,
+        c.shadow_status,
+        c.shadow_latency_ms,
+        c.shadow_body_sha256,
+        status_match,
+        body_match,
+        c.error,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct MirrorSummaryRow {
+    pub partner_name: String,
+    pub shadow_partner: String,
+    pub compared: i64,
+    pub status_mismatches: i64,
+    pub body_mismatches: i64,
+    pub shadow_errors: i64,
+    pub avg_primary_latency_ms: f64,
+    /// Over shadow calls that answered.
+    pub avg_shadow_latency_ms: Option<f64>,
+}
+
+/// Comparisons in [from, to), per partner and shadow.
+pub async fn query_mirror_summary(
+    db: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    partner_name: Option<&str>,
+) -> Result<Vec<MirrorSummaryRow>, sqlx::Error> {
+    sqlx::query_as!(
+        MirrorSummaryRow,
+        r#"
+        SELECT
+          partner_name,
+          shadow_partner,
+          COUNT(*)::bigint AS "compared!",
+          COUNT(*) FILTER (WHERE NOT status_match)::bigint AS "status_mismatches!",
+          COUNT(*) FILTER (WHERE NOT body_match)::bigint AS "body_mismatches!",
+          COUNT(*) FILTER (WHERE error IS NOT NULL)::bigint AS "shadow_errors!",
+          AVG(primary_latency_ms)::float8 AS "avg_primary_latency_ms!",
+          AVG(shadow_latency_ms)::float8 AS avg_shadow_latency_ms
+        FROM mirror_comparisons
+        WHERE ts >= $1
+          AND ts < $2
+          AND ($3::text IS NULL OR partner_name = $3)
+        GROUP BY partner_name, shadow_partner
+        ORDER BY partner_name, shadow_partner
+        "#,
+        from,
+        to,
+        partner_name
+    )
+    .fetch_all(db)
+    .await
+}
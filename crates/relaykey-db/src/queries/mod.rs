@@ -1,6 +1,8 @@
 pub mod virtual_keys;
 pub mod admin; 
+pub mod fixtures;
 pub mod policies; 
 pub mod metrics; 
+pub mod mirror;
 pub mod payment_intents; 
 pub mod x402_metrics;
\ No newline at end of file
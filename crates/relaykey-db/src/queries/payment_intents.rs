@@ -24,6 +24,7 @@ pub struct VerifiedIntentReuseRow {
     pub payment_token: Option<String>, 
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_payment_intent(
     db: &PgPool,
     virtual_key_id: Uuid,
@@ -1,6 +1,9 @@
 use sqlx::PgPool; 
 use uuid::Uuid; 
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+
+use crate::models::{CanaryRule, RedactionProfile, RouteRule};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PolicyRow{
@@ -11,6 +14,30 @@ pub struct PolicyRow{
     pub rps_burst: Option<i32>, 
     pub monthly_quota: Option<i32>, 
     pub timeout_ms: i32, 
+    /// None = gateway default.
+    #[serde(default)]
+    pub max_request_body_bytes: Option<i64>,
+    /// None = unlimited.
+    #[serde(default)]
+    pub max_response_body_bytes: Option<i64>,
+    /// None = unlimited.
+    #[serde(default)]
+    pub max_stream_duration_ms: Option<i32>,
+    /// SSE events / WebSocket messages per connection. None = unlimited.
+    #[serde(default)]
+    pub max_stream_messages: Option<i32>,
+    /// Per-route rules (caching, ...). Empty = policy defaults everywhere.
+    #[serde(default)]
+    pub routes: Json<Vec<RouteRule>>,
+    /// Response masking for keys on this policy.
+    #[serde(default)]
+    pub redaction: Json<RedactionProfile>,
+    /// Share of traffic for a partner sent to an alternate partner.
+    #[serde(default)]
+    pub canaries: Json<Vec<CanaryRule>>,
+    /// Bumped whenever `routes`, `redaction` or `canaries` changes.
+    #[serde(default)]
+    pub version: i32,
 }
 
 pub async fn get_policy_by_id(db: &PgPool, id: Uuid) -> Result<Option<PolicyRow>, sqlx::Error> {
@@ -24,7 +51,15 @@ pub async fn get_policy_by_id(db: &PgPool, id: Uuid) -> Result<Option<PolicyRow>
             rps_limit, 
             rps_burst, 
             monthly_quota,
-            timeout_ms
+            timeout_ms,
+            max_request_body_bytes,
+            max_response_body_bytes,
+            max_stream_duration_ms,
+            max_stream_messages,
+            routes as "routes: Json<Vec<RouteRule>>",
+            redaction as "redaction: Json<RedactionProfile>",
+            canaries as "canaries: Json<Vec<CanaryRule>>",
+            version
         FROM policies 
         WHERE id = $1 
         "#, 
@@ -32,4 +67,96 @@ pub async fn get_policy_by_id(db: &PgPool, id: Uuid) -> Result<Option<PolicyRow>
     )
     .fetch_optional(db)
     .await
-}
\ No newline at end of file
+}
+
+/// Replace a policy's route rules and bump its version. None = no such policy.
+pub async fn update_policy_routes(
+    db: &PgPool,
+    id: Uuid,
+    routes: &[RouteRule],
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE policies
+        SET routes = $2, version = version + 1
+        WHERE id = $1
+        RETURNING version
+        "#,
+        id,
+        Json(routes) as _
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Create the policy with this name, or replace its allowlist and route rules (bumping
+/// its version). Used for policies generated from a partner's API description.
+pub async fn upsert_generated_policy(
+    db: &PgPool,
+    name: &str,
+    endpoint_allowlist: &[String],
+    routes: &[RouteRule],
+) -> Result<(Uuid, i32), sqlx::Error> {
+    let rec = sqlx::query!(
+        r#"
+        INSERT INTO policies (name, endpoint_allowlist, routes)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE
+        SET endpoint_allowlist = EXCLUDED.endpoint_allowlist,
+            routes = EXCLUDED.routes,
+            version = policies.version + 1
+        RETURNING id, version
+        "#,
+        name,
+        endpoint_allowlist,
+        Json(routes) as _
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok((rec.id, rec.version))
+}
+
+/// Replace a policy's redaction profile and bump its version. None = no such policy.
+pub async fn update_policy_redaction(
+    db: &PgPool,
+    id: Uuid,
+    redaction: &RedactionProfile,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE policies
+        SET redaction = $2, version = version + 1
+        WHERE id = $1
+        RETURNING version
+        "#,
+        id,
+        Json(redaction) as _
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Replace a policy's canary rules and bump its version. With `expected_version`, only
+/// if the policy is still at that version. None = no such policy (or version).
+pub async fn update_policy_canaries(
+    db: &PgPool,
+    id: Uuid,
+    canaries: &[CanaryRule],
+    expected_version: Option<i32>,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE policies
+        SET canaries = $2, version = version + 1
+        WHERE id = $1
+          AND ($3::int IS NULL OR version = $3)
+        RETURNING version
+        "#,
+        id,
+        Json(canaries) as _,
+        expected_version
+    )
+    .fetch_optional(db)
+    .await
+}
@@ -1,7 +1,12 @@
-use sqlx::PgPool;
+use sqlx::{types::Json, PgPool};
 use uuid::Uuid;
 use chrono; 
 
+use crate::models::{
+    CircuitBreakerConfig, ClientIdentity, ForwardingHeaders, GraphQlConfig, HeaderRules, HedgingConfig,
+    JsonRpcConfig, MirrorConfig, PartnerTls, UpstreamAuth, UpstreamEndpoints,
+};
+
 #[derive(Debug, Clone)]
 pub struct VirtualKeyRow {
     pub id: Uuid,
@@ -16,6 +21,8 @@ pub struct VirtualKeyRow {
     pub key_hash: String, 
     pub policy_id: Uuid, 
     pub created_at: chrono::DateTime<chrono::Utc>, 
+    /// `live`, `mock` or `record`. None = the environment's default.
+    pub sandbox_mode: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,12 +30,56 @@ pub struct PartnerRow {
     pub id: Uuid,
     pub name: String,
     pub base_url: String,
+    /// May resolve to private address space (skips the SSRF resolver guard).
+    pub internal: bool,
+    pub circuit_breaker: Json<CircuitBreakerConfig>,
+    pub idempotency_header: Option<String>,
+    pub hedging: Json<HedgingConfig>,
+    pub upstream_endpoints: Json<UpstreamEndpoints>,
+    pub tls: Json<PartnerTls>,
+    pub header_rules: Json<HeaderRules>,
+    pub forwarding: Json<ForwardingHeaders>,
+    pub jsonrpc: Json<JsonRpcConfig>,
+    pub graphql: Json<GraphQlConfig>,
+    pub mirror: Json<MirrorConfig>,
 }
 
-#[derive(Debug, Clone)]
+impl PartnerRow {
+    /// Configured upstream origins in priority order (`base_url` when none are listed).
+    pub fn endpoint_urls(&self) -> Vec<String> {
+        if self.upstream_endpoints.endpoints.is_empty() {
+            return vec![self.base_url.clone()];
+        }
+
+        self.upstream_endpoints
+            .endpoints
+            .iter()
+            .map(|e| e.url.clone())
+            .collect()
+    }
+}
+
+#[derive(Clone)]
 pub struct CredentialRow {
+    pub id: Uuid,
     pub header_name: String,
+    /// The secret; never shown by `Debug`.
     pub header_value: String,
+    pub auth: Json<UpstreamAuth>,
+    /// None = the partner's TLS identity.
+    pub client_identity: Option<Json<ClientIdentity>>,
+}
+
+impl std::fmt::Debug for CredentialRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialRow")
+            .field("id", &self.id)
+            .field("header_name", &self.header_name)
+            .field("header_value", &"<redacted>")
+            .field("auth", &self.auth)
+            .field("client_identity", &self.client_identity)
+            .finish()
+    }
 }
 
 pub async fn get_virtual_key_by_hash(
@@ -50,7 +101,8 @@ pub async fn get_virtual_key_by_hash(
             monthly_quota,
             key_hash,
             policy_id,  
-            created_at 
+            created_at,
+            sandbox_mode
         FROM virtual_keys
         WHERE key_hash = $1
         "#,
@@ -69,7 +121,21 @@ pub async fn get_partner_by_name(
     let row = sqlx::query_as!(
         PartnerRow,
         r#"
-        SELECT id, name, base_url
+        SELECT
+            id,
+            name,
+            base_url,
+            internal,
+            circuit_breaker as "circuit_breaker: Json<CircuitBreakerConfig>",
+            idempotency_header,
+            hedging as "hedging: Json<HedgingConfig>",
+            upstream_endpoints as "upstream_endpoints: Json<UpstreamEndpoints>",
+            tls as "tls: Json<PartnerTls>",
+            header_rules as "header_rules: Json<HeaderRules>",
+            forwarding as "forwarding: Json<ForwardingHeaders>",
+            jsonrpc as "jsonrpc: Json<JsonRpcConfig>",
+            graphql as "graphql: Json<GraphQlConfig>",
+            mirror as "mirror: Json<MirrorConfig>"
         FROM partners
         WHERE name = $1
         "#,
@@ -88,7 +154,12 @@ pub async fn get_credential_for_partner(
     let row = sqlx::query_as!(
         CredentialRow,
         r#"
-        SELECT header_name, header_value
+        SELECT
+            id,
+            header_name,
+            header_value,
+            auth as "auth: Json<UpstreamAuth>",
+            client_identity as "client_identity: Json<ClientIdentity>"
         FROM upstream_credentials
         WHERE partner_id = $1
         ORDER BY created_at DESC
@@ -100,4 +171,32 @@ pub async fn get_credential_for_partner(
     .await?;
 
     Ok(row)
-}
\ No newline at end of file
+}
+/// Most recent credential for the partner other than `exclude_id` (used for hedged attempts).
+pub async fn get_alternate_credential_for_partner(
+    db: &PgPool,
+    partner_id: Uuid,
+    exclude_id: Uuid,
+) -> Result<Option<CredentialRow>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        CredentialRow,
+        r#"
+        SELECT
+            id,
+            header_name,
+            header_value,
+            auth as "auth: Json<UpstreamAuth>",
+            client_identity as "client_identity: Json<ClientIdentity>"
+        FROM upstream_credentials
+        WHERE partner_id = $1 AND id <> $2
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        partner_id,
+        exclude_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row)
+}
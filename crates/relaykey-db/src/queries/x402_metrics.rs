@@ -27,6 +27,7 @@ pub struct X402ErrorRollupRow {
     pub count: i64,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_x402_event(
     db: &PgPool,
     customer_id: Uuid,
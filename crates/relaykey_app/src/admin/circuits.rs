@@ -0,0 +1,24 @@
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::circuit::list_breakers;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct CircuitsQuery {
+    pub partner_name: Option<String>,
+}
+
+pub async fn admin_circuits(
+    Extension(state): Extension<Arc<AppState>>,
+    axum::extract::Query(q): axum::extract::Query<CircuitsQuery>,
+) -> impl IntoResponse {
+    match list_breakers(&state.redis, q.partner_name.as_deref()).await {
+        Ok(out) => Json(out).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "list_breakers failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::failover::list_endpoints_down;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct EndpointsQuery {
+    pub partner_name: Option<String>,
+}
+
+pub async fn admin_endpoints(
+    Extension(state): Extension<Arc<AppState>>,
+    axum::extract::Query(q): axum::extract::Query<EndpointsQuery>,
+) -> impl IntoResponse {
+    match list_endpoints_down(&state.redis, q.partner_name.as_deref()).await {
+        Ok(out) => Json(out).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "list_endpoints_down failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
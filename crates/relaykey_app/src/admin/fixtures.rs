@@ -0,0 +1,181 @@
+use axum::{
+    extract::Path,
+    http::{header::CONTENT_TYPE, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    sandbox::{body_fingerprint, normalize_query},
+    state::AppState,
+};
+
+use relaykey_db::queries::fixtures::{delete_fixture, insert_canned_fixture, list_fixtures, NewFixture};
+use relaykey_db::queries::virtual_keys::get_partner_by_name;
+
+#[derive(Deserialize)]
+pub struct CreateFixtureRequest {
+    pub method: String,
+    /// Forwarded path; a trailing `/*` matches everything below it.
+    pub path: String,
+    /// Omitted: any query matches.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Request body to match (JSON compared by value, strings byte for byte). Omitted: any body.
+    #[serde(default)]
+    pub body_match: Option<Value>,
+    #[serde(default = "default_status")]
+    pub status: i32,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Strings are sent as they are; anything else as JSON.
+    #[serde(default)]
+    pub body: Option<Value>,
+    /// Render `{customer_id}`-style placeholders in the body and header values.
+    #[serde(default)]
+    pub template: bool,
+}
+
+fn default_status() -> i32 {
+    200
+}
+
+#[derive(Serialize)]
+pub struct CreateFixtureResponse {
+    pub id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct FixtureResponse {
+    pub id: Uuid,
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body_sha256: Option<String>,
+    pub status: i32,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+    pub template: bool,
+    pub source: String,
+    pub created_at: String,
+}
+
+fn value_bytes(value: &Value) -> Vec<u8> {
+    match value {
+        Value::String(s) => s.clone().into_bytes(),
+        other => other.to_string().into_bytes(),
+    }
+}
+
+/// Store a canned response for a partner.
+pub async fn create_fixture_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(body): Json<CreateFixtureRequest>,
+) -> Response {
+    let error = if Method::from_bytes(body.method.as_bytes()).is_err()
+        || body.method != body.method.to_ascii_uppercase()
+    {
+        Some("method must be an upper-case HTTP method")
+    } else if !body.path.starts_with('/') {
+        Some("path must start with /")
+    } else if !(100..=599).contains(&body.status) {
+        Some("status must be within 100..=599")
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response();
+    }
+
+    match get_partner_by_name(&state.db, &name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_partner_by_name failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let mut headers = body.headers;
+    if matches!(body.body, Some(ref v) if !v.is_string()) {
+        headers
+            .entry(CONTENT_TYPE.as_str().to_string())
+            .or_insert_with(|| "application/json".to_string());
+    }
+
+    let fixture = NewFixture {
+        partner_name: name,
+        method: body.method,
+        path: body.path,
+        query: body.query.map(|q| normalize_query(Some(q.trim_start_matches('?')))),
+        body_sha256: body.body_match.as_ref().and_then(|v| body_fingerprint(&value_bytes(v))),
+        status: body.status,
+        headers,
+        body: body.body.as_ref().map(value_bytes).unwrap_or_default(),
+        template: body.template,
+    };
+
+    match insert_canned_fixture(&state.db, &fixture).await {
+        Ok(id) => (StatusCode::CREATED, Json(CreateFixtureResponse { id })).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "insert_canned_fixture failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Recorded and canned fixtures of a partner, newest first.
+pub async fn list_fixtures_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Response {
+    match list_fixtures(&state.db, &name, None).await {
+        Ok(rows) => {
+            let out: Vec<FixtureResponse> = rows
+                .into_iter()
+                .map(|f| FixtureResponse {
+                    id: f.id,
+                    method: f.method,
+                    path: f.path,
+                    query: f.query,
+                    body_sha256: f.body_sha256,
+                    status: f.status,
+                    headers: f.headers.0,
+                    body: String::from_utf8_lossy(&f.body).into_owned(),
+                    template: f.template,
+                    source: f.source,
+                    created_at: f.created_at.to_rfc3339(),
+                })
+                .collect();
+
+            Json(out).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "list_fixtures failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn delete_fixture_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path((name, id)): Path<(String, Uuid)>,
+) -> Response {
+    match delete_fixture(&state.db, &name, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "delete_fixture failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::state::AppState;
+use relaykey_db::queries::mirror::query_mirror_summary;
+
+#[derive(Deserialize)]
+pub struct MirrorQuery {
+    pub from: String,
+    pub to: String,
+    pub partner_name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MirrorSummaryJson {
+    pub partner_name: String,
+    pub shadow_partner: String,
+    pub compared: i64,
+    pub status_mismatches: i64,
+    pub body_mismatches: i64,
+    pub shadow_errors: i64,
+    pub avg_primary_latency_ms: f64,
+    pub avg_shadow_latency_ms: Option<f64>,
+}
+
+fn parse_day(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// How mirrored requests compared with the primary's answers, per partner and shadow.
+pub async fn admin_mirror(
+    Extension(state): Extension<Arc<AppState>>,
+    axum::extract::Query(q): axum::extract::Query<MirrorQuery>,
+) -> impl IntoResponse {
+    let Some(from) = parse_day(&q.from) else {
+        return (StatusCode::BAD_REQUEST, "invalid from (expected YYYY-MM-DD)").into_response();
+    };
+    let Some(to) = parse_day(&q.to) else {
+        return (StatusCode::BAD_REQUEST, "invalid to (expected YYYY-MM-DD)").into_response();
+    };
+
+    let rows = match query_mirror_summary(
+        &state.db,
+        from.and_time(Default::default()).and_utc(),
+        to.and_time(Default::default()).and_utc(),
+        q.partner_name.as_deref(),
+    )
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = %e, "query_mirror_summary failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let out: Vec<MirrorSummaryJson> = rows
+        .into_iter()
+        .map(|r| MirrorSummaryJson {
+            partner_name: r.partner_name,
+            shadow_partner: r.shadow_partner,
+            compared: r.compared,
+            status_mismatches: r.status_mismatches,
+            body_mismatches: r.body_mismatches,
+            shadow_errors: r.shadow_errors,
+            avg_primary_latency_ms: r.avg_primary_latency_ms,
+            avg_shadow_latency_ms: r.avg_shadow_latency_ms,
+        })
+        .collect();
+
+    Json(out).into_response()
+}
@@ -1,4 +1,10 @@
+pub mod circuits;
+pub mod endpoints;
 pub mod errors;
+pub mod fixtures;
 pub mod keygen;
+pub mod mirror;
+pub mod partners;
+pub mod policies;
 pub mod usage;
 pub mod virtual_keys;
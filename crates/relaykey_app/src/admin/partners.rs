@@ -0,0 +1,184 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    openapi::{import, ImportError},
+    policies::cache::invalidate_policy,
+    ssrf::validate_base_url,
+    state::AppState,
+    tls,
+};
+
+use relaykey_db::models::{HedgingConfig, PartnerTls, UpstreamEndpoints};
+use relaykey_db::queries::admin::{get_partner_openapi_spec, list_partners, upsert_partner};
+use relaykey_db::queries::virtual_keys::get_partner_by_name;
+
+#[derive(Deserialize)]
+pub struct UpsertPartnerRequest {
+    pub name: String,
+    pub base_url: String,
+    /// Partners on the private network; skips the SSRF address checks.
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub upstream_endpoints: Option<UpstreamEndpoints>,
+    /// mTLS identity, custom CA and pinning. Omitted: left as they are.
+    #[serde(default)]
+    pub tls: Option<PartnerTls>,
+    /// Request hedging. Omitted: left as it is.
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+}
+
+#[derive(Serialize)]
+pub struct UpsertPartnerResponse {
+    pub id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct PartnerResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub base_url: String,
+    pub internal: bool,
+    pub upstream_endpoints: UpstreamEndpoints,
+}
+
+/// Create or update a partner by name. Origins are validated before anything is stored.
+pub async fn upsert_partner_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Json(body): Json<UpsertPartnerRequest>,
+) -> Response {
+    let endpoints = body.upstream_endpoints.unwrap_or_default();
+
+    let hedge_origin = body.hedging.as_ref().and_then(|h| h.base_url.as_deref());
+    let origins = std::iter::once(body.base_url.as_str())
+        .chain(endpoints.endpoints.iter().map(|e| e.url.as_str()))
+        .chain(hedge_origin);
+    for raw in origins {
+        if let Err(message) = validate_base_url(raw, body.internal).await {
+            return (StatusCode::UNPROCESSABLE_ENTITY, message).into_response();
+        }
+    }
+
+    if let Some(Err(message)) = body.tls.as_ref().map(tls::validate) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, message).into_response();
+    }
+
+    match upsert_partner(
+        &state.db,
+        &body.name,
+        &body.base_url,
+        body.internal,
+        &endpoints,
+        body.tls.as_ref(),
+        body.hedging.as_ref(),
+    )
+    .await
+    {
+        Ok(id) => (StatusCode::CREATED, Json(UpsertPartnerResponse { id })).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "upsert_partner failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn list_partners_handler(Extension(state): Extension<Arc<AppState>>) -> Response {
+    match list_partners(&state.db).await {
+        Ok(partners) => {
+            let out: Vec<PartnerResponse> = partners
+                .into_iter()
+                .map(|p| PartnerResponse {
+                    id: p.id,
+                    name: p.name,
+                    base_url: p.base_url,
+                    internal: p.internal,
+                    upstream_endpoints: p.upstream_endpoints.0,
+                })
+                .collect();
+
+            Json(out).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "list_partners failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct OpenApiSpecResponse {
+    pub title: Option<String>,
+    pub spec_version: Option<String>,
+    pub operations: i32,
+    pub imported_at: String,
+    pub spec: serde_json::Value,
+}
+
+/// Store a partner's OpenAPI 3 document (JSON) and regenerate its draft policy.
+pub async fn import_openapi_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(spec): Json<serde_json::Value>,
+) -> Response {
+    let partner = match get_partner_by_name(&state.db, &name).await {
+        Ok(Some(p)) => p,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_partner_by_name failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match import(&state.db, partner.id, &partner.name, &spec).await {
+        Ok(outcome) => {
+            invalidate_policy(&state.redis, outcome.policy_id).await;
+            (StatusCode::CREATED, Json(outcome)).into_response()
+        }
+        Err(e @ ImportError::InvalidSpec(_)) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, partner = %partner.name, "openapi import failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn get_openapi_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Response {
+    let partner = match get_partner_by_name(&state.db, &name).await {
+        Ok(Some(p)) => p,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_partner_by_name failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match get_partner_openapi_spec(&state.db, partner.id).await {
+        Ok(Some(row)) => Json(OpenApiSpecResponse {
+            title: row.title,
+            spec_version: row.spec_version,
+            operations: row.operations,
+            imported_at: row.imported_at.to_rfc3339(),
+            spec: row.spec,
+        })
+        .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_partner_openapi_spec failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
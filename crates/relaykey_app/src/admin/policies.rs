@@ -0,0 +1,183 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    canary::first_invalid_rule, policies::cache::invalidate_policy, redaction,
+    request_schema::first_invalid_schema, state::AppState,
+};
+
+use relaykey_db::models::{CanaryRule, RedactionProfile, RouteRule};
+use relaykey_db::queries::policies::{
+    get_policy_by_id, update_policy_canaries, update_policy_redaction, update_policy_routes,
+};
+use relaykey_db::queries::virtual_keys::get_partner_by_name;
+
+#[derive(Serialize)]
+pub struct PolicyRoutesResponse {
+    pub version: i32,
+    pub routes: Vec<RouteRule>,
+}
+
+#[derive(Serialize)]
+pub struct PolicyRedactionResponse {
+    pub version: i32,
+    pub redaction: RedactionProfile,
+}
+
+#[derive(Serialize)]
+pub struct PolicyCanariesResponse {
+    pub version: i32,
+    pub canaries: Vec<CanaryRule>,
+}
+
+pub async fn get_policy_routes_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+) -> Response {
+    match get_policy_by_id(&state.db, policy_id).await {
+        Ok(Some(policy)) => Json(PolicyRoutesResponse {
+            version: policy.version,
+            routes: policy.routes.0,
+        })
+        .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_policy_by_id failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Replace a policy's route rules. Every `request_schema` must compile; the new version
+/// takes effect on the next request (schemas are recompiled once for it).
+pub async fn put_policy_routes_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+    Json(routes): Json<Vec<RouteRule>>,
+) -> Response {
+    if let Some((index, error)) = first_invalid_schema(&routes) {
+        let body = json!({ "route": index, "error": format!("invalid request_schema: {error}") });
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+    }
+
+    match update_policy_routes(&state.db, policy_id, &routes).await {
+        Ok(Some(version)) => {
+            invalidate_policy(&state.redis, policy_id).await;
+            Json(PolicyRoutesResponse { version, routes }).into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "update_policy_routes failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn get_policy_redaction_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+) -> Response {
+    match get_policy_by_id(&state.db, policy_id).await {
+        Ok(Some(policy)) => Json(PolicyRedactionResponse {
+            version: policy.version,
+            redaction: policy.redaction.0,
+        })
+        .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_policy_by_id failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Replace a policy's redaction profile. Paths, patterns and builtin names are checked
+/// first; `{}` turns redaction off.
+pub async fn put_policy_redaction_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+    Json(redaction): Json<RedactionProfile>,
+) -> Response {
+    if let Err(error) = redaction::compile(&redaction) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "error": error }))).into_response();
+    }
+
+    match update_policy_redaction(&state.db, policy_id, &redaction).await {
+        Ok(Some(version)) => {
+            invalidate_policy(&state.redis, policy_id).await;
+            Json(PolicyRedactionResponse { version, redaction }).into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "update_policy_redaction failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn get_policy_canaries_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+) -> Response {
+    match get_policy_by_id(&state.db, policy_id).await {
+        Ok(Some(policy)) => Json(PolicyCanariesResponse {
+            version: policy.version,
+            canaries: policy.canaries.0,
+        })
+        .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "get_policy_by_id failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Replace a policy's canary rules. Both partners of every rule must exist. Re-enabling a
+/// rolled-back canary is a matter of storing it again with a new `percent`.
+pub async fn put_policy_canaries_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(policy_id): Path<Uuid>,
+    Json(canaries): Json<Vec<CanaryRule>>,
+) -> Response {
+    if let Some((index, error)) = first_invalid_rule(&canaries) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "rule": index, "error": error })))
+            .into_response();
+    }
+
+    for (index, rule) in canaries.iter().enumerate() {
+        for name in [&rule.partner, &rule.alternate] {
+            match get_partner_by_name(&state.db, name).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    let body = json!({ "rule": index, "error": format!("unknown partner: {name}") });
+                    return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "get_partner_by_name failed");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        }
+    }
+
+    match update_policy_canaries(&state.db, policy_id, &canaries, None).await {
+        Ok(Some(version)) => {
+            invalidate_policy(&state.redis, policy_id).await;
+            Json(PolicyCanariesResponse { version, canaries }).into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "update_policy_canaries failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -6,7 +6,10 @@ use uuid::Uuid;
 
 use crate::state::AppState;
 use relaykey_db::queries::{
-    metrics::{UsageRollupRow, query_usage_rollup},
+    metrics::{
+        OperationRollupRow, RpcMethodRollupRow, UsageRollupRow, query_operation_rollup,
+        query_rpc_method_rollup, query_usage_rollup,
+    },
     x402_metrics::{X402UsageRollupRow, query_x402_usage_rollup},
 };
 
@@ -37,6 +40,15 @@ pub struct UsageRollupJson {
     pub status_4xx: i64,
     pub status_5xx: i64,
 
+    pub response_bytes: i64,
+    pub cache_hits: i64,
+    pub mocked_requests: i64,
+    pub cost_units: i64,
+    /// Forwarded JSON-RPC calls by method (JSON-RPC partners only).
+    pub rpc_methods: Vec<RpcMethodUsageJson>,
+    /// Forwarded requests by partner operation (routes tagged with `operation_id`).
+    pub operations: Vec<OperationUsageJson>,
+
     pub x402_intents_created: i64,
     pub x402_verified_count: i64,
     pub x402_unpaid_count: i64,
@@ -48,6 +60,22 @@ pub struct UsageRollupJson {
     // pub x402_conversion_rate: f64,
 }
 
+#[derive(Serialize)]
+pub struct RpcMethodUsageJson {
+    pub method: String,
+    pub calls: i64,
+    pub cost_units: i64,
+}
+
+#[derive(Serialize)]
+pub struct OperationUsageJson {
+    pub operation_id: String,
+    pub calls: i64,
+}
+
+type RollupKey = (String, Uuid, Uuid, String);
+type X402Counts = (i64, i64, i64, i64, i64);
+
 fn parse_day(s: &str) -> Result<NaiveDate, ()> {
     NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| ())
 }
@@ -108,10 +136,56 @@ pub async fn admin_usage(
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
+    let rpc_rows: Vec<RpcMethodRollupRow> = match query_rpc_method_rollup(
+        &state.db,
+        from_day,
+        to_day,
+        q.customer_id,
+        q.virtual_key_id,
+        partner,
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut rpc_map: HashMap<RollupKey, Vec<RpcMethodUsageJson>> = HashMap::new();
+    for r in rpc_rows {
+        let key = (r.day.to_string(), r.customer_id, r.virtual_key_id, r.partner_name);
+        rpc_map.entry(key).or_default().push(RpcMethodUsageJson {
+            method: r.method,
+            calls: r.calls,
+            cost_units: r.cost_units,
+        });
+    }
+
+    let operation_rows: Vec<OperationRollupRow> = match query_operation_rollup(
+        &state.db,
+        from_day,
+        to_day,
+        q.customer_id,
+        q.virtual_key_id,
+        partner,
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut operation_map: HashMap<RollupKey, Vec<OperationUsageJson>> = HashMap::new();
+    for r in operation_rows {
+        let key = (r.day.to_string(), r.customer_id, r.virtual_key_id, r.partner_name);
+        operation_map.entry(key).or_default().push(OperationUsageJson {
+            operation_id: r.operation_id,
+            calls: r.calls,
+        });
+    }
+
     // key = (day, customer_id, virtual_key_id, partner_name)
     // value = (intents_created, verified_count, unpaid_count, failed_count, expired_count)
-    let mut x402_map: HashMap<(String, Uuid, Uuid, String), (i64, i64, i64, i64, i64)> =
-        HashMap::new();
+    let mut x402_map: HashMap<RollupKey, X402Counts> = HashMap::new();
 
     for r in x402_rows {
         let key = (
@@ -149,6 +223,8 @@ pub async fn admin_usage(
                 .get(&key)
                 .copied()
                 .unwrap_or((0, 0, 0, 0, 0));
+            let rpc_methods = rpc_map.remove(&key).unwrap_or_default();
+            let operations = operation_map.remove(&key).unwrap_or_default();
 
             UsageRollupJson {
                 day: r.day.to_string(),
@@ -163,6 +239,12 @@ pub async fn admin_usage(
                 status_3xx: r.status_3xx,
                 status_4xx: r.status_4xx,
                 status_5xx: r.status_5xx,
+                response_bytes: r.response_bytes,
+                cache_hits: r.cache_hits,
+                mocked_requests: r.mocked_requests,
+                cost_units: r.cost_units,
+                rpc_methods,
+                operations,
                 x402_intents_created,
                 x402_verified_count,
                 x402_unpaid_count,
@@ -1,4 +1,5 @@
 use axum::{
+    extract::Path,
     http::StatusCode,
     response::{IntoResponse, Response},
     Extension, Json,
@@ -7,10 +8,13 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::sandbox::SandboxMode;
 use crate::state::AppState;
 
 use relaykey_core::crypto::key_hash::hash_virtual_key;
-use relaykey_db::queries::admin::{insert_virtual_key, list_virtual_keys};
+use relaykey_db::queries::admin::{
+    insert_virtual_key, list_virtual_keys, update_virtual_key_sandbox_mode,
+};
 
 #[derive(Deserialize)]
 pub struct CreateVirtualKeyRequest {
@@ -20,6 +24,15 @@ pub struct CreateVirtualKeyRequest {
     pub tags: Vec<String>,
     pub policy_id: Uuid,
     pub customer_id: Uuid,
+    /// `live`, `mock` or `record`. Omitted: mock in RELAYKEY_MOCK_ENVIRONMENTS, else live.
+    #[serde(default)]
+    pub sandbox_mode: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SandboxModeRequest {
+    /// null clears the key's mode, so its environment decides.
+    pub mode: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -36,6 +49,7 @@ pub struct VirtualKeyResponse {
     pub tags: Vec<String>,
     pub enabled: bool,
     pub policy_id: Uuid,
+    pub sandbox_mode: Option<String>,
     pub created_at: String, // Display-based (works across chrono/time)
 }
 
@@ -43,6 +57,10 @@ pub async fn create_virtual_key(
     Extension(state): Extension<Arc<AppState>>,
     Json(body): Json<CreateVirtualKeyRequest>,
 ) -> Response {
+    if let Some(resp) = body.sandbox_mode.as_deref().and_then(invalid_sandbox_mode) {
+        return resp;
+    }
+
     // Make the raw key environment-aware (nice for debugging/tests)
     let raw_key = format!("rk_{}_{}", body.environment, Uuid::new_v4());
 
@@ -71,6 +89,13 @@ pub async fn create_virtual_key(
         }
     };
 
+    if let Some(mode) = body.sandbox_mode.as_deref() {
+        if let Err(e) = update_virtual_key_sandbox_mode(&state.db, id, Some(mode)).await {
+            tracing::error!(error = %e, "update_virtual_key_sandbox_mode failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
     (
         StatusCode::CREATED,
         Json(CreateVirtualKeyResponse { id, key: raw_key }),
@@ -90,6 +115,7 @@ pub async fn list_virtual_keys_handler(Extension(state): Extension<Arc<AppState>
                     tags: k.tags,
                     enabled: k.enabled,
                     policy_id: k.policy_id,
+                    sandbox_mode: k.sandbox_mode,
                     created_at: k.created_at.to_string(),
                 })
                 .collect();
@@ -102,3 +128,33 @@ pub async fn list_virtual_keys_handler(Extension(state): Extension<Arc<AppState>
         }
     }
 }
+
+fn invalid_sandbox_mode(mode: &str) -> Option<Response> {
+    SandboxMode::parse(mode).is_none().then(|| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "error": "sandbox mode must be live, mock or record" })),
+        )
+            .into_response()
+    })
+}
+
+/// Pin a key to live, mock or record mode, or clear it (`null`) to follow its environment.
+pub async fn put_sandbox_mode_handler(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(body): Json<SandboxModeRequest>,
+) -> Response {
+    if let Some(resp) = body.mode.as_deref().and_then(invalid_sandbox_mode) {
+        return resp;
+    }
+
+    match update_virtual_key_sandbox_mode(&state.db, id, body.mode.as_deref()).await {
+        Ok(true) => Json(serde_json::json!({ "id": id, "sandbox_mode": body.mode })).into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "update_virtual_key_sandbox_mode failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -1,19 +1,21 @@
 use axum::{
     extract::DefaultBodyLimit,
     middleware,
-    routing::{any, get, post},
+    routing::{any, delete, get, post, put},
     Router,
 };
 use std::sync::Arc; 
 
 use crate::{
     auth::{require_admin, require_virtual_key},
-    admin::{virtual_keys, usage, errors},
+    coalesce::middleware::coalesce_requests,
+    admin::{circuits, endpoints, fixtures, mirror, partners, policies as admin_policies, virtual_keys, usage, errors},
     health,
     limits::middleware::enforce_limits,
     metrics,
     proxy,
     policies::allowlist::enforce_allowlist,
+    response_cache::middleware::serve_cached,
     x402::{
         noop::NoopProvider,
         stub::StubProvider, 
@@ -24,7 +26,7 @@ use crate::{
 pub fn build_router() -> Router<()> {
     let provider_registry = Arc::new(
         ProviderRegistry::new()
-            .register("noop", Arc::new(NoopProvider::default()))
+            .register("noop", Arc::new(NoopProvider))
             .register("stub", Arc::new(StubProvider))
     );
 
@@ -35,8 +37,10 @@ pub fn build_router() -> Router<()> {
 
     let protected = Router::new()
         .route("/proxy/:partner/*tail", any(proxy::handler))
+        .route_layer(middleware::from_fn(coalesce_requests))
         .route_layer(middleware::from_fn(crate::x402::middleware::enforce_x402))
         .route_layer(middleware::from_fn(enforce_limits))
+        .route_layer(middleware::from_fn(serve_cached))
         .route_layer(middleware::from_fn(enforce_allowlist))
         .route_layer(middleware::from_fn(require_virtual_key));
 
@@ -46,8 +50,46 @@ pub fn build_router() -> Router<()> {
             post(virtual_keys::create_virtual_key)
                 .get(virtual_keys::list_virtual_keys_handler),
         )
+        .route(
+            "/admin/partners",
+            post(partners::upsert_partner_handler).get(partners::list_partners_handler),
+        )
+        .route(
+            "/admin/virtual-keys/:id/sandbox",
+            put(virtual_keys::put_sandbox_mode_handler),
+        )
+        .route(
+            "/admin/partners/:name/fixtures",
+            post(fixtures::create_fixture_handler).get(fixtures::list_fixtures_handler),
+        )
+        .route(
+            "/admin/partners/:name/fixtures/:id",
+            delete(fixtures::delete_fixture_handler),
+        )
+        .route(
+            "/admin/partners/:name/openapi",
+            post(partners::import_openapi_handler).get(partners::get_openapi_handler),
+        )
+        .route(
+            "/admin/policies/:id/routes",
+            get(admin_policies::get_policy_routes_handler)
+                .put(admin_policies::put_policy_routes_handler),
+        )
+        .route(
+            "/admin/policies/:id/redaction",
+            get(admin_policies::get_policy_redaction_handler)
+                .put(admin_policies::put_policy_redaction_handler),
+        )
+        .route(
+            "/admin/policies/:id/canaries",
+            get(admin_policies::get_policy_canaries_handler)
+                .put(admin_policies::put_policy_canaries_handler),
+        )
         .route("/admin/usage", get(usage::admin_usage))
         .route("/admin/errors", get(errors::admin_errors))
+        .route("/admin/circuits", get(circuits::admin_circuits))
+        .route("/admin/endpoints", get(endpoints::admin_endpoints))
+        .route("/admin/mirror", get(mirror::admin_mirror))
         .route_layer(middleware::from_fn(require_admin));
 
     public
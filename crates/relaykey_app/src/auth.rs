@@ -27,6 +27,8 @@ pub struct VirtualKeyCtx {
     pub policy_id: Uuid,
     pub policy: PolicyRow,
     pub customer_id: Uuid,
+    /// `live`, `mock` or `record`; None = the environment's default.
+    pub sandbox_mode: Option<String>,
 }
 
 pub async fn require_virtual_key(
@@ -86,6 +88,7 @@ pub async fn require_virtual_key(
         monthly_quota: policy.monthly_quota,
         policy_id,
         policy,
+        sandbox_mode: vk.sandbox_mode,
     });
 
     next.run(req).await
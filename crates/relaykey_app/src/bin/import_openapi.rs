@@ -0,0 +1,50 @@
+use relaykey_app::{openapi::import, policies::cache::invalidate_policy, settings::Settings};
+use relaykey_db::{init_db, queries::virtual_keys::get_partner_by_name};
+
+fn arg_value(args: &[String], key: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == key)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    dotenvy::dotenv().ok();
+    let settings = Settings::from_env()?;
+
+    // Usage:
+    // cargo run -p relaykey-app --bin import_openapi -- --partner pricing --file openapi.json
+    let args: Vec<String> = std::env::args().collect();
+    let partner_name =
+        arg_value(&args, "--partner").ok_or_else(|| "missing --partner NAME".to_string())?;
+    let file = arg_value(&args, "--file").ok_or_else(|| "missing --file PATH".to_string())?;
+
+    let raw = std::fs::read(&file).map_err(|e| format!("failed to read {file}: {e}"))?;
+    let spec: serde_json::Value =
+        serde_json::from_slice(&raw).map_err(|e| format!("{file} is not a JSON document: {e}"))?;
+
+    let db = init_db(&settings.database_url)
+        .await
+        .map_err(|e| format!("DB init failed: {e}"))?;
+
+    let partner = get_partner_by_name(&db, &partner_name)
+        .await
+        .map_err(|e| format!("partner lookup failed: {e}"))?
+        .ok_or_else(|| format!("unknown partner: {partner_name}"))?;
+
+    let outcome = import(&db, partner.id, &partner.name, &spec)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Best-effort: gateways otherwise pick the new version up when their cached copy expires.
+    if let Ok(redis) = redis::Client::open(settings.redis_url.as_str()) {
+        invalidate_policy(&redis, outcome.policy_id).await;
+    }
+
+    println!(
+        "imported {} operations into policy {} ({}, version {})",
+        outcome.operations, outcome.policy_name, outcome.policy_id, outcome.policy_version
+    );
+    Ok(())
+}
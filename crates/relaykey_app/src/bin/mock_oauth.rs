@@ -0,0 +1,111 @@
+//! Local OAuth2 client-credentials partner for trying out token credentials.
+//!
+//! POST /oauth/token   grant_type=client_credentials (any client_id, secret "secret")
+//! ANY  /api/*         200 with a valid bearer token, 401 otherwise
+//!
+//! Tokens expire after MOCK_TOKEN_TTL_SECS (default 120) so refreshes and
+//! 401-driven retries can be observed. Listens on MOCK_OAUTH_ADDR (default 127.0.0.1:9400).
+
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{any, post},
+    Form, Json, Router,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct Tokens {
+    issued: Mutex<HashMap<String, Instant>>,
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    client_secret: Option<String>,
+}
+
+fn token_ttl() -> Duration {
+    let secs = std::env::var("MOCK_TOKEN_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(120);
+    Duration::from_secs(secs)
+}
+
+async fn issue(
+    State(tokens): State<Arc<Tokens>>,
+    headers: HeaderMap,
+    Form(req): Form<TokenRequest>,
+) -> impl IntoResponse {
+    // client_secret_basic sends the secret in Authorization instead of the form
+    let has_basic = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("Basic "));
+    let secret_ok = req.client_secret.as_deref() == Some("secret") || has_basic;
+
+    if req.grant_type != "client_credentials" || !secret_ok {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid_client" })));
+    }
+
+    let ttl = token_ttl();
+    let token = Uuid::new_v4().to_string();
+    if let Ok(mut issued) = tokens.issued.lock() {
+        issued.insert(token.clone(), Instant::now() + ttl);
+    }
+    println!("issued token {token}");
+
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "access_token": token,
+            "token_type": "Bearer",
+            "expires_in": ttl.as_secs(),
+        })),
+    )
+}
+
+async fn protected(State(tokens): State<Arc<Tokens>>, headers: HeaderMap) -> StatusCode {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let valid = token.is_some_and(|t| {
+        tokens
+            .issued
+            .lock()
+            .ok()
+            .and_then(|issued| issued.get(t).copied())
+            .is_some_and(|expires| expires > Instant::now())
+    });
+
+    if valid {
+        StatusCode::OK
+    } else {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let addr = std::env::var("MOCK_OAUTH_ADDR").unwrap_or_else(|_| "127.0.0.1:9400".to_string());
+
+    let app = Router::new()
+        .route("/oauth/token", post(issue))
+        .route("/api/*rest", any(protected))
+        .with_state(Arc::new(Tokens::default()));
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!("mock oauth partner on http://{addr}");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -46,6 +46,14 @@ async fn main() -> Result<(), String> {
         .await
         .map_err(|e| format!("error rollup failed: {e}"))?;
 
+    relaykey_db::queries::metrics::rollup_rpc_method_daily(&db, from, to)
+        .await
+        .map_err(|e| format!("rpc method rollup failed: {e}"))?;
+
+    relaykey_db::queries::metrics::rollup_operation_daily(&db, from, to)
+        .await
+        .map_err(|e| format!("operation rollup failed: {e}"))?;
+
         relaykey_db::queries::x402_metrics::rollup_x402_usage_daily(&db, from, to)
         .await
         .map_err(|e| format!("x402 usage rollup failed: {e}"))?;
@@ -0,0 +1,177 @@
+use chrono::Utc;
+use redis::Script;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use relaykey_db::{
+    models::{CanaryRule, CanarySticky},
+    queries::policies::{get_policy_by_id, update_policy_canaries, PolicyRow},
+};
+
+use crate::auth::VirtualKeyCtx;
+use crate::policies::cache::invalidate_policy;
+use crate::state::AppState;
+
+const CANARY_PREFIX: &str = "rk:canary:";
+
+/// Key: rk:canary:{policy_id}:{partner}:{alternate}
+fn canary_key(policy_id: Uuid, rule: &CanaryRule) -> String {
+    format!("{CANARY_PREFIX}{policy_id}:{}:{}", rule.partner, rule.alternate)
+}
+
+/// Stable bucket in 0..10_000 for a customer or key. Independent of `percent`, so
+/// raising it only ever adds callers to the canary.
+fn bucket(policy_id: Uuid, partner: &str, sticky_id: Uuid) -> u64 {
+    let digest = Sha256::digest(format!("{policy_id}:{partner}:{sticky_id}").as_bytes());
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(head) % 10_000
+}
+
+/// The canary serving this caller's requests for `partner`, if any.
+pub fn route<'a>(policy: &'a PolicyRow, partner: &str, vk: &VirtualKeyCtx) -> Option<&'a CanaryRule> {
+    let rule = policy.canaries.iter().find(|c| c.partner == partner)?;
+    let sticky_id = match rule.sticky {
+        CanarySticky::Customer => vk.customer_id,
+        CanarySticky::Key => vk.id,
+    };
+    let cutoff = (rule.percent.clamp(0.0, 100.0) * 100.0).round() as u64;
+    (bucket(policy.id, partner, sticky_id) < cutoff).then_some(rule)
+}
+
+/// First rule that can't be stored, with the reason. Partners must exist (checked by the caller).
+pub fn first_invalid_rule(rules: &[CanaryRule]) -> Option<(usize, String)> {
+    let mut seen = HashSet::new();
+    rules.iter().enumerate().find_map(|(i, r)| {
+        let error = if r.partner.is_empty() || r.alternate.is_empty() {
+            "partner and alternate are required"
+        } else if r.partner == r.alternate {
+            "alternate must differ from partner"
+        } else if !seen.insert(r.partner.as_str()) {
+            "one canary per partner"
+        } else if !(0.0..=100.0).contains(&r.percent) {
+            "percent must be within 0..=100"
+        } else if !(0.0..=1.0).contains(&r.max_error_rate) {
+            "max_error_rate must be within 0..=1"
+        } else {
+            return None;
+        };
+        Some((i, error.to_string()))
+    })
+}
+
+/// Record one attempt against the alternate. When its error rate over the window reaches
+/// `max_error_rate`, the canary is rolled back. Best-effort: Redis errors are logged and ignored.
+pub async fn canary_record(state: &AppState, policy_id: Uuid, rule: Option<&CanaryRule>, success: bool) {
+    static LUA: &str = r#"
+local key = KEYS[1]
+local now_ms = tonumber(ARGV[1])
+local success = tonumber(ARGV[2])
+local error_rate = tonumber(ARGV[3])
+local min_requests = tonumber(ARGV[4])
+local window_ms = tonumber(ARGV[5])
+
+local data = redis.call("HMGET", key, "window_start_ms", "total", "failures")
+local window_start = tonumber(data[1]) or now_ms
+local total = tonumber(data[2]) or 0
+local failures = tonumber(data[3]) or 0
+
+if now_ms - window_start >= window_ms then
+  window_start = now_ms
+  total = 0
+  failures = 0
+end
+
+total = total + 1
+if success == 0 then
+  failures = failures + 1
+end
+
+-- Tripping starts a fresh window, so one breach rolls back once.
+local tripped = 0
+if total >= min_requests and failures / total >= error_rate then
+  tripped = 1
+  window_start = now_ms
+  total = 0
+  failures = 0
+end
+
+redis.call("HSET", key, "window_start_ms", window_start, "total", total, "failures", failures)
+redis.call("PEXPIRE", key, window_ms * 2)
+return tripped
+"#;
+
+    let Some(rule) = rule else {
+        return;
+    };
+
+    let mut conn = match state.redis.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %rule.alternate, "canary: redis unavailable");
+            return;
+        }
+    };
+
+    let tripped: redis::RedisResult<i64> = Script::new(LUA)
+        .key(canary_key(policy_id, rule))
+        .arg(Utc::now().timestamp_millis())
+        .arg(if success { 1 } else { 0 })
+        .arg(rule.max_error_rate)
+        .arg(rule.min_requests.max(1))
+        .arg(rule.window_secs.max(1).saturating_mul(1000))
+        .invoke_async(&mut conn)
+        .await;
+
+    match tripped {
+        Ok(1) => roll_back(state, policy_id, rule).await,
+        Ok(_) => {}
+        Err(e) => tracing::warn!(error = %e, partner = %rule.alternate, "canary record failed"),
+    }
+}
+
+/// Set the canary's share to 0% in the stored policy.
+async fn roll_back(state: &AppState, policy_id: Uuid, rule: &CanaryRule) {
+    tracing::warn!(
+        policy_id = %policy_id,
+        partner = %rule.partner,
+        alternate = %rule.alternate,
+        max_error_rate = rule.max_error_rate,
+        "canary error rate exceeded; rolling back"
+    );
+
+    // Retried once if the policy changed under us.
+    for _ in 0..2 {
+        let policy = match get_policy_by_id(&state.db, policy_id).await {
+            Ok(Some(p)) => p,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!(error = %e, policy_id = %policy_id, "canary rollback: policy lookup failed");
+                return;
+            }
+        };
+
+        let mut canaries = policy.canaries.0;
+        let Some(stored) = canaries
+            .iter_mut()
+            .find(|c| c.partner == rule.partner && c.alternate == rule.alternate && c.percent > 0.0)
+        else {
+            return;
+        };
+        stored.percent = 0.0;
+        stored.rolled_back_at = Some(Utc::now());
+
+        match update_policy_canaries(&state.db, policy_id, &canaries, Some(policy.version)).await {
+            Ok(Some(_)) => {
+                invalidate_policy(&state.redis, policy_id).await;
+                return;
+            }
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::error!(error = %e, policy_id = %policy_id, "canary rollback failed");
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use redis::{AsyncCommands, Script};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+use relaykey_db::models::CircuitBreakerConfig;
+
+const BREAKER_PREFIX: &str = "rk:cb:";
+/// Idle breakers disappear after a day; an untouched breaker is a closed one.
+const BREAKER_TTL_SECS: i64 = 60 * 60 * 24;
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// Key: rk:cb:{partner}:{credential_id}
+fn breaker_key(partner_name: &str, credential_id: Uuid) -> String {
+    format!("{BREAKER_PREFIX}{partner_name}:{credential_id}")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerDecision {
+    /// Closed: send normally.
+    Allow,
+    /// Half-open: this request is the single probe.
+    Probe,
+    /// Open (or a probe is already in flight).
+    Reject { retry_after: Duration },
+}
+
+/// Gate an upstream call on the (partner, credential) breaker.
+/// Fail-open: Redis errors allow the request.
+pub async fn breaker_check(
+    redis_client: &redis::Client,
+    partner_name: &str,
+    credential_id: Uuid,
+    cfg: &CircuitBreakerConfig,
+) -> BreakerDecision {
+    static LUA: &str = r#"
+local key = KEYS[1]
+local now_ms = tonumber(ARGV[1])
+local open_ms = tonumber(ARGV[2])
+local ttl = tonumber(ARGV[3])
+
+local data = redis.call("HMGET", key, "state", "opened_at_ms", "probe_at_ms")
+local state = data[1] or "closed"
+local opened_at = tonumber(data[2]) or 0
+local probe_at = tonumber(data[3]) or 0
+
+if state == "closed" then
+  return {0, 0}
+end
+
+if state == "open" then
+  local wait = opened_at + open_ms - now_ms
+  if wait > 0 then
+    return {2, wait}
+  end
+  redis.call("HSET", key, "state", "half_open", "probe_at_ms", now_ms)
+  redis.call("EXPIRE", key, ttl)
+  return {1, 0}
+end
+
+-- half_open: one probe at a time; a probe that never reported back is
+-- replaced after another open period.
+local wait = probe_at + open_ms - now_ms
+if wait > 0 then
+  return {2, wait}
+end
+redis.call("HSET", key, "probe_at_ms", now_ms)
+redis.call("EXPIRE", key, ttl)
+return {1, 0}
+"#;
+
+    if !cfg.enabled {
+        return BreakerDecision::Allow;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "circuit breaker: redis unavailable (fail-open)");
+            return BreakerDecision::Allow;
+        }
+    };
+
+    let result: redis::RedisResult<(i64, i64)> = Script::new(LUA)
+        .key(breaker_key(partner_name, credential_id))
+        .arg(now_ms())
+        .arg(cfg.open_secs.saturating_mul(1000))
+        .arg(BREAKER_TTL_SECS)
+        .invoke_async(&mut conn)
+        .await;
+
+    match result {
+        Ok((0, _)) => BreakerDecision::Allow,
+        Ok((1, _)) => BreakerDecision::Probe,
+        Ok((_, wait_ms)) => BreakerDecision::Reject {
+            retry_after: Duration::from_millis(wait_ms.max(0) as u64),
+        },
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "circuit breaker check failed (fail-open)");
+            BreakerDecision::Allow
+        }
+    }
+}
+
+/// Record one upstream attempt outcome and trip/close the breaker as needed.
+/// Best-effort: Redis errors are logged and ignored.
+pub async fn breaker_record(
+    redis_client: &redis::Client,
+    partner_name: &str,
+    credential_id: Uuid,
+    cfg: &CircuitBreakerConfig,
+    success: bool,
+) {
+    static LUA: &str = r#"
+local key = KEYS[1]
+local now_ms = tonumber(ARGV[1])
+local success = tonumber(ARGV[2])
+local max_consecutive = tonumber(ARGV[3])
+local error_rate = tonumber(ARGV[4])
+local min_requests = tonumber(ARGV[5])
+local window_ms = tonumber(ARGV[6])
+local ttl = tonumber(ARGV[7])
+
+local data = redis.call("HMGET", key, "state", "window_start_ms", "window_total", "window_failures", "consecutive_failures", "opened_at_ms")
+local state = data[1] or "closed"
+local window_start = tonumber(data[2]) or now_ms
+local total = tonumber(data[3]) or 0
+local failures = tonumber(data[4]) or 0
+local consecutive = tonumber(data[5]) or 0
+local opened_at = tonumber(data[6]) or 0
+
+if now_ms - window_start >= window_ms then
+  window_start = now_ms
+  total = 0
+  failures = 0
+end
+
+total = total + 1
+local tripped = 0
+
+if success == 1 then
+  consecutive = 0
+  if state == "half_open" then
+    state = "closed"
+    window_start = now_ms
+    total = 0
+    failures = 0
+  end
+else
+  failures = failures + 1
+  consecutive = consecutive + 1
+  if state == "half_open" then
+    state = "open"
+    opened_at = now_ms
+    tripped = 1
+  elseif state == "closed" then
+    if consecutive >= max_consecutive or (total >= min_requests and failures / total >= error_rate) then
+      state = "open"
+      opened_at = now_ms
+      tripped = 1
+    end
+  end
+end
+
+redis.call("HSET", key,
+  "state", state,
+  "window_start_ms", window_start,
+  "window_total", total,
+  "window_failures", failures,
+  "consecutive_failures", consecutive,
+  "opened_at_ms", opened_at,
+  "updated_at_ms", now_ms)
+redis.call("EXPIRE", key, ttl)
+
+return {state, tripped}
+"#;
+
+    if !cfg.enabled {
+        return;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "circuit breaker: redis unavailable");
+            return;
+        }
+    };
+
+    let result: redis::RedisResult<(String, i64)> = Script::new(LUA)
+        .key(breaker_key(partner_name, credential_id))
+        .arg(now_ms())
+        .arg(if success { 1 } else { 0 })
+        .arg(cfg.consecutive_failures.max(1))
+        .arg(cfg.error_rate)
+        .arg(cfg.min_requests.max(1))
+        .arg(cfg.window_secs.max(1).saturating_mul(1000))
+        .arg(BREAKER_TTL_SECS)
+        .invoke_async(&mut conn)
+        .await;
+
+    match result {
+        Ok((state, 1)) => {
+            tracing::warn!(
+                partner = %partner_name,
+                credential_id = %credential_id,
+                state = %state,
+                "circuit breaker opened"
+            );
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "circuit breaker record failed");
+        }
+    }
+}
+
+/// Give back a probe slot taken by `breaker_check` when the request ends without reaching the
+/// partner, so the next request can probe instead of waiting out another open period.
+pub async fn breaker_release(
+    redis_client: &redis::Client,
+    partner_name: &str,
+    credential_id: Uuid,
+    cfg: &CircuitBreakerConfig,
+) {
+    static LUA: &str = r#"
+local key = KEYS[1]
+if redis.call("HGET", key, "state") == "half_open" then
+  redis.call("HSET", key, "probe_at_ms", 0)
+end
+return 0
+"#;
+
+    if !cfg.enabled {
+        return;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "circuit breaker: redis unavailable");
+            return;
+        }
+    };
+
+    let result: redis::RedisResult<i64> = Script::new(LUA)
+        .key(breaker_key(partner_name, credential_id))
+        .invoke_async(&mut conn)
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(error = %e, partner = %partner_name, "circuit breaker release failed");
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerSnapshot {
+    pub partner_name: String,
+    pub credential_id: String,
+    pub state: String,
+    pub consecutive_failures: i64,
+    pub window_total: i64,
+    pub window_failures: i64,
+    pub opened_at_ms: Option<i64>,
+    pub updated_at_ms: Option<i64>,
+}
+
+fn field_i64(fields: &HashMap<String, String>, name: &str) -> Option<i64> {
+    fields.get(name).and_then(|v| v.parse().ok())
+}
+
+/// Current breaker state for every (partner, credential) seen recently.
+pub async fn list_breakers(
+    redis_client: &redis::Client,
+    partner_name: Option<&str>,
+) -> redis::RedisResult<Vec<BreakerSnapshot>> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+
+    let pattern = match partner_name {
+        Some(p) => format!("{BREAKER_PREFIX}{p}:*"),
+        None => format!("{BREAKER_PREFIX}*"),
+    };
+
+    let mut keys: Vec<String> = Vec::new();
+    {
+        let mut iter: redis::AsyncIter<String> = conn.scan_match(&pattern).await?;
+        while let Some(k) = iter.next_item().await {
+            keys.push(k);
+        }
+    }
+    keys.sort();
+
+    let mut out = Vec::with_capacity(keys.len());
+    for key in keys {
+        // rk:cb:{partner}:{credential_id} — the uuid never contains ':'
+        let Some((partner, credential_id)) = key
+            .strip_prefix(BREAKER_PREFIX)
+            .and_then(|rest| rest.rsplit_once(':'))
+        else {
+            continue;
+        };
+
+        let fields: HashMap<String, String> = conn.hgetall(&key).await?;
+        let opened_at_ms = field_i64(&fields, "opened_at_ms").filter(|v| *v > 0);
+
+        out.push(BreakerSnapshot {
+            partner_name: partner.to_string(),
+            credential_id: credential_id.to_string(),
+            state: fields
+                .get("state")
+                .cloned()
+                .unwrap_or_else(|| "closed".to_string()),
+            consecutive_failures: field_i64(&fields, "consecutive_failures").unwrap_or(0),
+            window_total: field_i64(&fields, "window_total").unwrap_or(0),
+            window_failures: field_i64(&fields, "window_failures").unwrap_or(0),
+            opened_at_ms,
+            updated_at_ms: field_i64(&fields, "updated_at_ms"),
+        });
+    }
+
+    Ok(out)
+}
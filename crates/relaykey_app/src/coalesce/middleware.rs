@@ -0,0 +1,147 @@
+use axum::{
+    body::Body,
+    http::{
+        header::{CONTENT_LENGTH, TRANSFER_ENCODING, UPGRADE},
+        Method, Request,
+    },
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use std::{sync::Arc, time::Instant};
+
+use relaykey_db::queries::policies::PolicyRow;
+
+use crate::auth::VirtualKeyCtx;
+use crate::canary;
+use crate::idempotency::capture_body;
+use crate::policies::routes::{request_fingerprint, route_for, split_proxy_path};
+use crate::response_body::{accepts_event_stream, is_event_stream};
+use crate::sandbox::{mode_for, SandboxMode};
+use crate::state::AppState;
+use crate::transform::{self, TemplateVars};
+use crate::usage::{insert_usage_event_detailed, UsageDetails, UsageEvent};
+
+use super::{wait, Flight, SharedResponse};
+
+/// Single-flight for routes with `coalesce`: identical concurrent GET/HEAD requests on
+/// this instance share the first one's upstream call. Runs after limits and x402, so
+/// every caller is still counted; only the partner call is shared.
+pub async fn coalesce_requests(
+    Extension(state): Extension<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+
+    let method = req.method().clone();
+    if method != Method::GET && method != Method::HEAD {
+        return next.run(req).await;
+    }
+
+    // Streams, upgrades and requests with a body are never shared.
+    let headers = req.headers();
+    if headers.contains_key(UPGRADE)
+        || headers.contains_key(TRANSFER_ENCODING)
+        || headers
+            .get(CONTENT_LENGTH)
+            .is_some_and(|v| v.as_bytes() != b"0")
+        || accepts_event_stream(headers)
+    {
+        return next.run(req).await;
+    }
+
+    let (Some(vk), Some(policy)) = (
+        req.extensions().get::<VirtualKeyCtx>(),
+        req.extensions().get::<PolicyRow>(),
+    ) else {
+        return next.run(req).await;
+    };
+
+    // Sandboxed keys neither use nor feed shared responses: fixtures must never reach a
+    // live caller, nor partner data a mock one.
+    if mode_for(vk, &state.mock_environments) != SandboxMode::Live {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path().to_string();
+    let (partner, upstream_path) = split_proxy_path(&path);
+    let Some(rule) = route_for(policy, partner, method.as_str(), &upstream_path) else {
+        return next.run(req).await;
+    };
+    let Some(cfg) = rule.coalesce.clone() else {
+        return next.run(req).await;
+    };
+
+    // Keyed (and metered) by the partner that serves this caller, canaries included.
+    let partner = canary::route(policy, partner, vk)
+        .map_or(partner, |c| c.alternate.as_str())
+        .to_string();
+    let (vk_id, customer_id) = (vk.id, vk.customer_id);
+    let fingerprint = request_fingerprint(
+        &upstream_path,
+        req.uri().query(),
+        &cfg.vary_headers,
+        req.headers(),
+        cfg.per_customer.then_some(customer_id),
+    );
+    let mut key = format!("{partner}:{method}:{fingerprint}");
+    // Redacted and full responses are never shared.
+    if policy.redaction.is_active() {
+        key.push_str(&format!(":{}", policy.id));
+    }
+    // Templated rewrites make the partner's answer specific to the caller.
+    if let Some(transform) = &rule.transform {
+        let vars = TemplateVars::from_key(vk);
+        key.push_str(&format!(":{}", transform::fingerprint(transform, &vars)));
+    }
+
+    let publisher = match state.coalescer.join(&key) {
+        Flight::Leader(publisher) => publisher,
+        Flight::Follower(slot) => {
+            let Some(shared) = wait(slot).await else {
+                // The leader got no shareable response; go to the partner ourselves.
+                return next.run(req).await;
+            };
+
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let details = UsageDetails {
+                coalesced: true,
+                response_bytes: Some(shared.body.len() as i64),
+                ..Default::default()
+            };
+            let _ = insert_usage_event_detailed(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk_id,
+                    customer_id,
+                    partner_name: &partner,
+                    path: &path,
+                    forwarded: false,
+                    blocked_reason: None,
+                    status_code: Some(shared.status.as_u16()),
+                    latency_ms,
+                },
+                details,
+            )
+            .await;
+            return shared.to_response();
+        }
+    };
+
+    let resp = next.run(req).await;
+    if is_event_stream(resp.headers()) {
+        return resp;
+    }
+
+    let (parts, body) = resp.into_parts();
+    let (body, captured) = capture_body(body, cfg.max_body_bytes).await;
+    if let Some(bytes) = captured {
+        publisher.publish(SharedResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: bytes,
+        });
+    }
+    Response::from_parts(parts, body)
+}
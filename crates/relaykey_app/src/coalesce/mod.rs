@@ -0,0 +1,96 @@
+pub mod middleware;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+/// A completed partner response, handed to every request that waited on it.
+#[derive(Debug)]
+pub struct SharedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl SharedResponse {
+    pub fn to_response(&self) -> Response {
+        (self.status, self.headers.clone(), Body::from(self.body.clone())).into_response()
+    }
+}
+
+type Slot = watch::Receiver<Option<Arc<SharedResponse>>>;
+type Flights = Arc<Mutex<HashMap<String, Slot>>>;
+
+/// In-flight upstream calls on this instance, by request fingerprint.
+#[derive(Default)]
+pub struct Coalescer {
+    flights: Flights,
+}
+
+/// Outcome of joining a flight.
+pub enum Flight {
+    /// Nobody is calling the partner for this request yet: forward it and `publish`.
+    Leader(Publisher),
+    /// Someone already is: wait for their response.
+    Follower(Slot),
+}
+
+impl Coalescer {
+    pub fn join(&self, key: &str) -> Flight {
+        let Ok(mut flights) = self.flights.lock() else {
+            // Poisoned: don't coalesce, forward as a lone leader.
+            let (tx, _) = watch::channel(None);
+            return Flight::Leader(Publisher {
+                key: None,
+                flights: self.flights.clone(),
+                tx,
+            });
+        };
+
+        if let Some(slot) = flights.get(key) {
+            return Flight::Follower(slot.clone());
+        }
+
+        let (tx, rx) = watch::channel(None);
+        flights.insert(key.to_string(), rx);
+        Flight::Leader(Publisher {
+            key: Some(key.to_string()),
+            flights: self.flights.clone(),
+            tx,
+        })
+    }
+}
+
+/// Held by the leader. Dropping it without publishing (error, cancelled request,
+/// unshareable response) releases the followers to forward on their own.
+pub struct Publisher {
+    key: Option<String>,
+    flights: Flights,
+    tx: watch::Sender<Option<Arc<SharedResponse>>>,
+}
+
+impl Publisher {
+    pub fn publish(self, response: SharedResponse) {
+        let _ = self.tx.send(Some(Arc::new(response)));
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if let (Some(key), Ok(mut flights)) = (self.key.take(), self.flights.lock()) {
+            flights.remove(&key);
+        }
+    }
+}
+
+/// Wait for the leader's response. None when the leader gave up without one.
+pub async fn wait(mut slot: Slot) -> Option<Arc<SharedResponse>> {
+    slot.wait_for(Option::is_some).await.ok()?.clone()
+}
@@ -0,0 +1,204 @@
+use rand::Rng;
+use redis::AsyncCommands;
+use serde::Serialize;
+use std::time::Duration;
+
+use relaykey_db::models::{EndpointStrategy, UpstreamEndpoints};
+
+const ENDPOINT_DOWN_PREFIX: &str = "rk:endpoint_down:";
+
+/// Key: rk:endpoint_down:{partner}:{url}
+fn endpoint_down_key(partner_name: &str, url: &str) -> String {
+    format!("{ENDPOINT_DOWN_PREFIX}{partner_name}:{url}")
+}
+
+/// One configured endpoint in the order the retry loop should try it.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointChoice {
+    /// Index into the partner's endpoint list.
+    pub index: usize,
+    /// False when another instance (or we) recently saw it fail.
+    pub healthy: bool,
+}
+
+/// Try order for this request: healthy endpoints first (list order, or a
+/// weighted shuffle), then unhealthy ones as a last resort.
+/// Fail-open: Redis errors treat every endpoint as healthy.
+pub async fn endpoint_order(
+    redis_client: &redis::Client,
+    partner_name: &str,
+    cfg: &UpstreamEndpoints,
+    urls: &[String],
+) -> Vec<EndpointChoice> {
+    let down = endpoints_down(redis_client, partner_name, urls).await;
+
+    let weights: Vec<u32> = if cfg.endpoints.len() == urls.len() {
+        cfg.endpoints.iter().map(|e| e.weight).collect()
+    } else {
+        vec![1; urls.len()]
+    };
+
+    let mut healthy: Vec<usize> = (0..urls.len()).filter(|i| !down[*i]).collect();
+    let unhealthy: Vec<usize> = (0..urls.len()).filter(|i| down[*i]).collect();
+
+    if cfg.strategy == EndpointStrategy::Weighted {
+        healthy = weighted_shuffle(&healthy, &weights);
+    }
+
+    healthy
+        .into_iter()
+        .map(|index| EndpointChoice {
+            index,
+            healthy: true,
+        })
+        .chain(unhealthy.into_iter().map(|index| EndpointChoice {
+            index,
+            healthy: false,
+        }))
+        .collect()
+}
+
+/// Weighted sampling without replacement; weight-0 endpoints go last in list order.
+fn weighted_shuffle(indices: &[usize], weights: &[u32]) -> Vec<usize> {
+    let mut rng = rand::thread_rng();
+    let mut pool: Vec<usize> = indices.iter().copied().filter(|i| weights[*i] > 0).collect();
+    let zero_weight = indices.iter().copied().filter(|i| weights[*i] == 0);
+
+    let mut out = Vec::with_capacity(indices.len());
+    while !pool.is_empty() {
+        let total: u64 = pool.iter().map(|i| weights[*i] as u64).sum();
+        let mut pick = rng.gen_range(0..total);
+        let pos = pool
+            .iter()
+            .position(|i| {
+                let w = weights[*i] as u64;
+                if pick < w {
+                    true
+                } else {
+                    pick -= w;
+                    false
+                }
+            })
+            .unwrap_or(0);
+        out.push(pool.remove(pos));
+    }
+
+    out.extend(zero_weight);
+    out
+}
+
+async fn endpoints_down(redis_client: &redis::Client, partner_name: &str, urls: &[String]) -> Vec<bool> {
+    let all_up = vec![false; urls.len()];
+
+    // Single origin: nothing to fail over to, skip the round trip.
+    if urls.len() < 2 {
+        return all_up;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "endpoint health: redis unavailable (fail-open)");
+            return all_up;
+        }
+    };
+
+    let mut pipe = redis::pipe();
+    for url in urls {
+        pipe.exists(endpoint_down_key(partner_name, url));
+    }
+
+    match pipe.query_async::<_, Vec<bool>>(&mut conn).await {
+        Ok(down) if down.len() == urls.len() => down,
+        Ok(_) => all_up,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "endpoint health check failed (fail-open)");
+            all_up
+        }
+    }
+}
+
+/// Skip this endpoint on every instance for `cfg.unhealthy_secs`. Best-effort.
+pub async fn mark_endpoint_down(
+    redis_client: &redis::Client,
+    partner_name: &str,
+    url: &str,
+    cfg: &UpstreamEndpoints,
+) {
+    let ttl = Duration::from_secs(cfg.unhealthy_secs.max(1));
+
+    match redis_client.get_multiplexed_async_connection().await {
+        Ok(mut conn) => {
+            if let Err(e) = conn
+                .set_ex::<_, _, ()>(endpoint_down_key(partner_name, url), 1, ttl.as_secs())
+                .await
+            {
+                tracing::warn!(error = %e, partner = %partner_name, "endpoint health: failed to mark down");
+            }
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "endpoint health: redis unavailable");
+        }
+    }
+}
+
+/// Clear the down flag early once the endpoint answers again. Best-effort.
+pub async fn mark_endpoint_up(redis_client: &redis::Client, partner_name: &str, url: &str) {
+    if let Ok(mut conn) = redis_client.get_multiplexed_async_connection().await {
+        let _: Result<(), _> = conn.del(endpoint_down_key(partner_name, url)).await;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    pub partner_name: String,
+    pub url: String,
+    /// Milliseconds until the endpoint is tried first again.
+    pub down_for_ms: i64,
+}
+
+/// Endpoints currently marked down (healthy endpoints have no key).
+pub async fn list_endpoints_down(
+    redis_client: &redis::Client,
+    partner_name: Option<&str>,
+) -> redis::RedisResult<Vec<EndpointHealth>> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+
+    let pattern = match partner_name {
+        Some(p) => format!("{ENDPOINT_DOWN_PREFIX}{p}:*"),
+        None => format!("{ENDPOINT_DOWN_PREFIX}*"),
+    };
+
+    let mut keys: Vec<String> = Vec::new();
+    {
+        let mut iter: redis::AsyncIter<String> = conn.scan_match(&pattern).await?;
+        while let Some(k) = iter.next_item().await {
+            keys.push(k);
+        }
+    }
+    keys.sort();
+
+    let mut out = Vec::with_capacity(keys.len());
+    for key in keys {
+        // rk:endpoint_down:{partner}:{url} — partner names never contain ':'
+        let Some((partner, url)) = key
+            .strip_prefix(ENDPOINT_DOWN_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            continue;
+        };
+
+        let down_for_ms: i64 = conn.pttl(&key).await?;
+        if down_for_ms <= 0 {
+            continue;
+        }
+
+        out.push(EndpointHealth {
+            partner_name: partner.to_string(),
+            url: url.to_string(),
+            down_for_ms,
+        });
+    }
+
+    Ok(out)
+}
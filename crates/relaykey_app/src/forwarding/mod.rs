@@ -0,0 +1,163 @@
+use axum::http::{header::HOST, HeaderMap, HeaderName, HeaderValue};
+use rand::Rng;
+use std::net::IpAddr;
+
+use relaykey_db::models::ForwardingHeaders;
+
+const TRACEPARENT: &str = "traceparent";
+
+/// W3C trace context for the upstream call: the client's trace id when it sent a
+/// valid `traceparent`, otherwise a new trace. The span id is always ours.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+    flags: String,
+}
+
+impl TraceContext {
+    pub fn continue_or_start(headers: &HeaderMap) -> Self {
+        let mut rng = rand::thread_rng();
+        let span_id = format!("{:016x}", rng.gen_range(1..=u64::MAX));
+
+        if let Some((trace_id, flags)) = headers
+            .get(TRACEPARENT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_traceparent)
+        {
+            return Self {
+                trace_id,
+                span_id,
+                flags,
+            };
+        }
+
+        Self {
+            trace_id: format!("{:032x}", rng.gen_range(1..=u128::MAX)),
+            span_id,
+            flags: "01".to_string(),
+        }
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// `00-{trace_id}-{parent_id}-{flags}` -> (trace_id, flags). All-zero ids are invalid.
+fn parse_traceparent(raw: &str) -> Option<(String, String)> {
+    let mut parts = raw.trim().split('-');
+    let (version, trace_id, parent_id, flags) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+
+    let valid = version == "00"
+        && parts.next().is_none()
+        && is_lower_hex(trace_id, 32)
+        && is_lower_hex(parent_id, 16)
+        && is_lower_hex(flags, 2)
+        && trace_id.bytes().any(|b| b != b'0')
+        && parent_id.bytes().any(|b| b != b'0');
+
+    valid.then(|| (trace_id.to_string(), flags.to_string()))
+}
+
+/// RFC 7239 node: IPv6 addresses are bracketed and quoted.
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("\"[{v6}]\""),
+    }
+}
+
+/// Correlation headers for one request, computed once and sent on every attempt.
+/// Client-supplied values of the same headers are replaced, never passed through.
+#[derive(Debug, Clone, Default)]
+pub struct Forwarding {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    /// Client headers dropped because we own them (even when we have no value to send).
+    replaced: Vec<HeaderName>,
+    /// Set when `traceparent` is emitted; logged so vendor tickets can be matched.
+    pub trace: Option<TraceContext>,
+}
+
+impl Forwarding {
+    pub fn new(
+        cfg: &ForwardingHeaders,
+        request: &HeaderMap,
+        peer: Option<IpAddr>,
+        proto: &str,
+        request_id: Option<&str>,
+    ) -> Self {
+        let mut headers: Vec<(&'static str, String)> = Vec::new();
+        let mut replaced: Vec<&'static str> = Vec::new();
+        let host = request.get(HOST).and_then(|v| v.to_str().ok());
+
+        if cfg.x_forwarded {
+            replaced.extend(["x-forwarded-for", "x-forwarded-proto", "x-forwarded-host"]);
+            if let Some(ip) = peer {
+                headers.push(("x-forwarded-for", ip.to_string()));
+            }
+            headers.push(("x-forwarded-proto", proto.to_string()));
+            if let Some(host) = host {
+                headers.push(("x-forwarded-host", host.to_string()));
+            }
+        }
+
+        if cfg.forwarded {
+            replaced.push("forwarded");
+            let mut parts = Vec::new();
+            if let Some(ip) = peer {
+                parts.push(format!("for={}", forwarded_node(ip)));
+            }
+            parts.push(format!("proto={proto}"));
+            if let Some(host) = host {
+                parts.push(format!("host=\"{host}\""));
+            }
+            headers.push(("forwarded", parts.join(";")));
+        }
+
+        let trace = cfg.traceparent.then(|| TraceContext::continue_or_start(request));
+        if let Some(trace) = &trace {
+            replaced.push(TRACEPARENT);
+            headers.push((TRACEPARENT, trace.traceparent()));
+        }
+        let mut replaced: Vec<HeaderName> = replaced.into_iter().map(HeaderName::from_static).collect();
+
+        let mut out: Vec<(HeaderName, HeaderValue)> = headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                Some((HeaderName::from_static(name), HeaderValue::from_str(&value).ok()?))
+            })
+            .collect();
+
+        if let Some(name) = cfg
+            .request_id_header
+            .as_deref()
+            .and_then(|n| HeaderName::from_bytes(n.trim().as_bytes()).ok())
+        {
+            if let Some(value) = request_id.and_then(|id| HeaderValue::from_str(id).ok()) {
+                out.push((name.clone(), value));
+            }
+            replaced.push(name);
+        }
+
+        Self {
+            headers: out,
+            replaced,
+            trace,
+        }
+    }
+
+    pub fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    /// A client header that this request sets itself.
+    pub fn replaces(&self, name: &HeaderName) -> bool {
+        self.replaced.contains(name)
+    }
+}
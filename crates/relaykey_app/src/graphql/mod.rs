@@ -0,0 +1,286 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use graphql_parser::query::{
+    parse_query, Definition, Document, OperationDefinition, Selection, SelectionSet,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use relaykey_db::models::GraphQlConfig;
+
+use crate::usage::BlockedReason;
+
+const BLOCKED_CODE: &str = BlockedReason::GraphqlOperationRejected.code();
+
+/// Field budget when the partner sets no `max_complexity`, so fragment fan-out
+/// can't make the walk itself expensive.
+const MAX_FIELDS: usize = 10_000;
+
+/// The operation a request runs, recorded in usage events.
+#[derive(Debug, Clone)]
+pub struct GraphQlOperation {
+    pub name: Option<String>,
+    pub operation_type: &'static str,
+}
+
+#[derive(Debug)]
+pub enum GraphQlRejection {
+    /// Not a GraphQL request we can read (400).
+    Invalid(String),
+    /// Readable, but the partner's rules don't allow it (403).
+    Rejected(String),
+    /// Hash-only (automatic persisted query) request for a document we haven't seen
+    /// in full; answered the way APQ clients expect so they resend it with the query.
+    PersistedQueryNotFound,
+}
+
+impl GraphQlRejection {
+    pub fn message(&self) -> &str {
+        match self {
+            GraphQlRejection::Invalid(m) | GraphQlRejection::Rejected(m) => m,
+            GraphQlRejection::PersistedQueryNotFound => "PersistedQueryNotFound",
+        }
+    }
+}
+
+/// GraphQL-style error body, so clients surface the reason like any other GraphQL error.
+pub fn rejection_response(rejection: &GraphQlRejection) -> Response {
+    let (status, code) = match rejection {
+        GraphQlRejection::Invalid(_) => (StatusCode::BAD_REQUEST, BLOCKED_CODE),
+        GraphQlRejection::Rejected(_) => (StatusCode::FORBIDDEN, BLOCKED_CODE),
+        GraphQlRejection::PersistedQueryNotFound => (StatusCode::OK, "PERSISTED_QUERY_NOT_FOUND"),
+    };
+    let body = json!({
+        "errors": [{ "message": rejection.message(), "extensions": { "code": code } }]
+    });
+    (status, Json(body)).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GraphQlRequest {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    extensions: Option<Value>,
+}
+
+impl GraphQlRequest {
+    fn from_body(body: &[u8]) -> Result<Self, GraphQlRejection> {
+        serde_json::from_slice(body)
+            .map_err(|_| GraphQlRejection::Invalid("body is not a graphql request object".to_string()))
+    }
+
+    /// GraphQL over GET: `?query=...&operationName=...&extensions=...`.
+    fn from_query(query: &str) -> Self {
+        let mut req = Self::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "query" => req.query = Some(value.into_owned()),
+                "operationName" => req.operation_name = Some(value.into_owned()),
+                "extensions" => req.extensions = serde_json::from_str(&value).ok(),
+                _ => {}
+            }
+        }
+        req
+    }
+
+    fn persisted_hash(&self) -> Option<&str> {
+        self.extensions
+            .as_ref()?
+            .get("persistedQuery")?
+            .get("sha256Hash")?
+            .as_str()
+    }
+}
+
+/// Check a POST body against the partner's GraphQL rules.
+pub fn inspect_body(cfg: &GraphQlConfig, body: &[u8]) -> Result<GraphQlOperation, GraphQlRejection> {
+    inspect(cfg, GraphQlRequest::from_body(body)?)
+}
+
+/// Check a GET request's query string against the partner's GraphQL rules.
+pub fn inspect_query(
+    cfg: &GraphQlConfig,
+    query: Option<&str>,
+) -> Result<GraphQlOperation, GraphQlRejection> {
+    inspect(cfg, GraphQlRequest::from_query(query.unwrap_or("")))
+}
+
+fn is_persisted(cfg: &GraphQlConfig, hash: &str) -> bool {
+    cfg.persisted_hashes.iter().any(|h| h.eq_ignore_ascii_case(hash))
+}
+
+fn inspect(cfg: &GraphQlConfig, req: GraphQlRequest) -> Result<GraphQlOperation, GraphQlRejection> {
+    let Some(query) = req.query.as_deref() else {
+        // Hash only: allowlisted documents were reviewed when they were registered.
+        return match req.persisted_hash() {
+            Some(hash) if is_persisted(cfg, hash) => Ok(GraphQlOperation {
+                name: req.operation_name.clone(),
+                operation_type: "persisted",
+            }),
+            Some(_) if cfg.persisted_only => Err(GraphQlRejection::Rejected(
+                "operation is not a persisted operation".to_string(),
+            )),
+            Some(_) => Err(GraphQlRejection::PersistedQueryNotFound),
+            None => Err(GraphQlRejection::Invalid("missing query".to_string())),
+        };
+    };
+
+    if cfg.persisted_only {
+        let hash = hex::encode(Sha256::digest(query.as_bytes()));
+        if !is_persisted(cfg, &hash) {
+            return Err(GraphQlRejection::Rejected(
+                "operation is not a persisted operation".to_string(),
+            ));
+        }
+    }
+
+    let doc = parse_query::<&str>(query)
+        .map_err(|e| GraphQlRejection::Invalid(format!("invalid graphql document: {e}")))?;
+    check_document(cfg, &doc, req.operation_name.as_deref())
+}
+
+fn check_document<'a>(
+    cfg: &GraphQlConfig,
+    doc: &'a Document<'a, &'a str>,
+    operation_name: Option<&str>,
+) -> Result<GraphQlOperation, GraphQlRejection> {
+    let mut fragments = HashMap::new();
+    let mut operations = Vec::new();
+    for definition in &doc.definitions {
+        match definition {
+            Definition::Fragment(f) => {
+                fragments.insert(f.name, &f.selection_set);
+            }
+            Definition::Operation(op) => operations.push(op),
+        }
+    }
+
+    let operation = match operation_name {
+        Some(wanted) => operations
+            .iter()
+            .copied()
+            .find(|op| operation_parts(op).1 == Some(wanted)),
+        None if operations.len() == 1 => operations.first().copied(),
+        None => None,
+    }
+    .ok_or_else(|| {
+        GraphQlRejection::Invalid("operationName does not select exactly one operation".to_string())
+    })?;
+
+    let (operation_type, name, selection_set) = operation_parts(operation);
+
+    if let Some(allowed) = &cfg.allow_operation_types {
+        if !allowed.iter().any(|t| t.eq_ignore_ascii_case(operation_type)) {
+            return Err(GraphQlRejection::Rejected(format!(
+                "{operation_type} operations are not allowed"
+            )));
+        }
+    }
+
+    let mut walker = Walker {
+        fragments: &fragments,
+        fields: 0,
+        max_fields: cfg.max_complexity.unwrap_or(MAX_FIELDS).min(MAX_FIELDS),
+        max_depth: cfg.max_depth,
+        top_level: Vec::new(),
+    };
+    walker.walk(selection_set, 0, &mut Vec::new())?;
+
+    let allowed_fields = cfg.allow_fields.get(operation_type);
+    for field in &walker.top_level {
+        if *field == "__typename" {
+            continue;
+        }
+        if field.starts_with("__") {
+            if !cfg.allow_introspection {
+                return Err(GraphQlRejection::Rejected("introspection is not allowed".to_string()));
+            }
+            continue;
+        }
+        if allowed_fields.is_some_and(|allowed| !allowed.iter().any(|f| f == field)) {
+            return Err(GraphQlRejection::Rejected(format!(
+                "field {field} is not allowed on {operation_type}"
+            )));
+        }
+    }
+
+    Ok(GraphQlOperation {
+        name: name.map(str::to_string),
+        operation_type,
+    })
+}
+
+fn operation_parts<'a>(
+    op: &'a OperationDefinition<'a, &'a str>,
+) -> (&'static str, Option<&'a str>, &'a SelectionSet<'a, &'a str>) {
+    match op {
+        OperationDefinition::SelectionSet(s) => ("query", None, s),
+        OperationDefinition::Query(q) => ("query", q.name, &q.selection_set),
+        OperationDefinition::Mutation(m) => ("mutation", m.name, &m.selection_set),
+        OperationDefinition::Subscription(s) => ("subscription", s.name, &s.selection_set),
+    }
+}
+
+/// Walks an operation with fragments expanded, counting fields and depth and
+/// collecting the root field names.
+struct Walker<'f, 'a> {
+    fragments: &'f HashMap<&'a str, &'a SelectionSet<'a, &'a str>>,
+    fields: usize,
+    max_fields: usize,
+    max_depth: Option<usize>,
+    top_level: Vec<&'a str>,
+}
+
+impl<'a> Walker<'_, 'a> {
+    fn walk(
+        &mut self,
+        set: &'a SelectionSet<'a, &'a str>,
+        depth: usize,
+        spreads: &mut Vec<&'a str>,
+    ) -> Result<(), GraphQlRejection> {
+        for selection in &set.items {
+            match selection {
+                Selection::Field(field) => {
+                    self.fields += 1;
+                    if self.fields > self.max_fields {
+                        return Err(GraphQlRejection::Rejected(format!(
+                            "query selects more than {} fields",
+                            self.max_fields
+                        )));
+                    }
+                    if let Some(max) = self.max_depth.filter(|&max| depth + 1 > max) {
+                        return Err(GraphQlRejection::Rejected(format!("query is deeper than {max}")));
+                    }
+                    if depth == 0 {
+                        self.top_level.push(field.name);
+                    }
+                    self.walk(&field.selection_set, depth + 1, spreads)?;
+                }
+                Selection::InlineFragment(inline) => {
+                    self.walk(&inline.selection_set, depth, spreads)?;
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name;
+                    if spreads.contains(&name) {
+                        return Err(GraphQlRejection::Invalid(format!("fragment {name} spreads itself")));
+                    }
+                    let fragment = self
+                        .fragments
+                        .get(name)
+                        .copied()
+                        .ok_or_else(|| GraphQlRejection::Invalid(format!("unknown fragment {name}")))?;
+                    spreads.push(name);
+                    self.walk(fragment, depth, spreads)?;
+                    spreads.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use axum::http::{header::SET_COOKIE, HeaderMap, HeaderName, HeaderValue};
+use std::collections::{HashMap, HashSet};
+
+use relaykey_db::models::HeaderRules;
+
+static HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+pub fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP.contains(&name)
+}
+
+fn lowercase_set(names: &[String]) -> HashSet<String> {
+    names.iter().map(|n| n.trim().to_ascii_lowercase()).collect()
+}
+
+/// A partner's `HeaderRules`, parsed once per request. Names are matched lowercase.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderFilter {
+    allow: Option<HashSet<String>>,
+    deny: HashSet<String>,
+    inject: Vec<(HeaderName, HeaderValue)>,
+    forward_authorization: bool,
+    response_strip: HashSet<String>,
+    response_rename: HashMap<String, HeaderName>,
+    forward_set_cookie: bool,
+}
+
+impl HeaderFilter {
+    /// Entries that aren't valid header names/values are skipped with a warning.
+    pub fn new(partner_name: &str, rules: &HeaderRules) -> Self {
+        let inject = rules
+            .request_inject
+            .iter()
+            .filter_map(|(name, value)| {
+                let parsed = HeaderName::from_bytes(name.trim().as_bytes())
+                    .ok()
+                    .zip(HeaderValue::from_str(value).ok());
+                if parsed.is_none() {
+                    tracing::warn!(partner = %partner_name, header = %name, "invalid injected header; skipped");
+                }
+                parsed
+            })
+            .collect();
+
+        let response_rename = rules
+            .response_rename
+            .iter()
+            .filter_map(|(from, to)| {
+                let to = HeaderName::from_bytes(to.trim().as_bytes()).ok();
+                if to.is_none() {
+                    tracing::warn!(partner = %partner_name, header = %from, "invalid response header rename; skipped");
+                }
+                Some((from.trim().to_ascii_lowercase(), to?))
+            })
+            .collect();
+
+        Self {
+            allow: rules.request_allow.as_deref().map(lowercase_set),
+            deny: lowercase_set(&rules.request_deny),
+            inject,
+            forward_authorization: rules.forward_authorization,
+            response_strip: lowercase_set(&rules.response_strip),
+            response_rename,
+            forward_set_cookie: rules.forward_set_cookie,
+        }
+    }
+
+    /// Whether a client request header (lowercase name) may go to the partner.
+    pub fn forward_request(&self, name: &str) -> bool {
+        // never forwarded, whatever the rules say
+        if name == "host"
+            || name == "x-relaykey"
+            || name == "x-request-id"
+            || name == "cookie"
+            || name.starts_with("proxy-")
+            || is_hop_by_hop(name)
+        {
+            return false;
+        }
+
+        if name == "authorization" && !self.forward_authorization {
+            return false;
+        }
+
+        if self.deny.contains(name) {
+            return false;
+        }
+
+        // Injected headers replace whatever the client sent.
+        if self.inject.iter().any(|(n, _)| n.as_str() == name) {
+            return false;
+        }
+
+        self.allow.as_ref().is_none_or(|allow| allow.contains(name))
+    }
+
+    /// Static headers to add to every upstream request.
+    pub fn injected(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.inject
+    }
+
+    /// Partner response headers as the client should see them.
+    pub fn response_headers(&self, upstream: &HeaderMap) -> HeaderMap {
+        let mut out = HeaderMap::new();
+        for (name, value) in upstream.iter() {
+            let name_str = name.as_str();
+            if is_hop_by_hop(name_str) || self.response_strip.contains(name_str) {
+                continue;
+            }
+            // Don't leak upstream cookies to callers unless the partner is set up for it.
+            if name == SET_COOKIE && !self.forward_set_cookie {
+                continue;
+            }
+
+            let name = self
+                .response_rename
+                .get(name_str)
+                .cloned()
+                .unwrap_or_else(|| name.clone());
+            out.append(name, value.clone());
+        }
+        out
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::time::sleep;
+use url::Url;
+
+use relaykey_db::models::HedgingConfig;
+
+use crate::upstream_auth::UpstreamCredential;
+
+/// Latency samples kept per partner for the p95 estimate.
+const SAMPLE_WINDOW: usize = 256;
+/// Below this many samples the configured fallback delay is used.
+const MIN_SAMPLES: usize = 20;
+
+/// Per-instance rolling window of upstream latencies (time to response headers).
+/// Local on purpose: the hedge decision is on the hot path and must not cost a Redis round trip.
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    samples: Mutex<HashMap<String, VecDeque<u32>>>,
+}
+
+impl LatencyTracker {
+    pub fn record(&self, partner_name: &str, latency: Duration) {
+        let ms = latency.as_millis().min(u32::MAX as u128) as u32;
+        let Ok(mut samples) = self.samples.lock() else {
+            return;
+        };
+
+        let window = samples.entry(partner_name.to_string()).or_default();
+        if window.len() == SAMPLE_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(ms);
+    }
+
+    pub fn p95(&self, partner_name: &str) -> Option<Duration> {
+        let samples = self.samples.lock().ok()?;
+        let window = samples.get(partner_name)?;
+        if window.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let mut sorted: Vec<u32> = window.iter().copied().collect();
+        sorted.sort_unstable();
+        let idx = (sorted.len() * 95).div_ceil(100).saturating_sub(1);
+        Some(Duration::from_millis(sorted[idx] as u64))
+    }
+}
+
+/// How long to wait on the first attempt before sending the hedge.
+pub fn hedge_delay(cfg: &HedgingConfig, tracker: &LatencyTracker, partner_name: &str) -> Duration {
+    if let Some(ms) = cfg.delay_ms {
+        return Duration::from_millis(ms);
+    }
+
+    let min = Duration::from_millis(cfg.min_delay_ms);
+    let max = Duration::from_millis(cfg.max_delay_ms.max(cfg.min_delay_ms));
+
+    tracker
+        .p95(partner_name)
+        .unwrap_or(Duration::from_millis(cfg.fallback_delay_ms))
+        .clamp(min, max)
+}
+
+/// Where the hedged attempt goes. Defaults to the primary URL and credential.
+#[derive(Debug, Clone)]
+pub struct HedgeTarget {
+    pub url: Url,
+    pub credential: UpstreamCredential,
+    /// The credential's client (its own when it has a client identity).
+    pub http: reqwest::Client,
+}
+
+pub type Attempt = reqwest::Result<reqwest::Response>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leg {
+    Primary,
+    Hedge,
+}
+
+#[derive(Debug)]
+pub struct Raced {
+    pub result: Attempt,
+    /// Which attempt produced `result`.
+    pub leg: Leg,
+    /// A hedge was actually sent.
+    pub hedged: bool,
+    /// The other attempt finished first but failed (so we waited for this one).
+    pub other_failed: bool,
+}
+
+/// A response worth returning right away. Anything else waits for the other attempt.
+fn is_success(attempt: &Attempt) -> bool {
+    match attempt {
+        Ok(resp) => {
+            let status = resp.status();
+            !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(_) => false,
+    }
+}
+
+/// Run `primary`; if it hasn't finished after `delay`, ask `launch` for a hedge
+/// (it returns None when the retry budget says no) and race the two.
+/// The first success wins and the loser is dropped, which cancels its request.
+pub async fn race<P, L, H>(primary: P, delay: Duration, launch: L) -> Raced
+where
+    P: Future<Output = Attempt>,
+    L: Future<Output = Option<H>>,
+    H: Future<Output = Attempt>,
+{
+    tokio::pin!(primary);
+
+    tokio::select! {
+        result = &mut primary => {
+            return Raced { result, leg: Leg::Primary, hedged: false, other_failed: false };
+        }
+        _ = sleep(delay) => {}
+    }
+
+    let Some(hedge) = launch.await else {
+        let result = primary.await;
+        return Raced { result, leg: Leg::Primary, hedged: false, other_failed: false };
+    };
+    tokio::pin!(hedge);
+
+    tokio::select! {
+        result = &mut primary => {
+            if is_success(&result) {
+                return Raced { result, leg: Leg::Primary, hedged: true, other_failed: false };
+            }
+            let result = hedge.await;
+            Raced { result, leg: Leg::Hedge, hedged: true, other_failed: true }
+        }
+        result = &mut hedge => {
+            if is_success(&result) {
+                return Raced { result, leg: Leg::Hedge, hedged: true, other_failed: false };
+            }
+            let result = primary.await;
+            Raced { result, leg: Leg::Primary, hedged: true, other_failed: true }
+        }
+    }
+}
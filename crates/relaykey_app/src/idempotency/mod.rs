@@ -0,0 +1,243 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use futures_util::{stream, StreamExt};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Client-facing header carrying the idempotency key.
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+/// Set on responses served from the idempotency store instead of the partner.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Responses larger than this are forwarded but not kept for replay.
+pub const MAX_STORED_BODY_BYTES: usize = 1024 * 1024;
+
+const MAX_KEY_LEN: usize = 255;
+const RECORD_TTL_SECS: u64 = 60 * 60 * 24;
+const IN_FLIGHT_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum IdempotencyRecord {
+    InFlight {
+        request_hash: String,
+    },
+    Completed {
+        request_hash: String,
+        status: u16,
+        headers: Vec<(String, String)>,
+        /// None when the original body exceeded MAX_STORED_BODY_BYTES.
+        body_b64: Option<String>,
+    },
+}
+
+/// Outcome of claiming an idempotency key before forwarding.
+#[derive(Debug)]
+pub enum Claim {
+    /// First time we see this key: forward the request.
+    Proceed,
+    /// Same key, same request, still being forwarded by someone else.
+    InFlight,
+    /// Same key reused for a different request.
+    Mismatch,
+    /// Same key, same request, already answered.
+    Replay(Response),
+    /// Already answered, but the response was too large to keep.
+    NotReplayable,
+}
+
+/// Normalized client idempotency key, if one was supplied.
+pub fn client_idempotency_key(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(IDEMPOTENCY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && s.len() <= MAX_KEY_LEN)
+        .map(str::to_string)
+}
+
+/// Per-request idempotency state, built when the client sent an `Idempotency-Key`.
+#[derive(Debug, Clone)]
+pub struct IdempotencyCtx {
+    /// Key sent to the partner. Derived from (vk, partner, client key) so two
+    /// virtual keys sharing one upstream credential can never collide at the vendor.
+    pub upstream_key: String,
+    /// Key: rk:idem:{vk_id}:{upstream_key}
+    pub store_key: String,
+    pub request_hash: String,
+}
+
+impl IdempotencyCtx {
+    pub fn new(vk_id: Uuid, partner_name: &str, client_key: &str, request_hash: String) -> Self {
+        let digest = Sha256::digest(format!("{vk_id}:{partner_name}:{client_key}").as_bytes());
+        let upstream_key = URL_SAFE_NO_PAD.encode(digest);
+
+        Self {
+            store_key: format!("rk:idem:{vk_id}:{upstream_key}"),
+            upstream_key,
+            request_hash,
+        }
+    }
+}
+
+/// Claim the key for this request (SET NX). Fail-open: Redis errors proceed.
+/// The in-flight marker outlives the request timeout a little so body streaming is covered.
+pub async fn claim(
+    redis_client: &redis::Client,
+    ctx: &IdempotencyCtx,
+    request_timeout: Duration,
+) -> Claim {
+    let key = ctx.store_key.as_str();
+    let request_hash = ctx.request_hash.as_str();
+    let in_flight_ttl = request_timeout + IN_FLIGHT_GRACE;
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, "idempotency: redis unavailable (fail-open)");
+            return Claim::Proceed;
+        }
+    };
+
+    let in_flight = IdempotencyRecord::InFlight {
+        request_hash: request_hash.to_string(),
+    };
+    let Ok(json) = serde_json::to_string(&in_flight) else {
+        return Claim::Proceed;
+    };
+
+    let opts = redis::SetOptions::default()
+        .conditional_set(redis::ExistenceCheck::NX)
+        .with_expiration(redis::SetExpiry::PX(in_flight_ttl.as_millis().max(1) as usize));
+
+    let claimed: redis::RedisResult<Option<String>> = conn.set_options(key, json, opts).await;
+    match claimed {
+        Ok(Some(_)) => return Claim::Proceed,
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!(error = %e, "idempotency: claim failed (fail-open)");
+            return Claim::Proceed;
+        }
+    }
+
+    let existing: Option<String> = conn.get(key).await.unwrap_or(None);
+    let Some(record) = existing.and_then(|j| serde_json::from_str::<IdempotencyRecord>(&j).ok())
+    else {
+        // Expired between SET and GET (or unreadable): safest is to report in-flight.
+        return Claim::InFlight;
+    };
+
+    match record {
+        IdempotencyRecord::InFlight { request_hash: h } if h == request_hash => Claim::InFlight,
+        IdempotencyRecord::Completed {
+            request_hash: h,
+            status,
+            headers,
+            body_b64,
+        } if h == request_hash => match body_b64 {
+            Some(b64) => match STANDARD.decode(b64) {
+                Ok(body) => Claim::Replay(replay_response(status, &headers, body)),
+                Err(_) => Claim::NotReplayable,
+            },
+            None => Claim::NotReplayable,
+        },
+        _ => Claim::Mismatch,
+    }
+}
+
+/// Store the final response for later replay.
+pub async fn complete(
+    redis_client: &redis::Client,
+    ctx: &IdempotencyCtx,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: Option<&Bytes>,
+) {
+    let record = IdempotencyRecord::Completed {
+        request_hash: ctx.request_hash.clone(),
+        status: status.as_u16(),
+        headers: headers
+            .iter()
+            .filter_map(|(n, v)| v.to_str().ok().map(|v| (n.as_str().to_string(), v.to_string())))
+            .collect(),
+        body_b64: body.map(|b| STANDARD.encode(b)),
+    };
+
+    let Ok(json) = serde_json::to_string(&record) else {
+        return;
+    };
+
+    match redis_client.get_multiplexed_async_connection().await {
+        Ok(mut conn) => {
+            if let Err(e) = conn
+                .set_ex::<_, _, ()>(&ctx.store_key, json, RECORD_TTL_SECS)
+                .await
+            {
+                tracing::warn!(error = %e, "idempotency: failed to store response");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "idempotency: redis unavailable; response not stored"),
+    }
+}
+
+/// Drop an in-flight claim so the client can retry with the same key.
+pub async fn release(redis_client: &redis::Client, ctx: &IdempotencyCtx) {
+    if let Ok(mut conn) = redis_client.get_multiplexed_async_connection().await {
+        let _: Result<(), _> = conn.del(&ctx.store_key).await;
+    }
+}
+
+fn replay_response(status: u16, headers: &[(String, String)], body: Vec<u8>) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    let mut out = HeaderMap::new();
+    for (name, value) in headers {
+        if let (Ok(n), Ok(v)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            out.append(n, v);
+        }
+    }
+    out.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+    (status, out, Body::from(body)).into_response()
+}
+
+/// Read up to `cap` bytes of the response body so it can be stored.
+/// Returns the body to send to the client plus the full bytes if they fit under the cap;
+/// larger (or interrupted) bodies keep streaming and are not stored.
+pub async fn capture_body(body: Body, cap: usize) -> (Body, Option<Bytes>) {
+    let mut upstream = body.into_data_stream();
+    let mut buf: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = upstream.next().await {
+        match chunk {
+            Ok(chunk) => {
+                buf.extend_from_slice(&chunk);
+                if buf.len() > cap {
+                    let head = stream::once(async move { Ok::<Bytes, axum::Error>(Bytes::from(buf)) });
+                    return (Body::from_stream(head.chain(upstream)), None);
+                }
+            }
+            Err(e) => {
+                let head = stream::once(async move { Ok::<Bytes, axum::Error>(Bytes::from(buf)) });
+                let failed = stream::once(async move { Err::<Bytes, axum::Error>(e) });
+                return (Body::from_stream(head.chain(failed)), None);
+            }
+        }
+    }
+
+    let bytes = Bytes::from(buf);
+    (Body::from(bytes.clone()), Some(bytes))
+}
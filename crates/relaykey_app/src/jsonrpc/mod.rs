@@ -0,0 +1,103 @@
+use serde_json::Value;
+
+use relaykey_db::models::JsonRpcConfig;
+
+/// One call in a JSON-RPC request (a batch has several).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcCall {
+    pub method: String,
+    pub cost: u32,
+}
+
+#[derive(Debug)]
+pub enum RpcError {
+    /// Not a JSON-RPC request object or non-empty batch.
+    Invalid(&'static str),
+    BatchTooLarge { size: usize, max: usize },
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Invalid(e) => write!(f, "invalid json-rpc request: {e}"),
+            RpcError::BatchTooLarge { size, max } => {
+                write!(f, "json-rpc batch of {size} calls exceeds the limit of {max}")
+            }
+        }
+    }
+}
+
+/// Exact method name, or a prefix ending in `*`.
+fn method_matches(pattern: &str, method: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => method.starts_with(prefix),
+        None => pattern == method,
+    }
+}
+
+/// Exact entries win over patterns; among patterns the longest prefix wins.
+fn cost_of(cfg: &JsonRpcConfig, method: &str) -> u32 {
+    if let Some(&cost) = cfg.method_costs.get(method) {
+        return cost;
+    }
+
+    cfg.method_costs
+        .iter()
+        .filter(|(pattern, _)| pattern.ends_with('*') && method_matches(pattern, method))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, &cost)| cost)
+        .unwrap_or(cfg.default_cost)
+}
+
+/// The calls in a request body, with their cost units.
+pub fn parse_calls(cfg: &JsonRpcConfig, body: &[u8]) -> Result<Vec<RpcCall>, RpcError> {
+    let value: Value = serde_json::from_slice(body).map_err(|_| RpcError::Invalid("body is not json"))?;
+
+    let items = match value {
+        Value::Array(items) if items.is_empty() => return Err(RpcError::Invalid("empty batch")),
+        Value::Array(items) => items,
+        Value::Object(_) => vec![value],
+        _ => return Err(RpcError::Invalid("expected an object or a batch array")),
+    };
+
+    if let Some(max) = cfg.max_batch_size {
+        if items.len() > max {
+            return Err(RpcError::BatchTooLarge {
+                size: items.len(),
+                max,
+            });
+        }
+    }
+
+    items
+        .iter()
+        .map(|item| {
+            let method = item
+                .get("method")
+                .and_then(Value::as_str)
+                .ok_or(RpcError::Invalid("call without a method"))?;
+            Ok(RpcCall {
+                method: method.to_string(),
+                cost: cost_of(cfg, method),
+            })
+        })
+        .collect()
+}
+
+/// The first call the partner's method lists don't allow.
+pub fn first_denied<'a>(cfg: &JsonRpcConfig, calls: &'a [RpcCall]) -> Option<&'a str> {
+    calls
+        .iter()
+        .map(|c| c.method.as_str())
+        .find(|method| {
+            cfg.deny_methods.iter().any(|p| method_matches(p, method))
+                || cfg
+                    .allow_methods
+                    .as_ref()
+                    .is_some_and(|allow| !allow.iter().any(|p| method_matches(p, method)))
+        })
+}
+
+pub fn total_cost(calls: &[RpcCall]) -> u64 {
+    calls.iter().map(|c| u64::from(c.cost)).sum()
+}
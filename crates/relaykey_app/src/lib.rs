@@ -1,15 +1,39 @@
 pub mod admin;
 pub mod app;
 pub mod auth;
+pub mod canary;
+pub mod circuit;
+pub mod coalesce;
+pub mod failover;
+pub mod forwarding;
+pub mod graphql;
+pub mod headers;
 pub mod health;
+pub mod hedge;
+pub mod idempotency;
+pub mod jsonrpc;
 pub mod limits;
 pub mod metrics;
+pub mod mirror;
+pub mod oauth;
+pub mod openapi;
 pub mod policies;
 pub mod proxy;
+pub mod redaction;
+pub mod request_body;
+pub mod request_schema;
+pub mod response_cache;
+pub mod response_body;
 pub mod retry;
+pub mod sandbox;
 pub mod settings;
 pub mod shutdown;
+pub mod ssrf;
 pub mod state;
 pub mod telemetry;
+pub mod tls;
+pub mod transform;
+pub mod upstream_auth;
 pub mod usage;
+pub mod websocket;
 pub mod x402;
@@ -10,7 +10,7 @@ use tracing::warn;
 
 use crate::auth::VirtualKeyCtx;
 use crate::state::AppState;
-use crate::usage::{insert_usage_event, BlockedReason};
+use crate::usage::{insert_usage_event, BlockedReason, UsageEvent};
 
 use super::{monthly_quota_allow_and_incr, token_bucket_allow};
 
@@ -74,14 +74,16 @@ pub async fn enforce_limits(
 
                 let _ = insert_usage_event(
                     &state.db,
-                    vk.id,
-                    vk.customer_id,
-                    &partner_name,
-                    &path,
-                    false,
-                    Some(BlockedReason::RateLimitExceeded),
-                    None,
-                    latency_ms,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_name,
+                        path: &path,
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::RateLimitExceeded),
+                        status_code: None,
+                        latency_ms,
+                    },
                 )
                 .await;
 
@@ -117,14 +119,16 @@ pub async fn enforce_limits(
 
                 let _ = insert_usage_event(
                     &state.db,
-                    vk.id,
-                    vk.customer_id,
-                    &partner_name,
-                    &path,
-                    false,
-                    Some(BlockedReason::MonthlyQuotaExceeded),
-                    None,
-                    latency_ms,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_name,
+                        path: &path,
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::MonthlyQuotaExceeded),
+                        status_code: None,
+                        latency_ms,
+                    },
                 )
                 .await;
 
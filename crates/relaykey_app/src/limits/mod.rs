@@ -130,3 +130,49 @@ return {1, nextv}
 
     Ok(allowed == 1)
 }
+
+/// Charge `units` more against the monthly quota, on top of the one unit the limits
+/// middleware took (JSON-RPC calls that cost more than one unit).
+/// Nothing is charged when it would go over the limit.
+/// Key: quota:{vk_id}:{YYYYMM}
+pub async fn monthly_quota_charge(
+    redis_conn: &mut MultiplexedConnection,
+    vk_id: Uuid,
+    monthly_limit: i32,
+    units: u64,
+) -> Result<bool, redis::RedisError> {
+    static LUA: &str = r#"
+local key = KEYS[1]
+local limit = tonumber(ARGV[1])
+local units = tonumber(ARGV[2])
+local ttl = tonumber(ARGV[3])
+
+local current = tonumber(redis.call("GET", key))
+if current == nil then current = 0 end
+
+if current + units > limit then
+  return {0, current}
+end
+
+local nextv = redis.call("INCRBY", key, units)
+if nextv == units then
+  redis.call("EXPIRE", key, ttl)
+end
+
+return {1, nextv}
+"#;
+
+    let key = format!("quota:{}:{}", vk_id, yyyymm_utc());
+    let ttl = seconds_until_next_month_utc();
+
+    let script = Script::new(LUA);
+    let (allowed, _count): (i64, i64) = script
+        .key(key)
+        .arg(monthly_limit)
+        .arg(units)
+        .arg(ttl)
+        .invoke_async(redis_conn)
+        .await?;
+
+    Ok(allowed == 1)
+}
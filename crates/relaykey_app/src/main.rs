@@ -1,5 +1,5 @@
 use axum::{http::Request, Extension};
-use std::{sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use tower::ServiceBuilder;
 use tower_http::{
@@ -17,9 +17,10 @@ async fn main() -> Result<(), String> {
     dotenvy::dotenv().ok();
     let settings = Settings::from_env()?;
 
-    let http = reqwest::Client::builder()
-        .connect_timeout(Duration::from_secs(5))
-        .timeout(Duration::from_secs(30))
+    let http = relaykey_app::tls::upstream_client_builder(false)
+        .build()
+        .map_err(|e| format!("HTTP client init failed: {e}"))?;
+    let internal_http = relaykey_app::tls::upstream_client_builder(true)
         .build()
         .map_err(|e| format!("HTTP client init failed: {e}"))?;
 
@@ -37,7 +38,14 @@ async fn main() -> Result<(), String> {
         db,
         redis,
         http,
+        internal_http,
         key_salt: settings.key_salt.clone(),
+        mock_environments: settings.mock_environments.clone(),
+        latency: Default::default(),
+        clients: Default::default(),
+        coalescer: Default::default(),
+        schemas: Default::default(),
+        redactors: Default::default(),
     });
 
     let middleware = ServiceBuilder::new()
@@ -71,7 +79,10 @@ async fn main() -> Result<(), String> {
 
     tracing::info!("Listening on {}", settings.bind_addr);
 
-    axum::serve(listener, app.into_make_service())
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
         .with_graceful_shutdown(relaykey_app::shutdown::shutdown())
         .await
         .map_err(|e| format!("Server error: {e}"))?;
@@ -0,0 +1,211 @@
+use axum::{
+    body::Body,
+    http::{HeaderMap, Method},
+};
+use futures_util::{stream, StreamExt};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::oneshot, time::timeout};
+use url::Url;
+use uuid::Uuid;
+
+use relaykey_db::{
+    models::MirrorConfig,
+    queries::{
+        mirror::{insert_mirror_comparison, MirrorComparison},
+        virtual_keys::{get_credential_for_partner, get_partner_by_name},
+    },
+};
+
+use crate::headers::HeaderFilter;
+use crate::request_body::ReplayableBody;
+use crate::ssrf::literal_host_forbidden;
+use crate::state::AppState;
+use crate::upstream_auth::UpstreamCredential;
+
+/// Whether to mirror this request. Non-idempotent requests only with `include_writes`.
+pub fn sampled(cfg: &MirrorConfig, idempotent: bool) -> bool {
+    cfg.enabled
+        && cfg.shadow_partner.is_some()
+        && (idempotent || cfg.include_writes)
+        && cfg.sample_rate > 0.0
+        && rand::thread_rng().gen_bool(cfg.sample_rate.min(1.0))
+}
+
+/// Relay `body` unchanged and send its SHA-256 (hex) once it has been read to the end.
+/// Nothing is sent when the body fails or is dropped early.
+pub fn hash_body(body: Body, digest: oneshot::Sender<String>) -> Body {
+    let state = (body.into_data_stream(), Sha256::new(), Some(digest));
+    let chunks = stream::unfold(state, |(mut upstream, mut hasher, digest)| async move {
+        match upstream.next().await {
+            Some(Ok(chunk)) => {
+                hasher.update(&chunk);
+                Some((Ok(chunk), (upstream, hasher, digest)))
+            }
+            Some(Err(e)) => Some((Err(e), (upstream, hasher, None))),
+            None => {
+                if let Some(digest) = digest {
+                    let _ = digest.send(hex::encode(hasher.finalize()));
+                }
+                None
+            }
+        }
+    });
+    Body::from_stream(chunks)
+}
+
+/// A primary request to replay against the shadow partner.
+pub struct MirrorJob {
+    pub partner_name: String,
+    pub shadow_partner: String,
+    pub virtual_key_id: Uuid,
+    pub customer_id: Uuid,
+    pub method: Method,
+    /// Usage path (`/proxy/...`), recorded with the comparison.
+    pub path: String,
+    /// Forwarded path and query, as sent to the primary.
+    pub path_and_query: String,
+    /// Client headers the primary received, before its credential was applied.
+    pub headers: HeaderMap,
+    pub body: Option<ReplayableBody>,
+    pub timeout: Duration,
+    pub primary_status: u16,
+    pub primary_latency_ms: i32,
+}
+
+/// The shadow call runs once the primary response has been relayed, off the request
+/// path. It is a single attempt: no retries, breakers, budgets, usage events or quota.
+/// `primary_digest` is the receiving end of the primary's `hash_body`.
+pub fn spawn(state: Arc<AppState>, job: MirrorJob, primary_digest: oneshot::Receiver<String>) {
+    tokio::spawn(async move {
+        let primary_body_sha256 = timeout(job.timeout, primary_digest)
+            .await
+            .ok()
+            .and_then(Result::ok);
+
+        let shadow = timeout(job.timeout, call_shadow(&state, &job)).await;
+        let (shadow_status, shadow_latency_ms, shadow_body_sha256, error) = match shadow {
+            Ok(Ok(s)) => (Some(s.status), Some(s.latency_ms), Some(s.body_sha256), None),
+            Ok(Err(e)) => (None, None, None, Some(e)),
+            Err(_) => (None, None, None, Some("shadow call timed out".to_string())),
+        };
+
+        if let Some(e) = &error {
+            tracing::warn!(partner = %job.partner_name, shadow = %job.shadow_partner, error = %e, "mirrored request failed");
+        }
+
+        let comparison = MirrorComparison {
+            partner_name: job.partner_name,
+            shadow_partner: job.shadow_partner,
+            virtual_key_id: job.virtual_key_id,
+            customer_id: job.customer_id,
+            method: job.method.to_string(),
+            path: job.path,
+            primary_status: job.primary_status as i32,
+            primary_latency_ms: job.primary_latency_ms,
+            primary_body_sha256,
+            shadow_status,
+            shadow_latency_ms,
+            shadow_body_sha256,
+            error,
+        };
+        if let Err(e) = insert_mirror_comparison(&state.db, &comparison).await {
+            tracing::warn!(error = %e, partner = %comparison.partner_name, "failed to record mirror comparison");
+        }
+    });
+}
+
+struct ShadowOutcome {
+    status: i32,
+    latency_ms: i32,
+    body_sha256: String,
+}
+
+async fn call_shadow(state: &AppState, job: &MirrorJob) -> Result<ShadowOutcome, String> {
+    let shadow = get_partner_by_name(&state.db, &job.shadow_partner)
+        .await
+        .map_err(|e| format!("db error: {e}"))?
+        .ok_or_else(|| "unknown shadow partner".to_string())?;
+    let cred = get_credential_for_partner(&state.db, shadow.id)
+        .await
+        .map_err(|e| format!("db error: {e}"))?
+        .ok_or_else(|| "shadow partner has no upstream credential".to_string())?;
+    let mut credential = UpstreamCredential::from_row(&cred).map_err(|e| e.message().to_string())?;
+
+    // Same SSRF rules as the primary: the path can't leave the shadow's origin.
+    let base = Url::parse(&shadow.base_url).map_err(|_| "invalid shadow base_url".to_string())?;
+    let url = base
+        .join(&job.path_and_query)
+        .ok()
+        .filter(|u| {
+            u.scheme() == base.scheme()
+                && u.host_str() == base.host_str()
+                && u.port_or_known_default() == base.port_or_known_default()
+        })
+        .filter(|u| shadow.internal || !literal_host_forbidden(u))
+        .ok_or_else(|| "blocked by SSRF guard".to_string())?;
+
+    let partner_client = state
+        .clients
+        .for_credential(&shadow, &cred)
+        .map_err(|e| e.to_string())?;
+    let http = match &partner_client {
+        Some(c) => &c.http,
+        None if shadow.internal => &state.internal_http,
+        None => &state.http,
+    };
+
+    let token_http = if shadow.internal {
+        &state.internal_http
+    } else {
+        &state.http
+    };
+    credential
+        .authorize(&state.redis, token_http, shadow.internal, false)
+        .await
+        .map_err(|e| format!("upstream authentication failed: {e}"))?;
+
+    let signing_body = match &job.body {
+        Some(b) if credential.signs_body() => b.to_bytes().await.map_err(|e| e.to_string())?,
+        _ => Default::default(),
+    };
+    let url = credential.url(&url);
+    let credential_headers = credential.headers(&job.method, &url, &signing_body);
+
+    let header_filter = HeaderFilter::new(&shadow.name, &shadow.header_rules.0);
+    let mut out = http.request(job.method.clone(), url);
+    for (name, value) in job.headers.iter() {
+        if header_filter.forward_request(name.as_str())
+            && !credential_headers.iter().any(|(n, _)| n == name)
+        {
+            out = out.header(name, value);
+        }
+    }
+    for (name, value) in header_filter.injected().iter().chain(&credential_headers) {
+        out = out.header(name.clone(), value.clone());
+    }
+    if let Some(body) = &job.body {
+        out = out.body(body.to_reqwest().await.map_err(|e| e.to_string())?);
+    }
+
+    let sent = Instant::now();
+    let resp = out.send().await.map_err(|e| e.to_string())?;
+    let latency_ms = sent.elapsed().as_millis().min(i32::MAX as u128) as i32;
+    let status = resp.status().as_u16() as i32;
+
+    let mut hasher = Sha256::new();
+    let mut chunks = resp.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        hasher.update(chunk.map_err(|e| e.to_string())?);
+    }
+
+    Ok(ShadowOutcome {
+        status,
+        latency_ms,
+        body_sha256: hex::encode(hasher.finalize()),
+    })
+}
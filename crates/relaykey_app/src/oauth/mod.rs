@@ -0,0 +1,200 @@
+use rand::Rng;
+use redis::{aio::MultiplexedConnection, AsyncCommands, Script};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use uuid::Uuid;
+
+use relaykey_db::models::{ClientAuthStyle, OAuth2ClientCredentials};
+
+/// How long one instance may hold the refresh lock (covers the token request).
+const LOCK_TTL: Duration = Duration::from_secs(10);
+/// How often waiters look for the token the lock holder is fetching.
+const WAIT_POLL: Duration = Duration::from_millis(50);
+/// Used when the token endpoint doesn't say how long the token lives.
+const DEFAULT_EXPIRES_SECS: u64 = 300;
+
+/// Key: rk:oauth_token:{credential_id}
+fn token_key(credential_id: Uuid) -> String {
+    format!("rk:oauth_token:{credential_id}")
+}
+
+/// Key: rk:oauth_lock:{credential_id}
+fn lock_key(credential_id: Uuid) -> String {
+    format!("rk:oauth_lock:{credential_id}")
+}
+
+#[derive(Debug)]
+pub enum TokenError {
+    Request(reqwest::Error),
+    Status(u16),
+    Response(reqwest::Error),
+    /// Not usable as a header value.
+    InvalidToken,
+    /// The token URL is an address in blocked space (see `ssrf`).
+    Blocked,
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Request(e) => write!(f, "token request failed: {e}"),
+            TokenError::Status(s) => write!(f, "token endpoint returned {s}"),
+            TokenError::Response(e) => write!(f, "invalid token response: {e}"),
+            TokenError::InvalidToken => write!(f, "access token is not a valid header value"),
+            TokenError::Blocked => write!(f, "token url points at a blocked address"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// Access token for the credential, shared across instances through Redis.
+///
+/// A cached token is used until `refresh_skew_secs` before it expires. Only one
+/// instance refreshes at a time; the others wait for its result. With `stale`
+/// (the token a partner just rejected) a cached copy of that same token is ignored
+/// and a new one is fetched. Fail-open: without Redis every caller fetches its own.
+pub async fn access_token(
+    redis_client: &redis::Client,
+    http: &reqwest::Client,
+    credential_id: Uuid,
+    cfg: &OAuth2ClientCredentials,
+    client_secret: &str,
+    stale: Option<&str>,
+) -> Result<String, TokenError> {
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, credential_id = %credential_id, "oauth token cache: redis unavailable (fail-open)");
+            return fetch_token(http, cfg, client_secret).await.map(|(token, _)| token);
+        }
+    };
+
+    let deadline = Instant::now() + LOCK_TTL;
+    loop {
+        if let Some(token) = cached(&mut conn, credential_id, stale).await {
+            return Ok(token);
+        }
+
+        let lock_token = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        let locked: redis::RedisResult<bool> = redis::cmd("SET")
+            .arg(lock_key(credential_id))
+            .arg(&lock_token)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOCK_TTL.as_millis() as u64)
+            .query_async::<_, Option<String>>(&mut conn)
+            .await
+            .map(|r| r.is_some());
+
+        match locked {
+            Ok(true) => {
+                // The previous holder may have stored a token between our cache miss and
+                // the lock; fetching another could revoke it.
+                let result = match cached(&mut conn, credential_id, stale).await {
+                    Some(token) => Ok(token),
+                    None => refresh(&mut conn, http, credential_id, cfg, client_secret).await,
+                };
+                release_lock(&mut conn, credential_id, &lock_token).await;
+                return result;
+            }
+            Ok(false) if Instant::now() < deadline => sleep(WAIT_POLL).await,
+            // Lock holder is stuck (or Redis is failing): fetch for ourselves.
+            Ok(false) | Err(_) => {
+                return refresh(&mut conn, http, credential_id, cfg, client_secret).await;
+            }
+        }
+    }
+}
+
+async fn cached(conn: &mut MultiplexedConnection, credential_id: Uuid, stale: Option<&str>) -> Option<String> {
+    let token: Option<String> = conn.get(token_key(credential_id)).await.ok().flatten();
+    token.filter(|t| Some(t.as_str()) != stale)
+}
+
+async fn refresh(
+    conn: &mut MultiplexedConnection,
+    http: &reqwest::Client,
+    credential_id: Uuid,
+    cfg: &OAuth2ClientCredentials,
+    client_secret: &str,
+) -> Result<String, TokenError> {
+    let (token, expires_in) = fetch_token(http, cfg, client_secret).await?;
+
+    let ttl = expires_in.saturating_sub(cfg.refresh_skew_secs);
+    if ttl > 0 {
+        if let Err(e) = conn
+            .set_ex::<_, _, ()>(token_key(credential_id), &token, ttl)
+            .await
+        {
+            tracing::warn!(error = %e, credential_id = %credential_id, "oauth token cache: failed to store token");
+        }
+    }
+
+    tracing::info!(credential_id = %credential_id, expires_in, "fetched upstream oauth token");
+    Ok(token)
+}
+
+async fn release_lock(conn: &mut MultiplexedConnection, credential_id: Uuid, lock_token: &str) {
+    const LUA: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+  return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+    let _: redis::RedisResult<i64> = Script::new(LUA)
+        .key(lock_key(credential_id))
+        .arg(lock_token)
+        .invoke_async(conn)
+        .await;
+}
+
+/// The client-credentials grant itself. Returns the token and its lifetime in seconds.
+async fn fetch_token(
+    http: &reqwest::Client,
+    cfg: &OAuth2ClientCredentials,
+    client_secret: &str,
+) -> Result<(String, u64), TokenError> {
+    let mut form: Vec<(&str, &str)> = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = cfg.scope.as_deref() {
+        form.push(("scope", scope));
+    }
+    if let Some(audience) = cfg.audience.as_deref() {
+        form.push(("audience", audience));
+    }
+
+    let mut req = http.post(&cfg.token_url);
+    match cfg.client_auth {
+        ClientAuthStyle::Body => {
+            form.push(("client_id", &cfg.client_id));
+            form.push(("client_secret", client_secret));
+        }
+        ClientAuthStyle::Basic => {
+            req = req.basic_auth(&cfg.client_id, Some(client_secret));
+        }
+    }
+
+    let resp = req
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| TokenError::Request(e.without_url()))?;
+
+    if !resp.status().is_success() {
+        return Err(TokenError::Status(resp.status().as_u16()));
+    }
+
+    let body: TokenResponse = resp
+        .json()
+        .await
+        .map_err(|e| TokenError::Response(e.without_url()))?;
+
+    Ok((body.access_token, body.expires_in.unwrap_or(DEFAULT_EXPIRES_SECS)))
+}
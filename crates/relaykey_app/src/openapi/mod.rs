@@ -0,0 +1,243 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{cmp::Reverse, collections::BTreeSet};
+use url::Url;
+use uuid::Uuid;
+
+use relaykey_db::{
+    models::RouteRule,
+    queries::{admin::upsert_partner_openapi_spec, policies::upsert_generated_policy},
+    Db,
+};
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// Not an OpenAPI 3 document we can read (422).
+    InvalidSpec(String),
+    Db(sqlx::Error),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::InvalidSpec(e) => write!(f, "invalid openapi document: {e}"),
+            ImportError::Db(e) => write!(f, "database error: {e}"),
+        }
+    }
+}
+
+/// Allowlist and route rules derived from a partner's OpenAPI document.
+#[derive(Debug, Serialize)]
+pub struct DraftPolicy {
+    pub endpoint_allowlist: Vec<String>,
+    pub routes: Vec<RouteRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportOutcome {
+    pub policy_id: Uuid,
+    pub policy_name: String,
+    pub policy_version: i32,
+    pub operations: usize,
+    #[serde(flatten)]
+    pub draft: DraftPolicy,
+}
+
+/// Name of the policy an import (re)generates for a partner.
+pub fn draft_policy_name(partner: &str) -> String {
+    format!("{partner}-openapi-draft")
+}
+
+/// `/users/{id}/orders` -> `/users/*/orders`. Inner segments holding a template
+/// parameter become wildcard segments. A trailing one is kept (`/users/{id}`): a trailing
+/// `/*` would also match every deeper path.
+pub fn path_pattern(template: &str) -> String {
+    let segments: Vec<&str> = template.split('/').collect();
+    let last = segments.len() - 1;
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| if segment.contains('{') && i < last { "*" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Path of the first server URL (`https://api.example.com/v2` -> `/v2`), which the
+/// document's paths are relative to.
+fn server_base_path(spec: &Value) -> String {
+    let Some(server) = spec
+        .pointer("/servers/0/url")
+        .and_then(Value::as_str)
+    else {
+        return String::new();
+    };
+
+    let path = match Url::parse(server) {
+        Ok(url) => url.path().to_string(),
+        // Relative server URL ("/v2").
+        Err(_) => server.to_string(),
+    };
+    path.trim_end_matches('/').to_string()
+}
+
+/// Method + path rules for every operation in the document, tagged with its
+/// `operationId`. More specific paths come first, since the first matching rule wins.
+pub fn draft_policy(partner: &str, spec: &Value) -> Result<DraftPolicy, ImportError> {
+    let version = spec.get("openapi").and_then(Value::as_str).unwrap_or("");
+    if !version.starts_with("3.") {
+        return Err(ImportError::InvalidSpec("expected an openapi 3.x document".to_string()));
+    }
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| ImportError::InvalidSpec("missing paths".to_string()))?;
+
+    let base = server_base_path(spec);
+    let mut allowlist = BTreeSet::new();
+    let mut routes = Vec::new();
+
+    for (template, item) in paths {
+        if !template.starts_with('/') {
+            return Err(ImportError::InvalidSpec(format!("path {template} must start with /")));
+        }
+        let pattern = format!("{base}{}", path_pattern(template));
+        allowlist.insert(pattern.clone());
+
+        let Some(item) = item.as_object() else {
+            continue;
+        };
+        for method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            routes.push(RouteRule {
+                partner: Some(partner.to_string()),
+                path: pattern.clone(),
+                methods: vec![method.to_ascii_uppercase()],
+                operation_id: operation
+                    .get("operationId")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                ..Default::default()
+            });
+        }
+    }
+
+    // Deeper paths first, then fewer wildcards: `/users/me` before `/users/*`.
+    routes.sort_by_key(|r| {
+        let segments: Vec<&str> = r.path.split('/').collect();
+        let wildcards = segments.iter().filter(|s| **s == "*" || s.contains('{')).count();
+        (Reverse(segments.len()), wildcards)
+    });
+
+    Ok(DraftPolicy {
+        endpoint_allowlist: allowlist.into_iter().collect(),
+        routes,
+    })
+}
+
+/// Store the document against the partner and (re)generate its draft policy.
+pub async fn import(
+    db: &Db,
+    partner_id: Uuid,
+    partner: &str,
+    spec: &Value,
+) -> Result<ImportOutcome, ImportError> {
+    let draft = draft_policy(partner, spec)?;
+    let operations = draft.routes.len();
+    let info = spec.get("info");
+    let title = info.and_then(|i| i.get("title")).and_then(Value::as_str);
+    let spec_version = info.and_then(|i| i.get("version")).and_then(Value::as_str);
+
+    upsert_partner_openapi_spec(
+        db,
+        partner_id,
+        spec,
+        title,
+        spec_version,
+        operations.min(i32::MAX as usize) as i32,
+    )
+    .await
+    .map_err(ImportError::Db)?;
+
+    let policy_name = draft_policy_name(partner);
+    let (policy_id, policy_version) =
+        upsert_generated_policy(db, &policy_name, &draft.endpoint_allowlist, &draft.routes)
+            .await
+            .map_err(ImportError::Db)?;
+
+    Ok(ImportOutcome {
+        policy_id,
+        policy_name,
+        policy_version,
+        operations,
+        draft,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{draft_policy, path_pattern};
+    use crate::policies::routes::path_matches;
+
+    #[test]
+    fn inner_parameters_become_wildcards() {
+        assert_eq!(path_pattern("/users/{id}/orders"), "/users/*/orders");
+        assert_eq!(path_pattern("/files/{name}.json/meta"), "/files/*/meta");
+        assert_eq!(path_pattern("/users/me"), "/users/me");
+    }
+
+    #[test]
+    fn trailing_parameter_matches_one_segment() {
+        let pattern = path_pattern("/users/{id}");
+        assert_eq!(pattern, "/users/{id}");
+        assert!(path_matches(&pattern, "/users/1"));
+        assert!(!path_matches(&pattern, "/users/1/delete"));
+        assert!(!path_matches(&pattern, "/users/1/anything/deeper"));
+        assert!(!path_matches(&pattern, "/users/"));
+
+        let pattern = path_pattern("/users/{id}/orders/{order_id}");
+        assert_eq!(pattern, "/users/*/orders/{order_id}");
+        assert!(path_matches(&pattern, "/users/1/orders/2"));
+        assert!(!path_matches(&pattern, "/users/1/orders/2/refund"));
+        assert!(!path_matches(&pattern, "/x/users/1/orders/2"));
+    }
+
+    #[test]
+    fn specific_routes_come_first() {
+        let spec = json!({
+            "openapi": "3.0.3",
+            "servers": [{ "url": "https://api.example.com/v2/" }],
+            "paths": {
+                "/users/{id}": { "get": { "operationId": "getUser" } },
+                "/users/me": { "get": { "operationId": "getMe" } },
+                "/users": { "post": { "operationId": "createUser" } },
+                "/users/{id}/orders": { "get": { "operationId": "listOrders" } }
+            }
+        });
+        let draft = draft_policy("shop", &spec).unwrap();
+
+        let order: Vec<(&str, Option<&str>)> = draft
+            .routes
+            .iter()
+            .map(|r| (r.path.as_str(), r.operation_id.as_deref()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("/v2/users/*/orders", Some("listOrders")),
+                ("/v2/users/me", Some("getMe")),
+                ("/v2/users/{id}", Some("getUser")),
+                ("/v2/users", Some("createUser")),
+            ]
+        );
+        assert_eq!(
+            draft.endpoint_allowlist,
+            ["/v2/users", "/v2/users/*/orders", "/v2/users/me", "/v2/users/{id}"]
+        );
+    }
+}
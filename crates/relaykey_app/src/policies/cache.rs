@@ -4,6 +4,7 @@ use uuid::Uuid;
 
 use crate::state::AppState;
 use relaykey_db::queries::policies::{get_policy_by_id, PolicyRow};
+use relaykey_db::RedisConn;
 
 const POLICY_CACHE_PREFIX: &str = "rk:policy:";
 const POLICY_CACHE_TTL_SECS: usize = 300; // 5 min; tweak as you like
@@ -61,3 +62,13 @@ pub async fn load_policy_bundle(state: &AppState, policy_id: Uuid) -> Result<Opt
 
     Ok(policy_opt)
 }
+
+/// Drop the cached copy after an admin change, so the next request loads the new
+/// version. Best-effort: without Redis there is no cached copy to go stale.
+pub async fn invalidate_policy(redis: &RedisConn, policy_id: Uuid) {
+    if let Ok(mut conn) = redis.get_multiplexed_async_connection().await {
+        if let Err(e) = conn.del::<_, ()>(cache_key(policy_id)).await {
+            tracing::warn!(policy_id = %policy_id, error = %e, "policy cache invalidation failed");
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod allowlist;
 pub mod cache;
+pub mod routes;
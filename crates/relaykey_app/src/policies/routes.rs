@@ -0,0 +1,99 @@
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use relaykey_db::{models::RouteRule, queries::policies::PolicyRow};
+
+/// Minimal glob:
+/// - "/v1/*" matches "/v1/x", "/v1/x/y", etc.
+/// - "*" as an inner segment matches exactly one segment: "/v1/users/*/orders"
+/// - an OpenAPI template segment matches exactly one segment, last ones included:
+///   "/v1/users/{id}" matches "/v1/users/1" but not "/v1/users/1/orders"
+/// - Exact matches if no wildcard
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, open_ended) = match pattern.strip_suffix("/*") {
+        Some(prefix) => (prefix, true),
+        None => (pattern, false),
+    };
+
+    let mut segments = path.split('/');
+    for expected in pattern.split('/') {
+        match segments.next() {
+            Some(s) if s == expected || (is_one_segment_wildcard(expected) && !s.is_empty()) => {}
+            _ => return false,
+        }
+    }
+    open_ended || segments.next().is_none()
+}
+
+fn is_one_segment_wildcard(segment: &str) -> bool {
+    segment == "*" || segment.contains('{')
+}
+
+/// `/proxy/{partner}/{tail}` -> (partner, "/{tail}").
+pub fn split_proxy_path(full_path: &str) -> (&str, String) {
+    let mut parts = full_path.splitn(4, '/');
+    let partner = parts.nth(2).unwrap_or("-");
+    let upstream_path = parts
+        .next()
+        .map(|rest| format!("/{}", rest))
+        .unwrap_or_else(|| "/".to_string());
+    (partner, upstream_path)
+}
+
+/// The first of the policy's route rules matching this request.
+pub fn route_for<'a>(
+    policy: &'a PolicyRow,
+    partner: &str,
+    method: &str,
+    upstream_path: &str,
+) -> Option<&'a RouteRule> {
+    route_index_for(policy, partner, method, upstream_path).map(|i| &policy.routes[i])
+}
+
+/// Position of the matching rule in `policy.routes`.
+pub fn route_index_for(
+    policy: &PolicyRow,
+    partner: &str,
+    method: &str,
+    upstream_path: &str,
+) -> Option<usize> {
+    policy.routes.iter().position(|r| {
+        r.partner.as_deref().is_none_or(|p| p == partner)
+            && (r.methods.is_empty() || r.methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+            && path_matches(&r.path, upstream_path)
+    })
+}
+
+/// Hex digest identifying "the same request" for caching and coalescing: upstream path,
+/// query, the values of `vary_headers` and (when given) the customer.
+pub fn request_fingerprint(
+    upstream_path: &str,
+    query: Option<&str>,
+    vary_headers: &[String],
+    request_headers: &HeaderMap,
+    customer_id: Option<Uuid>,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(upstream_path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(query.unwrap_or("").as_bytes());
+
+    for name in vary_headers {
+        let name = name.trim().to_ascii_lowercase();
+        hasher.update(b"\n");
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        for value in request_headers.get_all(name.as_str()) {
+            hasher.update(value.as_bytes());
+            hasher.update(b",");
+        }
+    }
+
+    hasher.update(b"\n");
+    if let Some(customer_id) = customer_id {
+        hasher.update(customer_id.as_bytes());
+    }
+
+    hex::encode(hasher.finalize())
+}
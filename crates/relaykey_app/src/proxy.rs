@@ -1,51 +1,81 @@
 use axum::{
     body::{Body, Bytes},
-    extract::{Extension, Path},
-    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    extract::{ws::WebSocketUpgrade, ConnectInfo, Extension, Path},
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_LENGTH, RETRY_AFTER},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+    },
     response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+use tokio::{
+    sync::oneshot,
+    time::{sleep, timeout, Duration},
 };
-use std::{sync::Arc, time::Instant};
-use tokio::time::{sleep, timeout, Duration};
 use url::Url;
+use uuid::Uuid;
 
 use crate::auth::VirtualKeyCtx;
+use crate::canary::{self, canary_record};
+use crate::circuit::{breaker_check, breaker_record, breaker_release, BreakerDecision};
+use crate::failover::{endpoint_order, mark_endpoint_down, mark_endpoint_up};
+use crate::forwarding::Forwarding;
+use crate::graphql::{inspect_body, inspect_query, rejection_response, GraphQlRejection};
+use crate::headers::HeaderFilter;
+use crate::hedge::{hedge_delay, race, HedgeTarget, Leg, Raced};
+use crate::idempotency::{
+    self, capture_body, client_idempotency_key, Claim, IdempotencyCtx, IDEMPOTENCY_HEADER,
+    MAX_STORED_BODY_BYTES,
+};
+use crate::jsonrpc::{first_denied, parse_calls, total_cost, RpcCall, RpcError};
+use crate::limits::monthly_quota_charge;
+use crate::mirror::{self, hash_body, sampled, MirrorJob};
+use crate::policies::routes::{path_matches, route_index_for};
+use crate::response_body::{
+    accepts_event_stream, guard_response, is_event_stream, ResponseLimits, ResponseUsage,
+    DEFAULT_STREAM_TIMEOUT,
+};
+use crate::request_body::{
+    buffer_request, declared_too_large, max_request_body_bytes, BodyError, BufferedRequest,
+    ForwardBody,
+};
+use crate::request_schema::{invalid_response, validate, SchemaViolation};
+use crate::tls::PartnerClient;
+use crate::ssrf::{is_ssrf_blocked, literal_host_forbidden};
 use crate::state::AppState;
-use crate::usage::{insert_usage_event, BlockedReason};
+use crate::transform::{apply, is_json, request_headers, TemplateVars, TransformError};
+use crate::upstream_auth::UpstreamCredential;
+use crate::usage::{insert_usage_event, insert_usage_event_detailed, BlockedReason, UsageDetails, UsageEvent};
+use crate::websocket::{connect_upstream, is_handshake_header, relay, websocket_url};
 
 // Adjust if your path differs
+use relaykey_db::queries::fixtures::{list_fixtures, NewFixture};
 use relaykey_db::queries::policies::PolicyRow;
-use relaykey_db::queries::virtual_keys::{get_credential_for_partner, get_partner_by_name};
+use relaykey_db::queries::virtual_keys::{
+    get_alternate_credential_for_partner, get_credential_for_partner, get_partner_by_name,
+};
 
+use crate::sandbox::{
+    self, body_fingerprint, find_fixture, fixture_response, miss_response, mode_for,
+    normalize_query, SandboxMode, MAX_RECORDED_BYTES,
+};
 use crate::retry::{
+    advice::upstream_retry_after,
     budget::{allow_retry_dual_budget, RetryBudgets},
     classify::{classify_reqwest_error, classify_status, RetryClass},
+    cooldown::{partner_cooldown_remaining, set_partner_cooldown},
     partner::{profile_for_partner, status_retry_allowed},
     policy::RetryPolicy,
 };
 
-static HOP_BY_HOP: &[&str] = &[
-    "connection",
-    "keep-alive",
-    "proxy-authenticate",
-    "proxy-authorization",
-    "te",
-    "trailer",
-    "transfer-encoding",
-    "upgrade",
-];
-
-fn is_hop_by_hop(name: &str) -> bool {
-    HOP_BY_HOP.contains(&name)
-}
-
 fn is_idempotent(method: &Method) -> bool {
     matches!(method, &Method::GET | &Method::HEAD | &Method::OPTIONS)
 }
 
-/// Very lightweight allowlist matcher:
-/// - exact match: "/get"
-/// - prefix glob: "/v1/*" matches "/v1/anything"
-/// - single '*' anywhere: supports simple contains/prefix use-cases (not a full glob engine)
+/// Allowlist matcher, with the glob syntax of route rules (see `path_matches`).
+/// A lone "*" allows every path.
 fn path_allowed(allowlist: &[String], forwarded_path: &str) -> bool {
     if allowlist.is_empty() {
         // If you want "deny by default", change this to false.
@@ -59,33 +89,16 @@ fn match_one(pattern: &str, path: &str) -> bool {
     // Normalize: ensure forwarded path starts with '/'
     let path = if path.starts_with('/') { path } else { "/" };
 
-    // Fast exact match
-    if !pattern.contains('*') {
-        return pattern == path;
-    }
-
-    // Handle common "/prefix/*"
-    if let Some(prefix) = pattern.strip_suffix("/*") {
-        return path.starts_with(prefix)
-            && (path.len() == prefix.len() || path.as_bytes()[prefix.len()] == b'/');
-    }
-
-    // Very simple wildcard: split on '*' and require pieces in order
-    // Example: "/v1/*/foo" => pieces ["/v1/", "/foo"]
-    let parts: Vec<&str> = pattern.split('*').filter(|s| !s.is_empty()).collect();
-    if parts.is_empty() {
-        return true;
-    }
+    pattern == "*" || path_matches(pattern, path)
+}
 
-    let mut idx = 0usize;
-    for part in parts {
-        if let Some(pos) = path[idx..].find(part) {
-            idx += pos + part.len();
-        } else {
-            return false;
-        }
-    }
-    true
+/// Join `path_and_query` onto `base`, refusing anything that leaves the base origin.
+fn join_same_origin(base: &Url, path_and_query: &str) -> Option<Url> {
+    let joined = base.join(path_and_query).ok()?;
+    let same_origin = joined.scheme() == base.scheme()
+        && joined.host_str() == base.host_str()
+        && joined.port_or_known_default() == base.port_or_known_default();
+    same_origin.then_some(joined)
 }
 
 fn backoff_ms(attempt: usize, base: u64, cap: u64) -> u64 {
@@ -97,9 +110,11 @@ fn backoff_ms(attempt: usize, base: u64, cap: u64) -> u64 {
 
 fn cheap_jitter_ms(attempt: usize) -> u64 {
     // deterministic tiny jitter (no rand dependency)
-    ((attempt as u64 * 37) % 23) as u64
+    (attempt as u64 * 37) % 23
 }
 
+// One argument per axum extractor.
+#[allow(clippy::too_many_arguments)]
 pub async fn handler(
     Extension(state): Extension<Arc<AppState>>,
     Extension(vk): Extension<VirtualKeyCtx>,
@@ -108,7 +123,10 @@ pub async fn handler(
     method: Method,
     uri: Uri,
     headers: HeaderMap,
-    body: Bytes,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    buffered: Option<Extension<BufferedRequest>>,
+    ws: Option<WebSocketUpgrade>,
+    body: Body,
 ) -> Response {
     let start = Instant::now();
 
@@ -117,21 +135,26 @@ pub async fn handler(
         return StatusCode::METHOD_NOT_ALLOWED.into_response();
     }
 
-    // 1) Load partner
-    let partner_row = match get_partner_by_name(&state.db, &partner).await {
+    // 1) Load partner. A canary on the policy may hand this caller to an alternate partner;
+    // everything from here on (usage, breakers, credentials) is about the partner that serves it.
+    let canary = canary::route(&policy, &partner, &vk);
+    let physical_partner = canary.map_or(partner.as_str(), |c| c.alternate.as_str());
+    let partner_row = match get_partner_by_name(&state.db, physical_partner).await {
         Ok(Some(p)) => p,
         Ok(None) => {
             let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
             let _ = insert_usage_event(
                 &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner,
-                uri.path(),
-                false,
-                Some(BlockedReason::UnknownPartner),
-                None,
-                latency_ms,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: physical_partner,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::UnknownPartner),
+                    status_code: None,
+                    latency_ms,
+                },
             )
             .await;
             return (StatusCode::NOT_FOUND, "unknown partner").into_response();
@@ -140,76 +163,99 @@ pub async fn handler(
             let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
             let _ = insert_usage_event(
                 &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner,
-                uri.path(),
-                false,
-                Some(BlockedReason::DbError),
-                None,
-                latency_ms,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: physical_partner,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::DbError),
+                    status_code: None,
+                    latency_ms,
+                },
             )
             .await;
             return (StatusCode::INTERNAL_SERVER_ERROR, "db error").into_response();
         }
     };
 
+    // Sandboxed keys: mocked requests never reach the partner, so they need neither a
+    // credential nor a client for it.
+    let sandbox_mode = mode_for(&vk, &state.mock_environments);
+    let mocked = sandbox_mode == SandboxMode::Mock;
+
     // 2) Load credential (latest header for partner)
-    let cred = match get_credential_for_partner(&state.db, partner_row.id).await {
-        Ok(Some(c)) => c,
-        Ok(None) => {
-            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
-            let _ = insert_usage_event(
-                &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner_row.name,
-                uri.path(),
-                false,
-                Some(BlockedReason::MissingUpstreamCredential),
-                None,
-                latency_ms,
-            )
-            .await;
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "missing upstream credential",
-            )
-                .into_response();
-        }
-        Err(_) => {
-            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
-            let _ = insert_usage_event(
-                &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner_row.name,
-                uri.path(),
-                false,
-                Some(BlockedReason::DbError),
-                None,
-                latency_ms,
-            )
-            .await;
-            return (StatusCode::INTERNAL_SERVER_ERROR, "db error").into_response();
+    let cred = if mocked {
+        None
+    } else {
+        match get_credential_for_partner(&state.db, partner_row.id).await {
+            Ok(Some(c)) => Some(c),
+            Ok(None) => {
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let _ = insert_usage_event(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::MissingUpstreamCredential),
+                        status_code: None,
+                        latency_ms,
+                    },
+                )
+                .await;
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "missing upstream credential",
+                )
+                    .into_response();
+            }
+            Err(_) => {
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let _ = insert_usage_event(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::DbError),
+                        status_code: None,
+                        latency_ms,
+                    },
+                )
+                .await;
+                return (StatusCode::INTERNAL_SERVER_ERROR, "db error").into_response();
+            }
         }
     };
 
-    // 3) Build upstream URL safely (SSRF protection)
-    let base = match Url::parse(&partner_row.base_url) {
-        Ok(u) => u,
-        Err(_) => {
+    // 3) Build upstream URLs safely (SSRF protection)
+    let endpoint_cfg = &partner_row.upstream_endpoints.0;
+    let endpoint_urls = partner_row.endpoint_urls();
+    let bases = match endpoint_urls
+        .iter()
+        .map(|u| Url::parse(u))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(b) if !b.is_empty() => b,
+        _ => {
             let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
             let _ = insert_usage_event(
                 &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner_row.name,
-                uri.path(),
-                false,
-                Some(BlockedReason::InvalidPartnerBaseUrl),
-                None,
-                latency_ms,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::InvalidPartnerBaseUrl),
+                    status_code: None,
+                    latency_ms,
+                },
             )
             .await;
             return (
@@ -226,14 +272,16 @@ pub async fn handler(
         let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
         let _ = insert_usage_event(
             &state.db,
-            vk.id,
-            vk.customer_id,
-            &partner_row.name,
-            uri.path(),
-            false,
-            Some(BlockedReason::SsrfBlocked),
-            None,
-            latency_ms,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner_row.name,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: Some(BlockedReason::SsrfBlocked),
+                status_code: None,
+                latency_ms,
+            },
         )
         .await;
         return (StatusCode::BAD_REQUEST, "blocked by SSRF guard").into_response();
@@ -252,81 +300,683 @@ pub async fn handler(
         let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
         let _ = insert_usage_event(
             &state.db,
-            vk.id,
-            vk.customer_id,
-            &partner_row.name,
-            uri.path(),
-            false,
-            Some(BlockedReason::EndpointNotAllowed),
-            None,
-            latency_ms,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner_row.name,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: Some(BlockedReason::EndpointNotAllowed),
+                status_code: None,
+                latency_ms,
+            },
         )
         .await;
 
         return (StatusCode::FORBIDDEN, "Endpoint not allowed").into_response();
     }
 
-    let joined = match base.join(&(forwarded_path.clone() + &query)) {
+    let joined_all = match bases
+        .iter()
+        .map(|base| base.join(&(forwarded_path.clone() + &query)))
+        .collect::<Result<Vec<_>, _>>()
+    {
         Ok(u) => u,
         Err(_) => {
             let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
             let _ = insert_usage_event(
                 &state.db,
-                vk.id,
-                vk.customer_id,
-                &partner_row.name,
-                uri.path(),
-                false,
-                Some(BlockedReason::InvalidUpstreamPath),
-                None,
-                latency_ms,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::InvalidUpstreamPath),
+                    status_code: None,
+                    latency_ms,
+                },
             )
             .await;
             return (StatusCode::BAD_REQUEST, "invalid upstream path").into_response();
         }
     };
 
-    // SSRF guard: enforce origin unchanged (host/scheme/port must match base)
-    if joined.scheme() != base.scheme()
-        || joined.host_str() != base.host_str()
-        || joined.port_or_known_default() != base.port_or_known_default()
-    {
+    // SSRF guard: every candidate must keep the origin (host/scheme/port) of its
+    // configured base, so failover can only ever reach the configured set.
+    // External partners also never reach private address space: IP literals are
+    // checked here, hostnames by the upstream client's resolver at connect time.
+    let same_origin = bases.iter().zip(&joined_all).all(|(base, joined)| {
+        joined.scheme() == base.scheme()
+            && joined.host_str() == base.host_str()
+            && joined.port_or_known_default() == base.port_or_known_default()
+    });
+    let forbidden_address =
+        !partner_row.internal && joined_all.iter().any(literal_host_forbidden);
+    if !same_origin || forbidden_address {
         let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
         let _ = insert_usage_event(
             &state.db,
-            vk.id,
-            vk.customer_id,
-            &partner_row.name,
-            uri.path(),
-            false,
-            Some(BlockedReason::SsrfBlocked),
-            None,
-            latency_ms,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner_row.name,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: Some(BlockedReason::SsrfBlocked),
+                status_code: None,
+                latency_ms,
+            },
         )
         .await;
         return (StatusCode::BAD_REQUEST, "blocked by SSRF guard").into_response();
     }
 
-    // Prepare parsed credential header once
-    let header_name = match HeaderName::from_bytes(cred.header_name.as_bytes()) {
-        Ok(h) => h,
-        Err(_) => {
+    // Partners with their own TLS settings (mTLS identity, custom CA, pinning), and
+    // credentials with their own identity, get a dedicated client; everyone else shares
+    // the default one.
+    let partner_client = match cred.as_ref().map(|c| state.clients.for_credential(&partner_row, c)) {
+        None => None,
+        Some(Ok(c)) => c,
+        Some(Err(e)) => {
+            tracing::error!(error = %e, partner = %partner_row.name, "invalid partner tls configuration");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "invalid credential header_name",
+                "invalid partner tls configuration",
             )
-                .into_response()
+                .into_response();
         }
     };
+    let client_http = |client: &Option<PartnerClient>| match client {
+        Some(c) => c.http.clone(),
+        None if partner_row.internal => state.internal_http.clone(),
+        None => state.http.clone(),
+    };
+    let http = client_http(&partner_client);
+
+    // Per-partner header allow/deny/inject/rewrite rules.
+    let header_filter = HeaderFilter::new(&partner_row.name, &partner_row.header_rules.0);
+
+    // Forwarded / X-Forwarded-*, request id and traceparent, as configured for the partner.
+    let request_id = headers.get("x-request-id").and_then(|v| v.to_str().ok());
+    let forwarding = Forwarding::new(
+        &partner_row.forwarding.0,
+        &headers,
+        connect_info.map(|ConnectInfo(addr)| addr.ip()),
+        uri.scheme_str().unwrap_or("http"),
+        request_id,
+    );
+
+    // Parse the credential once; its scheme is applied to every upstream attempt.
+    let credential = match cred.as_ref().map(UpstreamCredential::from_row) {
+        None => None,
+        Some(Ok(c)) => Some(c),
+        Some(Err(e)) => return (StatusCode::INTERNAL_SERVER_ERROR, e.message()).into_response(),
+    };
 
-    let header_value = match HeaderValue::from_str(&cred.header_value) {
-        Ok(v) => v,
-        Err(_) => {
+    // Request body: buffered (replayable, spilled to disk when large) when retries,
+    // hedges or idempotency need it; otherwise streamed straight to the partner.
+    let max_body = max_request_body_bytes(&policy);
+    let client_key = client_idempotency_key(&headers);
+    // HMAC signing covers the body, so it has to be read before the first attempt;
+    // token credentials may resend it once after a 401 with a refreshed token.
+    // JSON-RPC partners: POST bodies are read up front to check each call.
+    let rpc_cfg = &partner_row.jsonrpc.0;
+    let inspect_rpc = rpc_cfg.enabled && method == Method::POST;
+    // GraphQL partners: the document (POST body or GET query string) is checked likewise.
+    let graphql_cfg = &partner_row.graphql.0;
+    let inspect_graphql = graphql_cfg.enabled && (method == Method::POST || method == Method::GET);
+    // Routes with a request schema: the body is validated before it is forwarded.
+    // Route rules name the partner of the proxy path, whoever serves the request.
+    let route_index = route_index_for(&policy, &partner, method.as_str(), &forwarded_path);
+    let schema_validator = route_index.and_then(|i| state.schemas.validator(&policy, i));
+    let operation_id: Option<Arc<str>> =
+        route_index.and_then(|i| policy.routes[i].operation_id.as_deref().map(Arc::from));
+    // Route transforms: request bodies are rewritten before forwarding, so they are read up front.
+    let transform = route_index.and_then(|i| policy.routes[i].transform.as_ref());
+    let transform_vars = TemplateVars::from_key(&vk);
+    let transforms_request = transform.is_some_and(|t| !t.request.is_empty());
+    let transforms_response = transform.is_some_and(|t| !t.response.is_empty());
+    let templated_headers = transform
+        .map(|t| request_headers(t, &transform_vars))
+        .unwrap_or_default();
+    // Keys on a redacting policy only ever see masked responses.
+    let redactor = match state.redactors.for_policy(&policy) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!(error = %e, policy_id = %policy.id, "invalid redaction profile");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "invalid redaction profile").into_response();
+        }
+    };
+    // Mirrored requests are replayed to the shadow partner afterwards, so they are kept.
+    // WebSockets and event streams are never mirrored.
+    let mirror_cfg = &partner_row.mirror.0;
+    let mirrored =
+        ws.is_none() && !accepts_event_stream(&headers) && sampled(mirror_cfg, is_idempotent(&method));
+    // Sandboxed keys: fixtures are matched (and recorded) by the body the client sent.
+    // Recorded and canned responses are stored uncompressed.
+    let plain_response =
+        transforms_response || redactor.is_some() || sandbox_mode == SandboxMode::Record;
+    let needs_replay = is_idempotent(&method)
+        || client_key.is_some()
+        || credential.as_ref().is_some_and(|c| c.signs_body() || c.uses_token())
+        || inspect_rpc
+        || inspect_graphql
+        || schema_validator.is_some()
+        || transforms_request
+        || mirrored
+        || sandbox_mode != SandboxMode::Live;
+
+    let body_result = match buffered {
+        // x402 already read (and hashed) it
+        Some(Extension(b)) => Ok(ForwardBody::Buffered(b)),
+        None if declared_too_large(&headers, max_body) => Err(BodyError::TooLarge),
+        None if needs_replay => {
+            let path_and_query = uri
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or(uri.path());
+            buffer_request(body, &method, path_and_query, max_body)
+                .await
+                .map(ForwardBody::Buffered)
+        }
+        None => Ok(ForwardBody::streaming(body, max_body)),
+    };
+
+    let mut forward_body = match body_result {
+        Ok(b) => b,
+        Err(BodyError::TooLarge) => {
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::RequestBodyTooLarge),
+                    status_code: None,
+                    latency_ms,
+                },
+            )
+            .await;
+            return (StatusCode::PAYLOAD_TOO_LARGE, "request body too large").into_response();
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to buffer request body");
+            return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+        }
+    };
+
+    let rpc_calls: Option<Arc<[RpcCall]>> = if inspect_rpc {
+        let parsed = match forward_body.replayable() {
+            Some(b) => match b.to_bytes().await {
+                Ok(bytes) => parse_calls(rpc_cfg, &bytes),
+                Err(e) => {
+                    tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body");
+                    return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+                }
+            },
+            None => Err(RpcError::Invalid("body not available")),
+        };
+
+        let calls: Arc<[RpcCall]> = match parsed {
+            Ok(calls) => calls.into(),
+            Err(e) => {
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let _ = insert_usage_event(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::InvalidRpcRequest),
+                        status_code: None,
+                        latency_ms,
+                    },
+                )
+                .await;
+                return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+            }
+        };
+
+        let details = UsageDetails {
+            rpc_calls: Some(calls.clone()),
+            ..Default::default()
+        };
+
+        if let Some(denied) = first_denied(rpc_cfg, &calls) {
+            let message = format!("json-rpc method not allowed: {denied}");
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event_detailed(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::RpcMethodNotAllowed),
+                    status_code: None,
+                    latency_ms,
+                },
+                details,
+            )
+            .await;
+            return (StatusCode::FORBIDDEN, message).into_response();
+        }
+
+        // The limits middleware charged one unit; expensive calls and batches pay the rest.
+        let extra_units = total_cost(&calls).saturating_sub(1);
+        // Mocked calls cost the partner nothing, so they aren't charged.
+        if let Some(limit) = vk.monthly_quota.filter(|_| extra_units > 0 && !mocked) {
+            let charged = match state.redis.get_multiplexed_async_connection().await {
+                Ok(mut conn) => monthly_quota_charge(&mut conn, vk.id, limit, extra_units).await,
+                Err(e) => Err(e),
+            };
+            match charged {
+                Ok(true) => {}
+                Ok(false) => {
+                    let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                    let _ = insert_usage_event_detailed(
+                        &state.db,
+                        UsageEvent {
+                            virtual_key_id: vk.id,
+                            customer_id: vk.customer_id,
+                            partner_name: &partner_row.name,
+                            path: uri.path(),
+                            forwarded: false,
+                            blocked_reason: Some(BlockedReason::MonthlyQuotaExceeded),
+                            status_code: None,
+                            latency_ms,
+                        },
+                        details,
+                    )
+                    .await;
+                    return (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        Json(serde_json::json!({ "code": "monthly_quota_exceeded" })),
+                    )
+                        .into_response();
+                }
+                // FAIL-OPEN
+                Err(e) => {
+                    tracing::warn!(error = %e, vk_id = %vk.id, partner = %partner_row.name, "json-rpc cost charge error (fail-open)");
+                }
+            }
+        }
+
+        Some(calls)
+    } else {
+        None
+    };
+
+    let graphql_operation: Option<Arc<str>> = if inspect_graphql {
+        let inspected = if method == Method::GET {
+            inspect_query(graphql_cfg, uri.query())
+        } else {
+            match forward_body.replayable() {
+                Some(b) => match b.to_bytes().await {
+                    Ok(bytes) => inspect_body(graphql_cfg, &bytes),
+                    Err(e) => {
+                        tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body");
+                        return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+                    }
+                },
+                None => Err(GraphQlRejection::Invalid("body not available".to_string())),
+            }
+        };
+
+        match inspected {
+            Ok(operation) => operation.name.map(Arc::from),
+            Err(rejection) => {
+                tracing::info!(partner = %partner_row.name, vk_id = %vk.id, reason = %rejection.message(), "graphql operation rejected");
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let _ = insert_usage_event(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::GraphqlOperationRejected),
+                        status_code: None,
+                        latency_ms,
+                    },
+                )
+                .await;
+                return rejection_response(&rejection);
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(validator) = &schema_validator {
+        let checked = match forward_body.replayable() {
+            Some(b) => match b.to_bytes().await {
+                Ok(bytes) => validate(validator, &bytes),
+                Err(e) => {
+                    tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body");
+                    return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+                }
+            },
+            None => Err(vec![SchemaViolation {
+                path: String::new(),
+                message: "body not available".to_string(),
+            }]),
+        };
+
+        if let Err(violations) = checked {
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::RequestSchemaInvalid),
+                    status_code: None,
+                    latency_ms,
+                },
+            )
+            .await;
+            return invalid_response(&violations);
+        }
+    }
+
+    let sandbox_body = if sandbox_mode == SandboxMode::Live {
+        None
+    } else {
+        match forward_body.replayable() {
+            Some(b) => match b.to_bytes().await {
+                Ok(bytes) => body_fingerprint(&bytes),
+                Err(e) => {
+                    tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body");
+                    return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+                }
+            },
+            None => None,
+        }
+    };
+    let sandbox_query = normalize_query(uri.query());
+
+    // Mock mode: answered from the fixtures of the partner named in the path; nothing is sent.
+    if mocked {
+        let fixtures = match list_fixtures(&state.db, &partner, Some(method.as_str())).await {
+            Ok(f) => f,
+            Err(e) => {
+                tracing::error!(error = %e, partner = %partner, "list_fixtures failed");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "db error").into_response();
+            }
+        };
+        let fixture = find_fixture(&fixtures, &forwarded_path, &sandbox_query, sandbox_body.as_deref());
+        let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+        let details = UsageDetails {
+            mocked: true,
+            rpc_calls: rpc_calls.clone(),
+            graphql_operation: graphql_operation.clone(),
+            operation_id: operation_id.clone(),
+            ..Default::default()
+        };
+
+        let Some(fixture) = fixture.filter(|_| ws.is_none()) else {
+            let _ = insert_usage_event_detailed(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::MockFixtureNotFound),
+                    status_code: Some(StatusCode::NOT_FOUND.as_u16()),
+                    latency_ms,
+                },
+                details,
+            )
+            .await;
+            return miss_response();
+        };
+
+        let response = fixture_response(fixture, &transform_vars);
+        let _ = insert_usage_event_detailed(
+            &state.db,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: None,
+                status_code: Some(response.status().as_u16()),
+                latency_ms,
+            },
+            details,
+        )
+        .await;
+        return response;
+    }
+    let (Some(cred), Some(mut credential)) = (cred, credential) else {
+        unreachable!("only mocked requests skip the upstream credential");
+    };
+
+    // The partner gets the rewritten body; the request hash (x402, idempotency) stays the
+    // one of the body the client sent.
+    if let Some(t) = transform.filter(|_| transforms_request) {
+        let transformed = match forward_body.replayable() {
+            Some(b) => match b.to_bytes().await {
+                Ok(bytes) => apply(&t.request, &transform_vars, &bytes),
+                Err(e) => {
+                    tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body");
+                    return (StatusCode::BAD_REQUEST, "failed to read request body").into_response();
+                }
+            },
+            None => Err(TransformError::NotJson),
+        };
+
+        match transformed {
+            Ok(bytes) => forward_body.replace_body(bytes),
+            Err(e) => {
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let _ = insert_usage_event(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: false,
+                        blocked_reason: Some(BlockedReason::RequestTransformFailed),
+                        status_code: None,
+                        latency_ms,
+                    },
+                )
+                .await;
+                return (StatusCode::BAD_REQUEST, format!("request transform failed: {e}"))
+                    .into_response();
+            }
+        }
+    }
+
+    // Idempotency-Key: duplicates of an answered request are replayed from the
+    // store instead of reaching the vendor a second time.
+    let idempotency = client_key
+        .zip(forward_body.request_hash())
+        .map(|(client_key, request_hash)| {
+            IdempotencyCtx::new(vk.id, &partner_row.name, &client_key, request_hash.to_string())
+        });
+
+    if let Some(idem) = &idempotency {
+        let request_timeout = Duration::from_millis(policy.timeout_ms.max(1) as u64);
+        let (resp, blocked_reason, status) =
+            match idempotency::claim(&state.redis, idem, request_timeout).await {
+                Claim::Proceed => (None, None, None),
+                Claim::Replay(resp) => {
+                    let status = resp.status().as_u16();
+                    (Some(resp), None, Some(status))
+                }
+                Claim::InFlight => (
+                    Some(
+                        (
+                            StatusCode::CONFLICT,
+                            "request with this idempotency key is in progress",
+                        )
+                            .into_response(),
+                    ),
+                    Some(BlockedReason::IdempotencyKeyInFlight),
+                    None,
+                ),
+                Claim::Mismatch => (
+                    Some(
+                        (
+                            StatusCode::UNPROCESSABLE_ENTITY,
+                            "idempotency key reused with a different request",
+                        )
+                            .into_response(),
+                    ),
+                    Some(BlockedReason::IdempotencyKeyMismatch),
+                    None,
+                ),
+                Claim::NotReplayable => (
+                    Some(
+                        (
+                            StatusCode::CONFLICT,
+                            "original response for this idempotency key cannot be replayed",
+                        )
+                            .into_response(),
+                    ),
+                    Some(BlockedReason::IdempotencyResponseUnavailable),
+                    None,
+                ),
+            };
+
+        if let Some(resp) = resp {
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason,
+                    status_code: status,
+                    latency_ms,
+                },
+            )
+            .await;
+            return resp;
+        }
+    }
+
+    // A partner that told us (or another gateway instance) to back off stays
+    // untouched until its advertised reset time.
+    if let Some(remaining) = partner_cooldown_remaining(&state.redis, &partner_row.name).await {
+        if let Some(idem) = &idempotency {
+            idempotency::release(&state.redis, idem).await;
+        }
+
+        let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+        let _ = insert_usage_event(
+            &state.db,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner_row.name,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: Some(BlockedReason::PartnerCoolingDown),
+                status_code: None,
+                latency_ms,
+            },
+        )
+        .await;
+
+        let retry_after_secs = remaining.as_millis().div_ceil(1000);
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, retry_after_secs.to_string())],
+            "partner cooling down",
+        )
+            .into_response();
+    }
+
+    // Token-based credentials (OAuth2): shared cached token, refreshed under a lock.
+    let token_http = if partner_row.internal {
+        &state.internal_http
+    } else {
+        &state.http
+    };
+    if let Err(e) = credential.authorize(&state.redis, token_http, partner_row.internal, false).await {
+        tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to obtain upstream access token");
+
+        if let Some(idem) = &idempotency {
+            idempotency::release(&state.redis, idem).await;
+        }
+
+        let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+        let _ = insert_usage_event(
+            &state.db,
+            UsageEvent {
+                virtual_key_id: vk.id,
+                customer_id: vk.customer_id,
+                partner_name: &partner_row.name,
+                path: uri.path(),
+                forwarded: false,
+                blocked_reason: Some(BlockedReason::UpstreamAuthFailed),
+                status_code: None,
+                latency_ms,
+            },
+        )
+        .await;
+        return (StatusCode::BAD_GATEWAY, "upstream authentication failed").into_response();
+    }
+
+    // Circuit breaker per (partner, credential): short-circuit while open,
+    // let a single probe through while half-open. Checked after the checks that can
+    // refuse the request outright; later returns that never reach the partner give the
+    // probe back.
+    let breaker_cfg = &partner_row.circuit_breaker.0;
+    let is_probe = match breaker_check(&state.redis, &partner_row.name, cred.id, breaker_cfg).await
+    {
+        BreakerDecision::Allow => false,
+        BreakerDecision::Probe => true,
+        BreakerDecision::Reject { retry_after } => {
+            if let Some(idem) = &idempotency {
+                idempotency::release(&state.redis, idem).await;
+            }
+
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::CircuitOpen),
+                    status_code: None,
+                    latency_ms,
+                },
+            )
+            .await;
+
+            let retry_after_secs = retry_after.as_millis().div_ceil(1000).max(1);
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "invalid credential header_value",
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, retry_after_secs.to_string())],
+                "circuit open",
             )
-                .into_response()
+                .into_response();
         }
     };
 
@@ -335,7 +985,24 @@ pub async fn handler(
     // -------------------------
     let retry_policy = RetryPolicy::default();
     let partner_profile = profile_for_partner(&partner_row.name);
-    let allow_retries = is_idempotent(&method);
+    // Non-idempotent requests may be retried only when the partner dedupes them
+    // by idempotency key. The key is forwarded under the partner's header name.
+    let upstream_idempotency = partner_row
+        .idempotency_header
+        .as_deref()
+        .and_then(|h| HeaderName::from_bytes(h.as_bytes()).ok())
+        .zip(idempotency.as_ref())
+        .and_then(|(name, idem)| {
+            HeaderValue::from_str(&idem.upstream_key)
+                .ok()
+                .map(|value| (name, value))
+        });
+
+    // A half-open probe is a single attempt; its outcome decides the breaker state.
+    // A streamed body can only be sent once.
+    let allow_retries = (is_idempotent(&method) || upstream_idempotency.is_some())
+        && !is_probe
+        && forward_body.is_replayable();
 
     // Total request budget from policy
     let total_budget_ms: u64 = policy.timeout_ms.max(1) as u64;
@@ -344,67 +1011,573 @@ pub async fn handler(
     // Retry budget defaults (can move into DB later)
     let budgets = RetryBudgets::default();
 
+    // Endpoint failover: healthy endpoints first; each retry moves to the next one.
+    let endpoints = endpoint_order(&state.redis, &partner_row.name, endpoint_cfg, &endpoint_urls).await;
+    let max_attempts = retry_policy.max_attempts.max(joined_all.len());
+    let primary_url = &joined_all[endpoints[0].index];
+
+    // WebSocket upgrade: one upstream connection, relayed message by message until
+    // either side closes. No retries or hedging; usage is recorded at close.
+    if let Some(ws) = ws {
+        // nothing to replay for a connection
+        if let Some(idem) = &idempotency {
+            idempotency::release(&state.redis, idem).await;
+        }
+
+        let Some(ws_url) = websocket_url(&credential.url(primary_url)) else {
+            if is_probe {
+                breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+            }
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let details = UsageDetails {
+                operation_id: operation_id.clone(),
+                ..Default::default()
+            };
+            let _ = insert_usage_event_detailed(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: None,
+                    status_code: Some(StatusCode::BAD_GATEWAY.as_u16()),
+                    latency_ms,
+                },
+                details,
+            )
+            .await;
+            return (StatusCode::BAD_GATEWAY, "partner does not support websockets").into_response();
+        };
+
+        // Messages aren't inspected, so they can't be masked.
+        if redactor.is_some() {
+            if is_probe {
+                breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+            }
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let _ = insert_usage_event(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: &partner_row.name,
+                    path: uri.path(),
+                    forwarded: false,
+                    blocked_reason: Some(BlockedReason::ResponseRedactionFailed),
+                    status_code: None,
+                    latency_ms,
+                },
+            )
+            .await;
+            return (StatusCode::FORBIDDEN, "websockets are not available with a redaction profile")
+                .into_response();
+        }
+
+        let credential_headers = credential.headers(&Method::GET, &ws_url, &[]);
+        let mut upstream_headers: Vec<(HeaderName, HeaderValue)> = headers
+            .iter()
+            .filter(|(name, _)| {
+                header_filter.forward_request(name.as_str())
+                    && !forwarding.replaces(name)
+                    && !is_handshake_header(name.as_str())
+                    && !credential_headers.iter().any(|(n, _)| n == *name)
+                    && !templated_headers.iter().any(|(n, _)| n == *name)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        upstream_headers.extend(header_filter.injected().iter().cloned());
+        upstream_headers.extend(forwarding.headers().iter().cloned());
+        upstream_headers.extend(templated_headers.iter().cloned());
+        upstream_headers.extend(credential_headers);
+
+        let handshake_timeout = Duration::from_millis(policy.timeout_ms.max(1) as u64);
+        let partner_tls = partner_client.as_ref().map(|c| c.tls.clone());
+        let connected = timeout(
+            handshake_timeout,
+            connect_upstream(&ws_url, &upstream_headers, partner_tls, partner_row.internal),
+        )
+        .await;
+
+        let (upstream, protocol) = match connected {
+            Ok(Ok(c)) => {
+                breaker_record(&state.redis, &partner_row.name, cred.id, breaker_cfg, true).await;
+                canary_record(&state, policy.id, canary, true).await;
+                c
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "websocket upstream handshake failed");
+                if is_ssrf_blocked(&e) {
+                    if is_probe {
+                        breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+                    }
+                    let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                    let _ = insert_usage_event(
+                        &state.db,
+                        UsageEvent {
+                            virtual_key_id: vk.id,
+                            customer_id: vk.customer_id,
+                            partner_name: &partner_row.name,
+                            path: uri.path(),
+                            forwarded: false,
+                            blocked_reason: Some(BlockedReason::SsrfBlocked),
+                            status_code: None,
+                            latency_ms,
+                        },
+                    )
+                    .await;
+                    return (StatusCode::BAD_REQUEST, "blocked by SSRF guard").into_response();
+                }
+                breaker_record(&state.redis, &partner_row.name, cred.id, breaker_cfg, false).await;
+                canary_record(&state, policy.id, canary, false).await;
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let details = UsageDetails {
+                    operation_id: operation_id.clone(),
+                    ..Default::default()
+                };
+                let _ = insert_usage_event_detailed(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: true,
+                        blocked_reason: None,
+                        status_code: Some(StatusCode::BAD_GATEWAY.as_u16()),
+                        latency_ms,
+                    },
+                    details,
+                )
+                .await;
+                return (StatusCode::BAD_GATEWAY, "upstream websocket handshake failed").into_response();
+            }
+            Err(_) => {
+                breaker_record(&state.redis, &partner_row.name, cred.id, breaker_cfg, false).await;
+                canary_record(&state, policy.id, canary, false).await;
+                let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let details = UsageDetails {
+                    operation_id: operation_id.clone(),
+                    ..Default::default()
+                };
+                let _ = insert_usage_event_detailed(
+                    &state.db,
+                    UsageEvent {
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        partner_name: &partner_row.name,
+                        path: uri.path(),
+                        forwarded: true,
+                        blocked_reason: None,
+                        status_code: Some(StatusCode::GATEWAY_TIMEOUT.as_u16()),
+                        latency_ms,
+                    },
+                    details,
+                )
+                .await;
+                return (StatusCode::GATEWAY_TIMEOUT, "upstream request timed out").into_response();
+            }
+        };
+
+        let limits = ResponseLimits::from_policy(&policy);
+        let usage = ResponseUsage {
+            db: state.db.clone(),
+            virtual_key_id: vk.id,
+            customer_id: vk.customer_id,
+            partner_name: partner_row.name.clone(),
+            path: uri.path().to_string(),
+            status_code: StatusCode::SWITCHING_PROTOCOLS.as_u16(),
+            latency_ms: start.elapsed().as_millis().min(i32::MAX as u128) as i32,
+            details: UsageDetails {
+                operation_id: operation_id.clone(),
+                ..Default::default()
+            },
+        };
+
+        tracing::info!(partner = %partner_row.name, vk_id = %vk.id, "websocket connected");
+
+        let ws = match protocol {
+            Some(p) => ws.protocols([p]),
+            None => ws,
+        };
+        return ws
+            .on_upgrade(move |client| relay(client, upstream, limits, usage))
+            .into_response();
+    }
+
+    // Server-sent events: the response is open-ended, so the client-wide request
+    // timeout is replaced by the policy's stream duration (or a generous default).
+    let sse_requested = accepts_event_stream(&headers);
+    let stream_timeout = ResponseLimits::from_policy(&policy)
+        .max_duration
+        .unwrap_or(DEFAULT_STREAM_TIMEOUT);
+
+    // Hedging (opt-in per partner, idempotent requests only): if the first attempt is
+    // slow, a second one goes out and the first success wins.
+    let hedge_cfg = &partner_row.hedging.0;
+    let hedge_target = if hedge_cfg.enabled
+        && !sse_requested
+        && is_idempotent(&method)
+        && !is_probe
+        && forward_body.is_replayable()
+    {
+        let url = match hedge_cfg.base_url.as_deref() {
+            Some(raw) => match Url::parse(raw)
+                .ok()
+                .and_then(|b| join_same_origin(&b, &(forwarded_path.clone() + &query)))
+                .filter(|u| partner_row.internal || !literal_host_forbidden(u))
+            {
+                Some(u) => u,
+                None => {
+                    tracing::warn!(partner = %partner_row.name, "invalid hedging base_url; hedging to primary");
+                    primary_url.clone()
+                }
+            },
+            None => primary_url.clone(),
+        };
+
+        let alternate = if hedge_cfg.alternate_credential {
+            get_alternate_credential_for_partner(&state.db, partner_row.id, cred.id)
+                .await
+                .ok()
+                .flatten()
+        } else {
+            None
+        };
+
+        // An alternate with its own client identity is sent through its own client.
+        let mut alternate = alternate.and_then(|c| {
+            let client = state.clients.for_credential(&partner_row, &c).ok()?;
+            Some((UpstreamCredential::from_row(&c).ok()?, client))
+        });
+        if let Some((alt, _)) = alternate.as_mut() {
+            if alt.authorize(&state.redis, token_http, partner_row.internal, false).await.is_err() {
+                alternate = None;
+            }
+        }
+        let (credential, http) = match alternate {
+            Some((alt, client)) => (alt, client_http(&client)),
+            None => (credential.clone(), http.clone()),
+        };
+
+        Some(HedgeTarget { url, credential, http })
+    } else {
+        None
+    };
+
+    // Signing credentials need the whole body in hand (it was buffered above).
+    let signs_body = credential.signs_body()
+        || hedge_target
+            .as_ref()
+            .is_some_and(|t| t.credential.signs_body());
+    let signing_body = match forward_body.replayable() {
+        Some(b) if signs_body => match b.to_bytes().await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to read buffered request body for signing");
+                if is_probe {
+                    breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+                }
+                if let Some(idem) = &idempotency {
+                    idempotency::release(&state.redis, idem).await;
+                }
+                return (StatusCode::INTERNAL_SERVER_ERROR, "failed to read request body")
+                    .into_response();
+            }
+        },
+        _ => Bytes::new(),
+    };
+
     // Helper: build reqwest request fresh each attempt (builders are one-shot)
-    let build_reqwest = || {
-        let mut out = state.http.request(method.clone(), joined.clone());
+    let build_reqwest = |http: &reqwest::Client, url: &Url, credential: &UpstreamCredential| {
+        let url = credential.url(url);
+        let mut out = http.request(method.clone(), url.clone());
+
+        // The credential owns any header it sets (e.g. Authorization).
+        let credential_headers = credential.headers(&method, &url, &signing_body);
 
         for (name, value) in headers.iter() {
-            let name_str = name.as_str().to_lowercase();
+            if !header_filter.forward_request(name.as_str()) || forwarding.replaces(name) {
+                continue;
+            }
 
-            // never forward these
-            if name_str == "host" || name_str == "x-relaykey" || name_str == "x-request-id" {
+            // Rewritten bodies get their own length; rewritten or redacted responses must
+            // arrive uncompressed.
+            if (transforms_request && name == CONTENT_LENGTH)
+                || (plain_response && name == ACCEPT_ENCODING)
+                || templated_headers.iter().any(|(n, _)| n == name)
+            {
                 continue;
             }
 
-            // drop hop-by-hop headers
-            if is_hop_by_hop(&name_str) {
+            // the client's raw key is replaced by the scoped upstream key below
+            if name == IDEMPOTENCY_HEADER && upstream_idempotency.is_some() {
                 continue;
             }
 
-            // drop sensitive end-user / proxy headers
-            if name_str == "authorization" || name_str == "cookie" || name_str.starts_with("proxy-")
-            {
+            if credential_headers.iter().any(|(n, _)| n == name) {
                 continue;
             }
 
             out = out.header(name, value);
         }
 
-        out = out.header(header_name.clone(), header_value.clone());
+        for (name, value) in header_filter
+            .injected()
+            .iter()
+            .chain(forwarding.headers())
+            .chain(&templated_headers)
+        {
+            out = out.header(name.clone(), value.clone());
+        }
+
+        if let Some((name, value)) = &upstream_idempotency {
+            out = out.header(name.clone(), value.clone());
+        }
+
+        for (name, value) in credential_headers {
+            out = out.header(name, value);
+        }
+
+        if sse_requested {
+            out = out.timeout(stream_timeout);
+        }
         out
     };
 
     let mut attempt: usize = 0;
     let mut retries_used: usize = 0;
     let mut budget_blocked: bool = false;
+    let mut hedged: bool = false;
+    let mut hedge_won: bool = false;
+    let mut token_refreshed: bool = false;
+    // Resend the current attempt (same endpoint, no retry spent) after a token refresh.
+    let mut resend: bool = false;
 
     loop {
-        attempt += 1;
+        if !std::mem::take(&mut resend) {
+            attempt += 1;
+        }
 
         // Remaining total time budget
         let now = Instant::now();
         if now >= deadline {
+            // A probe is a single attempt, so it can only end here before being sent.
+            if is_probe {
+                breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+            }
+            if let Some(idem) = &idempotency {
+                idempotency::release(&state.redis, idem).await;
+            }
             return (StatusCode::GATEWAY_TIMEOUT, "upstream request timed out").into_response();
         }
         let remaining = deadline - now;
 
         // Send attempt with remaining budget as timeout
-        let send_fut = build_reqwest().body(body.clone()).send();
-        let resp_result = timeout(remaining, send_fut).await;
+        let attempt_start = Instant::now();
+        let endpoint = endpoints[(attempt - 1) % endpoints.len()];
+        let endpoint_url = &endpoint_urls[endpoint.index];
+        let attempt_body = match forward_body.next_attempt().await {
+            Ok(Some(b)) => b,
+            // a streamed body was already consumed; never retried (see allow_retries)
+            Ok(None) => {
+                return (StatusCode::BAD_GATEWAY, "upstream request failed").into_response();
+            }
+            Err(e) => {
+                tracing::error!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to reopen buffered request body");
+                if is_probe {
+                    breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+                }
+                if let Some(idem) = &idempotency {
+                    idempotency::release(&state.redis, idem).await;
+                }
+                return (StatusCode::INTERNAL_SERVER_ERROR, "failed to read request body")
+                    .into_response();
+            }
+        };
+
+        let send_fut = build_reqwest(&http, &joined_all[endpoint.index], &credential)
+            .body(attempt_body)
+            .send();
+
+        // Set once the hedge is sent, so a timeout is charged to both credentials.
+        let mut hedge_cred_id: Option<Uuid> = None;
+        let raced = match &hedge_target {
+            // Only the first attempt is hedged; later attempts are ordinary retries.
+            // A resend after a token refresh is not hedged again.
+            Some(target) if attempt == 1 && !token_refreshed => {
+                let delay = hedge_delay(hedge_cfg, &state.latency, &partner_row.name);
+
+                // A hedge is an extra upstream call: it spends the same budgets as a retry.
+                let hedge_source = forward_body.replayable().cloned();
+                let launch = async {
+                    let hedge_body = hedge_source?.to_reqwest().await.ok()?;
+
+                    let decision =
+                        allow_retry_dual_budget(&state.redis, &budgets, &partner_row.name, vk.id)
+                            .await;
+
+                    if !decision.allowed {
+                        tracing::warn!(
+                            partner = %partner_row.name,
+                            vk_id = %vk.id,
+                            partner_remaining = ?decision.partner_remaining,
+                            vk_remaining = ?decision.vk_remaining,
+                            reason = ?decision.reason,
+                            "hedge blocked by budget"
+                        );
+                        return None;
+                    }
+
+                    tracing::info!(
+                        partner = %partner_row.name,
+                        vk_id = %vk.id,
+                        delay_ms = delay.as_millis() as u64,
+                        "sending hedged request"
+                    );
+                    hedge_cred_id = Some(target.credential.id);
+
+                    Some(
+                        build_reqwest(&target.http, &target.url, &target.credential)
+                            .body(hedge_body)
+                            .send(),
+                    )
+                };
+
+                timeout(remaining, race(send_fut, delay, launch)).await
+            }
+            _ => timeout(remaining, send_fut).await.map(|result| Raced {
+                result,
+                leg: Leg::Primary,
+                hedged: false,
+                other_failed: false,
+            }),
+        };
+
+        // Attribute the outcome to the credential that actually served it.
+        let mut attempt_cred_id = cred.id;
+        let mut served_by_endpoint = true;
+        let resp_result = match raced {
+            Ok(r) => {
+                hedge_won = r.leg == Leg::Hedge;
+                if let (true, Some(target)) = (r.hedged, &hedge_target) {
+                    hedged = true;
+                    let other_cred_id = match r.leg {
+                        Leg::Primary => target.credential.id,
+                        Leg::Hedge => cred.id,
+                    };
+                    if r.leg == Leg::Hedge {
+                        attempt_cred_id = target.credential.id;
+                        served_by_endpoint = false;
+                    }
+                    if r.other_failed {
+                        breaker_record(
+                            &state.redis,
+                            &partner_row.name,
+                            other_cred_id,
+                            breaker_cfg,
+                            false,
+                        )
+                        .await;
+                    }
+                }
+                Ok(r.result)
+            }
+            Err(elapsed) => Err(elapsed),
+        };
 
         match resp_result {
             // Completed with an HTTP response
             Ok(Ok(resp)) => {
                 let status = resp.status();
+
+                // Expired or revoked access token: refresh once and resend.
+                if status == StatusCode::UNAUTHORIZED
+                    && credential.uses_token()
+                    && attempt_cred_id == credential.id
+                    && !token_refreshed
+                    && forward_body.is_replayable()
+                {
+                    token_refreshed = true;
+                    match credential.authorize(&state.redis, token_http, partner_row.internal, true).await {
+                        Ok(()) => {
+                            tracing::info!(
+                                partner = %partner_row.name,
+                                vk_id = %vk.id,
+                                attempt,
+                                "upstream rejected access token; retrying with a fresh one"
+                            );
+                            resend = true;
+                            continue;
+                        }
+                        Err(e) => {
+                            tracing::warn!(error = %e, partner = %partner_row.name, vk_id = %vk.id, "failed to refresh upstream access token");
+                        }
+                    }
+                }
                 let axum_status =
                     StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
 
                 let class = classify_status(axum_status);
 
-                let can_retry_status = allow_retries
+                breaker_record(
+                    &state.redis,
+                    &partner_row.name,
+                    attempt_cred_id,
+                    breaker_cfg,
+                    !axum_status.is_server_error(),
+                )
+                .await;
+                canary_record(&state, policy.id, canary, !axum_status.is_server_error()).await;
+
+                if !axum_status.is_server_error() {
+                    state.latency.record(&partner_row.name, attempt_start.elapsed());
+                }
+
+                if served_by_endpoint && endpoints.len() > 1 {
+                    if axum_status.is_server_error() {
+                        mark_endpoint_down(&state.redis, &partner_row.name, endpoint_url, endpoint_cfg)
+                            .await;
+                    } else if !endpoint.healthy {
+                        mark_endpoint_up(&state.redis, &partner_row.name, endpoint_url).await;
+                    }
+                }
+
+                // Partner-advertised wait (Retry-After / X-RateLimit-Reset) on 429/503:
+                // share it with every instance so nobody keeps hammering the partner.
+                let upstream_wait = if axum_status == StatusCode::TOO_MANY_REQUESTS
+                    || axum_status == StatusCode::SERVICE_UNAVAILABLE
+                {
+                    upstream_retry_after(resp.headers(), Utc::now())
+                } else {
+                    None
+                };
+
+                if let Some(wait) = upstream_wait {
+                    set_partner_cooldown(&state.redis, &partner_row.name, wait).await;
+                }
+
+                let mut can_retry_status = allow_retries
                     && class == RetryClass::Retryable
                     && status_retry_allowed(&partner_profile, axum_status)
-                    && attempt < retry_policy.max_attempts;
+                    && attempt < max_attempts;
+
+                // Honor the advised wait only if it fits in what's left of the deadline;
+                // otherwise hand the response back right away.
+                if let Some(wait) = upstream_wait {
+                    if can_retry_status && Instant::now() + wait >= deadline {
+                        can_retry_status = false;
+                        tracing::warn!(
+                            partner = %partner_row.name,
+                            vk_id = %vk.id,
+                            status = %status.as_u16(),
+                            attempt,
+                            retry_after_ms = wait.as_millis() as u64,
+                            "upstream retry-after exceeds remaining budget; not retrying"
+                        );
+                    }
+                }
 
                 if can_retry_status {
                     // ---- Budget gate (BOTH partner + vk) ----
@@ -419,7 +1592,7 @@ pub async fn handler(
                             vk_id = %vk.id,
                             status = %status.as_u16(),
                             attempt,
-                            max_attempts = retry_policy.max_attempts,
+                            max_attempts,
                             partner_remaining = ?decision.partner_remaining,
                             vk_remaining = ?decision.vk_remaining,
                             reason = ?decision.reason,
@@ -429,18 +1602,23 @@ pub async fn handler(
                     } else {
                         retries_used += 1;
 
-                        let sleep_ms = backoff_ms(
-                            attempt,
-                            retry_policy.base_backoff_ms,
-                            retry_policy.max_backoff_ms,
-                        ) + cheap_jitter_ms(attempt);
+                        let sleep_ms = match upstream_wait {
+                            Some(wait) => wait.as_millis() as u64,
+                            None => {
+                                backoff_ms(
+                                    attempt,
+                                    retry_policy.base_backoff_ms,
+                                    retry_policy.max_backoff_ms,
+                                ) + cheap_jitter_ms(attempt)
+                            }
+                        };
 
                         tracing::warn!(
                             partner = %partner_row.name,
                             vk_id = %vk.id,
                             status = %status.as_u16(),
                             attempt,
-                            max_attempts = retry_policy.max_attempts,
+                            max_attempts,
                             backoff_ms = sleep_ms,
                             partner_remaining = ?decision.partner_remaining,
                             vk_remaining = ?decision.vk_remaining,
@@ -453,33 +1631,48 @@ pub async fn handler(
                 }
 
                 // Return upstream response (streaming; filter headers)
-                let mut resp_headers = axum::http::HeaderMap::new();
-                for (name, value) in resp.headers().iter() {
-                    let name_str = name.as_str().to_lowercase();
-                    if is_hop_by_hop(&name_str) {
-                        continue;
-                    }
-                    // Don't leak upstream cookies to callers.
-                    if name_str == "set-cookie" {
-                        continue;
-                    }
-                    resp_headers.insert(name, value.clone());
-                }
+                let mut resp_headers = header_filter.response_headers(resp.headers());
 
                 let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                let details = UsageDetails {
+                    hedged,
+                    hedge_won,
+                    rpc_calls: rpc_calls.clone(),
+                    graphql_operation: graphql_operation.clone(),
+                    operation_id: operation_id.clone(),
+                    ..Default::default()
+                };
+
+                let sse = is_event_stream(resp.headers());
+
+                // Declared too large: refuse before relaying anything.
+                let response_limits = ResponseLimits::from_policy(&policy);
+                if response_limits
+                    .max_bytes
+                    .is_some_and(|max| declared_too_large(resp.headers(), max))
+                {
+                    let _ = insert_usage_event_detailed(
+                        &state.db,
+                        UsageEvent {
+                            virtual_key_id: vk.id,
+                            customer_id: vk.customer_id,
+                            partner_name: &partner_row.name,
+                            path: uri.path(),
+                            forwarded: true,
+                            blocked_reason: Some(BlockedReason::ResponseTooLarge),
+                            status_code: Some(status.as_u16()),
+                            latency_ms,
+                        },
+                        details,
+                    )
+                    .await;
+
+                    if let Some(idem) = &idempotency {
+                        idempotency::release(&state.redis, idem).await;
+                    }
 
-                let _ = insert_usage_event(
-                    &state.db,
-                    vk.id,
-                    vk.customer_id,
-                    &partner_row.name,
-                    uri.path(),
-                    true,
-                    None,
-                    Some(status.as_u16()),
-                    latency_ms,
-                )
-                .await;
+                    return (StatusCode::BAD_GATEWAY, "upstream response too large").into_response();
+                }
 
                 tracing::info!(
                     partner = %partner_row.name,
@@ -487,21 +1680,265 @@ pub async fn handler(
                     attempts = attempt,
                     retries_used = retries_used,
                     budget_blocked = budget_blocked,
+                    hedged = hedged,
+                    hedge_won = hedge_won,
                     status = status.as_u16(),
+                    trace_id = ?forwarding.trace.as_ref().map(|t| &t.trace_id),
                     "proxy completed"
                 );
 
-                let body_stream = Body::from_stream(resp.bytes_stream());
-                return (status, resp_headers, body_stream).into_response();
+                // Usage is recorded when the body finishes, with the bytes actually relayed.
+                let usage = ResponseUsage {
+                    db: state.db.clone(),
+                    virtual_key_id: vk.id,
+                    customer_id: vk.customer_id,
+                    partner_name: partner_row.name.clone(),
+                    path: uri.path().to_string(),
+                    status_code: status.as_u16(),
+                    latency_ms,
+                    details,
+                };
+                let rewrite = transform.filter(|_| transforms_response && !sse && is_json(resp.headers()));
+                let mut upstream_body = Body::from_stream(resp.bytes_stream());
+
+                // The shadow gets the same request once this (unmodified) body has been read.
+                if let Some(shadow_partner) = mirror_cfg.shadow_partner.clone().filter(|_| mirrored && !sse) {
+                    let (digest_tx, digest_rx) = oneshot::channel();
+                    upstream_body = hash_body(upstream_body, digest_tx);
+
+                    let mut mirror_headers: HeaderMap = headers
+                        .iter()
+                        .filter(|(name, _)| {
+                            header_filter.forward_request(name.as_str())
+                                && !forwarding.replaces(name)
+                                && *name != CONTENT_LENGTH
+                                && *name != IDEMPOTENCY_HEADER
+                                && !(plain_response && *name == ACCEPT_ENCODING)
+                        })
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect();
+                    for (name, value) in &templated_headers {
+                        mirror_headers.insert(name.clone(), value.clone());
+                    }
+
+                    let job = MirrorJob {
+                        partner_name: partner_row.name.clone(),
+                        shadow_partner,
+                        virtual_key_id: vk.id,
+                        customer_id: vk.customer_id,
+                        method: method.clone(),
+                        path: uri.path().to_string(),
+                        path_and_query: forwarded_path.clone() + &query,
+                        headers: mirror_headers,
+                        body: forward_body.replayable().cloned(),
+                        timeout: Duration::from_millis(mirror_cfg.timeout_ms),
+                        primary_status: status.as_u16(),
+                        primary_latency_ms: attempt_start.elapsed().as_millis().min(i32::MAX as u128) as i32,
+                    };
+                    mirror::spawn(state.clone(), job, digest_rx);
+                }
+
+                // Rewritten responses are read whole; anything we can't rewrite is refused
+                // rather than relayed with the fields the route meant to strip.
+                if let Some(t) = rewrite {
+                    let (_, captured) = capture_body(upstream_body, t.max_response_bytes).await;
+                    let transformed = match captured {
+                        Some(bytes) => apply(&t.response, &transform_vars, &bytes).map_err(|e| e.to_string()),
+                        None => Err("response unreadable or larger than max_response_bytes".to_string()),
+                    };
+
+                    match transformed {
+                        Ok(bytes) => {
+                            resp_headers.remove(CONTENT_LENGTH);
+                            upstream_body = Body::from(bytes);
+                        }
+                        Err(reason) => {
+                            tracing::warn!(partner = %partner_row.name, vk_id = %vk.id, reason = %reason, "response transform failed");
+                            let _ = insert_usage_event_detailed(
+                                &state.db,
+                                UsageEvent {
+                                    virtual_key_id: vk.id,
+                                    customer_id: vk.customer_id,
+                                    partner_name: &partner_row.name,
+                                    path: uri.path(),
+                                    forwarded: true,
+                                    blocked_reason: Some(BlockedReason::ResponseTransformFailed),
+                                    status_code: Some(status.as_u16()),
+                                    latency_ms,
+                                },
+                                usage.details,
+                            )
+                            .await;
+
+                            if let Some(idem) = &idempotency {
+                                idempotency::release(&state.redis, idem).await;
+                            }
+
+                            return (StatusCode::BAD_GATEWAY, "upstream response could not be transformed")
+                                .into_response();
+                        }
+                    }
+                }
+
+                // Redaction runs last, on exactly what the client would otherwise see.
+                if let Some(redactor) = &redactor {
+                    resp_headers.remove(CONTENT_LENGTH);
+                    if sse {
+                        upstream_body = redactor.clone().redact_stream(upstream_body);
+                    } else {
+                        match redactor.redact_response(&resp_headers, upstream_body).await {
+                            Ok(bytes) => upstream_body = Body::from(bytes),
+                            Err(reason) => {
+                                tracing::warn!(partner = %partner_row.name, vk_id = %vk.id, reason = %reason, "response redaction failed");
+                                let _ = insert_usage_event_detailed(
+                                    &state.db,
+                                    UsageEvent {
+                                        virtual_key_id: vk.id,
+                                        customer_id: vk.customer_id,
+                                        partner_name: &partner_row.name,
+                                        path: uri.path(),
+                                        forwarded: true,
+                                        blocked_reason: Some(BlockedReason::ResponseRedactionFailed),
+                                        status_code: Some(status.as_u16()),
+                                        latency_ms,
+                                    },
+                                    usage.details,
+                                )
+                                .await;
+
+                                if let Some(idem) = &idempotency {
+                                    idempotency::release(&state.redis, idem).await;
+                                }
+
+                                return (StatusCode::BAD_GATEWAY, "upstream response could not be redacted")
+                                    .into_response();
+                            }
+                        }
+                    }
+                }
+
+                // Record mode: what the client got becomes the fixture for this request.
+                // 5xx answers are passed on but not kept.
+                if sandbox_mode == SandboxMode::Record && !sse && !status.is_server_error() {
+                    let (body, captured) = capture_body(upstream_body, MAX_RECORDED_BYTES).await;
+                    upstream_body = body;
+                    if let Some(bytes) = captured {
+                        let fixture = NewFixture {
+                            partner_name: partner.clone(),
+                            method: method.to_string(),
+                            path: forwarded_path.clone(),
+                            query: Some(sandbox_query.clone()),
+                            body_sha256: sandbox_body.clone(),
+                            status: i32::from(status.as_u16()),
+                            headers: Default::default(),
+                            body: Vec::new(),
+                            template: false,
+                        };
+                        sandbox::record(state.clone(), fixture, resp_headers.clone(), bytes);
+                    }
+                }
+
+                let body = guard_response(upstream_body, response_limits, usage, sse);
+
+                // Keyed requests: keep the answer for duplicate submissions.
+                // 5xx is not stored so the client can retry with the same key;
+                // neither are event streams, which have no end to wait for.
+                if let Some(idem) = &idempotency {
+                    if status.is_server_error() || sse {
+                        idempotency::release(&state.redis, idem).await;
+                    } else {
+                        let cap = response_limits
+                            .max_bytes
+                            .map_or(MAX_STORED_BODY_BYTES, |max| max.min(MAX_STORED_BODY_BYTES));
+                        let (body, stored) = capture_body(body, cap).await;
+                        idempotency::complete(
+                            &state.redis,
+                            idem,
+                            status,
+                            &resp_headers,
+                            stored.as_ref(),
+                        )
+                        .await;
+                        return (status, resp_headers, body).into_response();
+                    }
+                }
+
+                return (status, resp_headers, body).into_response();
             }
 
             // Completed with a reqwest error
             Ok(Err(e)) => {
+                // the URL may carry a query-parameter credential
+                let e = e.without_url();
+
+                // The client overran the body cap mid-upload: their fault, not the partner's.
+                if forward_body.overflowed() {
+                    if is_probe {
+                        breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+                    }
+                    let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                    let _ = insert_usage_event(
+                        &state.db,
+                        UsageEvent {
+                            virtual_key_id: vk.id,
+                            customer_id: vk.customer_id,
+                            partner_name: &partner_row.name,
+                            path: uri.path(),
+                            forwarded: false,
+                            blocked_reason: Some(BlockedReason::RequestBodyTooLarge),
+                            status_code: None,
+                            latency_ms,
+                        },
+                    )
+                    .await;
+                    return (StatusCode::PAYLOAD_TOO_LARGE, "request body too large")
+                        .into_response();
+                }
+
+                // The resolver refused the partner's addresses: a configuration problem,
+                // not partner health, and never worth retrying.
+                if is_ssrf_blocked(&e) {
+                    tracing::warn!(partner = %partner_row.name, vk_id = %vk.id, "upstream blocked by SSRF guard");
+
+                    if is_probe {
+                        breaker_release(&state.redis, &partner_row.name, cred.id, breaker_cfg).await;
+                    }
+                    if let Some(idem) = &idempotency {
+                        idempotency::release(&state.redis, idem).await;
+                    }
+
+                    let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+                    let _ = insert_usage_event(
+                        &state.db,
+                        UsageEvent {
+                            virtual_key_id: vk.id,
+                            customer_id: vk.customer_id,
+                            partner_name: &partner_row.name,
+                            path: uri.path(),
+                            forwarded: false,
+                            blocked_reason: Some(BlockedReason::SsrfBlocked),
+                            status_code: None,
+                            latency_ms,
+                        },
+                    )
+                    .await;
+                    return (StatusCode::BAD_REQUEST, "blocked by SSRF guard").into_response();
+                }
+
+                breaker_record(&state.redis, &partner_row.name, attempt_cred_id, breaker_cfg, false)
+                    .await;
+                canary_record(&state, policy.id, canary, false).await;
+
+                if served_by_endpoint && endpoints.len() > 1 {
+                    mark_endpoint_down(&state.redis, &partner_row.name, endpoint_url, endpoint_cfg)
+                        .await;
+                }
+
                 let class = classify_reqwest_error(&e);
 
                 let can_retry_err = allow_retries
                     && class == RetryClass::Retryable
-                    && attempt < retry_policy.max_attempts;
+                    && attempt < max_attempts;
 
                 if can_retry_err {
                     // ---- Budget gate (BOTH partner + vk) ----
@@ -515,7 +1952,7 @@ pub async fn handler(
                             partner = %partner_row.name,
                             vk_id = %vk.id,
                             attempt,
-                            max_attempts = retry_policy.max_attempts,
+                            max_attempts,
                             error = %e,
                             partner_remaining = ?decision.partner_remaining,
                             vk_remaining = ?decision.vk_remaining,
@@ -536,7 +1973,7 @@ pub async fn handler(
                             partner = %partner_row.name,
                             vk_id = %vk.id,
                             attempt,
-                            max_attempts = retry_policy.max_attempts,
+                            max_attempts,
                             error = %e,
                             backoff_ms = sleep_ms,
                             partner_remaining = ?decision.partner_remaining,
@@ -559,11 +1996,23 @@ pub async fn handler(
                     "upstream request failed"
                 );
 
+                if let Some(idem) = &idempotency {
+                    idempotency::release(&state.redis, idem).await;
+                }
+
                 return (StatusCode::BAD_GATEWAY, "upstream request failed").into_response();
             }
 
             // tokio timeout elapsed (hit the remaining budget for this attempt)
             Err(_elapsed) => {
+                breaker_record(&state.redis, &partner_row.name, attempt_cred_id, breaker_cfg, false)
+                    .await;
+                if let Some(hedge_cred_id) = hedge_cred_id.filter(|id| *id != attempt_cred_id) {
+                    breaker_record(&state.redis, &partner_row.name, hedge_cred_id, breaker_cfg, false)
+                        .await;
+                }
+                canary_record(&state, policy.id, canary, false).await;
+
                 // since we use remaining budget, this effectively means total budget expired
                 tracing::warn!(
                     partner = %partner_row.name,
@@ -573,6 +2022,11 @@ pub async fn handler(
                     budget_blocked = budget_blocked,
                     "upstream request timed out"
                 );
+
+                if let Some(idem) = &idempotency {
+                    idempotency::release(&state.redis, idem).await;
+                }
+
                 return (StatusCode::GATEWAY_TIMEOUT, "upstream request timed out").into_response();
             }
         }
@@ -0,0 +1,475 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header::CONTENT_TYPE, HeaderMap},
+};
+use futures_util::{stream, StreamExt};
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+use relaykey_db::{models::RedactionProfile, queries::policies::PolicyRow};
+
+use crate::idempotency::capture_body;
+use crate::transform::is_json;
+
+fn builtin_pattern(name: &str) -> Option<&'static str> {
+    match name {
+        "iban" => Some(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,4})?\b"),
+        "ssn" => Some(r"\b\d{3}-\d{2}-\d{4}\b"),
+        "email" => Some(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b"),
+        "card_number" => Some(r"\b(?:\d[ -]?){12,18}\d\b"),
+        _ => None,
+    }
+}
+
+/// One step of a JSONPath selector.
+#[derive(Debug)]
+enum Step {
+    Field(String),
+    Index(usize),
+    /// `*` / `[*]`: every member or element.
+    Any,
+    /// `..name`: `name` at any depth below.
+    Descend(String),
+}
+
+/// Subset of JSONPath: `$`, `.name`, `['name']`, `[0]`, `.*`, `[*]` and `..name`.
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let invalid = || format!("invalid json path: {path}");
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = Vec::new();
+
+    let name_len = |s: &str| s.find(['.', '[']).unwrap_or(s.len());
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("..") {
+            let len = name_len(after);
+            if len == 0 {
+                return Err(invalid());
+            }
+            steps.push(Step::Descend(after[..len].to_string()));
+            rest = &after[len..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let len = name_len(after);
+            match &after[..len] {
+                "" => return Err(invalid()),
+                "*" => steps.push(Step::Any),
+                name => steps.push(Step::Field(name.to_string())),
+            }
+            rest = &after[len..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let step = if inner == "*" {
+                Step::Any
+            } else if let Ok(i) = inner.parse::<usize>() {
+                Step::Index(i)
+            } else {
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                    .ok_or_else(invalid)?;
+                Step::Field(quoted.to_string())
+            };
+            steps.push(step);
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(steps)
+}
+
+/// A policy's redaction profile, compiled.
+#[derive(Debug)]
+pub struct Redactor {
+    paths: Vec<Vec<Step>>,
+    patterns: Vec<Regex>,
+    mask: String,
+    max_body_bytes: usize,
+}
+
+/// Compile a profile. Used by the admin API to reject bad profiles up front.
+pub fn compile(profile: &RedactionProfile) -> Result<Redactor, String> {
+    let paths = profile
+        .json_paths
+        .iter()
+        .map(|p| parse_path(p))
+        .collect::<Result<_, _>>()?;
+
+    let builtin = profile
+        .builtin
+        .iter()
+        .map(|name| builtin_pattern(name).ok_or_else(|| format!("unknown builtin pattern: {name}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let patterns = builtin
+        .into_iter()
+        .chain(profile.patterns.iter().map(String::as_str))
+        .map(|p| Regex::new(p).map_err(|e| format!("invalid pattern {p}: {e}")))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Redactor {
+        paths,
+        patterns,
+        mask: profile.mask.clone(),
+        max_body_bytes: profile.max_body_bytes,
+    })
+}
+
+fn is_textual(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        // Untyped: treated as text, and refused below if it isn't UTF-8.
+        return true;
+    };
+    let media = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    media.starts_with("text/")
+        || media.ends_with("+json")
+        || media.ends_with("+xml")
+        || matches!(
+            media.as_str(),
+            "application/json"
+                | "application/xml"
+                | "application/javascript"
+                | "application/x-www-form-urlencoded"
+        )
+}
+
+/// End of the last complete SSE event in `buf`.
+fn last_event_end(buf: &[u8]) -> Option<usize> {
+    let lf = buf.windows(2).rposition(|w| w == b"\n\n").map(|i| i + 2);
+    let crlf = buf.windows(4).rposition(|w| w == b"\r\n\r\n").map(|i| i + 4);
+    lf.max(crlf)
+}
+
+impl Redactor {
+    pub fn redact_text(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, re| {
+                re.replace_all(&text, self.mask.as_str()).into_owned()
+            })
+    }
+
+    pub fn redact_value(&self, value: &mut Value) {
+        for steps in &self.paths {
+            mask_path(value, steps, &self.mask);
+        }
+        if !self.patterns.is_empty() {
+            self.redact_strings(value);
+        }
+    }
+
+    fn redact_strings(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.redact_text(s),
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_strings(v)),
+            Value::Object(map) => map.values_mut().for_each(|v| self.redact_strings(v)),
+            _ => {}
+        }
+    }
+
+    /// Read a (non-stream) response in full and mask it. Errors name why it can't be relayed.
+    pub async fn redact_response(&self, headers: &HeaderMap, body: Body) -> Result<Bytes, String> {
+        let (_, captured) = capture_body(body, self.max_body_bytes).await;
+        let bytes = captured
+            .ok_or_else(|| "response unreadable or larger than max_body_bytes".to_string())?;
+        if bytes.is_empty() {
+            return Ok(bytes);
+        }
+
+        // With json_paths the body must be JSON, whatever its content type claims:
+        // the patterns alone would let the selected fields through.
+        if !self.paths.is_empty() || is_json(headers) {
+            match serde_json::from_slice::<Value>(&bytes) {
+                Ok(mut doc) => {
+                    self.redact_value(&mut doc);
+                    return serde_json::to_vec(&doc).map(Bytes::from).map_err(|e| e.to_string());
+                }
+                Err(_) if !self.paths.is_empty() => {
+                    return Err("response is not json; json_paths can't be applied".to_string());
+                }
+                Err(_) => {}
+            }
+        }
+
+        if !is_textual(headers) {
+            return Err("response content type can't be redacted".to_string());
+        }
+        let text = std::str::from_utf8(&bytes).map_err(|_| "response is not text".to_string())?;
+        Ok(Bytes::from(self.redact_text(text)))
+    }
+
+    /// Mask complete SSE events. With json_paths, each event's data must be JSON; it is
+    /// masked and sent as a single `data:` line. Other fields only get the patterns.
+    fn redact_events(&self, text: &str) -> Result<String, String> {
+        if self.paths.is_empty() {
+            return Ok(self.redact_text(text));
+        }
+
+        let mut out = String::with_capacity(text.len());
+        // Data of the current event, and where in `out` its line goes.
+        let mut data: Option<(String, usize)> = None;
+        let flush = |out: &mut String, data: &mut Option<(String, usize)>| {
+            let Some((payload, at)) = data.take() else {
+                return Ok(());
+            };
+            let mut doc: Value = serde_json::from_str(&payload)
+                .map_err(|_| "event data is not json; json_paths can't be applied".to_string())?;
+            self.redact_value(&mut doc);
+            out.insert_str(at, &format!("data: {doc}\n"));
+            Ok::<_, String>(())
+        };
+
+        for line in text.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\r', '\n']);
+            if content.is_empty() {
+                flush(&mut out, &mut data)?;
+                out.push_str(line);
+            } else if let Some(payload) = content.strip_prefix("data:") {
+                let payload = payload.strip_prefix(' ').unwrap_or(payload);
+                match &mut data {
+                    Some((joined, _)) => {
+                        joined.push('\n');
+                        joined.push_str(payload);
+                    }
+                    None => data = Some((payload.to_string(), out.len())),
+                }
+            } else {
+                out.push_str(&self.redact_text(line));
+            }
+        }
+        flush(&mut out, &mut data)?;
+        Ok(out)
+    }
+
+    /// Mask an event stream event by event, holding back partial events. An event
+    /// larger than `max_body_bytes`, or one that can't be masked, ends the stream.
+    pub fn redact_stream(self: Arc<Self>, body: Body) -> Body {
+        let state = (body.into_data_stream(), Vec::new(), false);
+        let events = stream::unfold(state, move |(mut upstream, mut pending, done)| {
+            let redactor = self.clone();
+            async move {
+                if done {
+                    return None;
+                }
+                loop {
+                    match upstream.next().await {
+                        Some(Ok(chunk)) => {
+                            pending.extend_from_slice(&chunk);
+                            if let Some(end) = last_event_end(&pending) {
+                                let complete: Vec<u8> = pending.drain(..end).collect();
+                                return match redactor.redact_events(&String::from_utf8_lossy(&complete)) {
+                                    Ok(out) => Some((Ok(Bytes::from(out)), (upstream, pending, false))),
+                                    Err(e) => Some((Err(axum::Error::new(e)), (upstream, pending, true))),
+                                };
+                            }
+                            if pending.len() > redactor.max_body_bytes {
+                                let e = axum::Error::new("event too large to redact");
+                                return Some((Err(e), (upstream, pending, true)));
+                            }
+                        }
+                        Some(Err(e)) => return Some((Err(e), (upstream, pending, true))),
+                        None if pending.is_empty() => return None,
+                        None => {
+                            let out = redactor
+                                .redact_events(&String::from_utf8_lossy(&pending))
+                                .map(Bytes::from)
+                                .map_err(axum::Error::new);
+                            return Some((out, (upstream, Vec::new(), true)));
+                        }
+                    }
+                }
+            }
+        });
+        Body::from_stream(events)
+    }
+}
+
+fn mask_path(value: &mut Value, steps: &[Step], mask: &str) {
+    let Some((step, rest)) = steps.split_first() else {
+        *value = Value::String(mask.to_string());
+        return;
+    };
+
+    match (step, value) {
+        (Step::Field(name), Value::Object(map)) => {
+            if let Some(child) = map.get_mut(name) {
+                mask_path(child, rest, mask);
+            }
+        }
+        (Step::Index(i), Value::Array(items)) => {
+            if let Some(child) = items.get_mut(*i) {
+                mask_path(child, rest, mask);
+            }
+        }
+        (Step::Any, Value::Object(map)) => map.values_mut().for_each(|v| mask_path(v, rest, mask)),
+        (Step::Any, Value::Array(items)) => items.iter_mut().for_each(|v| mask_path(v, rest, mask)),
+        (Step::Descend(name), value) => {
+            if let Value::Object(map) = value {
+                if let Some(child) = map.get_mut(name) {
+                    mask_path(child, rest, mask);
+                }
+            }
+            match value {
+                Value::Object(map) => map.values_mut().for_each(|v| mask_path(v, steps, mask)),
+                Value::Array(items) => items.iter_mut().for_each(|v| mask_path(v, steps, mask)),
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Compiled redactors for one version of a policy.
+struct Compiled {
+    version: i32,
+    redactor: Result<Arc<Redactor>, String>,
+}
+
+/// Compiled redaction profiles, per policy; recompiled only when the policy version changes.
+#[derive(Default)]
+pub struct RedactorCache {
+    policies: Mutex<HashMap<Uuid, Arc<Compiled>>>,
+}
+
+impl RedactorCache {
+    /// The policy's redactor; None when it has no profile. An invalid stored profile
+    /// is an error: responses for its keys must not go out unmasked.
+    pub fn for_policy(&self, policy: &PolicyRow) -> Result<Option<Arc<Redactor>>, String> {
+        if !policy.redaction.is_active() {
+            return Ok(None);
+        }
+
+        if let Ok(policies) = self.policies.lock() {
+            if let Some(compiled) = policies.get(&policy.id).filter(|c| c.version == policy.version) {
+                return compiled.redactor.clone().map(Some);
+            }
+        }
+
+        let compiled = Arc::new(Compiled {
+            version: policy.version,
+            redactor: compile(&policy.redaction).map(Arc::new),
+        });
+        if let Ok(mut policies) = self.policies.lock() {
+            policies.insert(policy.id, compiled.clone());
+        }
+        compiled.redactor.clone().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CONTENT_TYPE, HeaderMap, HeaderValue},
+    };
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    use relaykey_db::models::RedactionProfile;
+
+    use super::{compile, parse_path, Redactor, Step};
+
+    fn redactor(json_paths: &[&str]) -> Redactor {
+        compile(&RedactionProfile {
+            json_paths: json_paths.iter().map(|p| p.to_string()).collect(),
+            mask: "***".to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn content_type(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn parses_supported_paths() {
+        let steps = parse_path("$.people[*]['date of birth']..iban[0].*").unwrap();
+        let expected = [
+            Step::Field("people".into()),
+            Step::Any,
+            Step::Field("date of birth".into()),
+            Step::Descend("iban".into()),
+            Step::Index(0),
+            Step::Any,
+        ];
+        assert_eq!(format!("{steps:?}"), format!("{expected:?}"));
+        assert!(parse_path("$").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        for path in ["people", "$.", "$..", "$[", "$[name]", "$people"] {
+            assert!(parse_path(path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn masks_selected_values() {
+        let mut doc = json!({
+            "document": { "number": "P1234", "country": "DE" },
+            "people": [{ "dob": "1990-01-01" }, { "dob": "1985-05-05", "name": "A" }],
+            "accounts": { "main": { "iban": "DE89" }, "list": [{ "iban": "FR76" }] },
+            "missing": null
+        });
+        redactor(&["$.document.number", "$.people[*].dob", "$..iban", "$.absent.field"])
+            .redact_value(&mut doc);
+
+        assert_eq!(
+            doc,
+            json!({
+                "document": { "number": "***", "country": "DE" },
+                "people": [{ "dob": "***" }, { "dob": "***", "name": "A" }],
+                "accounts": { "main": { "iban": "***" }, "list": [{ "iban": "***" }] },
+                "missing": null
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn json_paths_apply_whatever_the_content_type() {
+        let redactor = redactor(&["$.document.number"]);
+        let body = r#"{"document":{"number":"P1234"}}"#;
+
+        for headers in [content_type("text/plain"), HeaderMap::new()] {
+            let out = redactor.redact_response(&headers, Body::from(body)).await.unwrap();
+            let doc: Value = serde_json::from_slice(&out).unwrap();
+            assert_eq!(doc, json!({ "document": { "number": "***" } }));
+        }
+
+        let malformed = Body::from(r#"{"document":{"number":"P1234"}"#);
+        let result = redactor
+            .redact_response(&content_type("application/json"), malformed)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn json_paths_apply_to_event_data() {
+        let redactor = Arc::new(redactor(&["$.dob"]));
+        let events = "event: person\ndata: {\"dob\":\"1990-01-01\",\ndata: \"name\":\"A\"}\n\n: keepalive\n\n";
+
+        let body = redactor.clone().redact_stream(Body::from(events));
+        let out = to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            "event: person\ndata: {\"dob\":\"***\",\"name\":\"A\"}\n\n: keepalive\n\n"
+        );
+
+        let body = redactor.redact_stream(Body::from("data: P1234\n\n"));
+        assert!(to_bytes(body, usize::MAX).await.is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header::CONTENT_LENGTH, HeaderMap, Method},
+};
+use futures_util::StreamExt;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tempfile::TempPath;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+use relaykey_db::queries::policies::PolicyRow;
+
+use crate::x402::hash::RequestHasher;
+
+/// Used when the policy leaves `max_request_body_bytes` unset.
+pub const DEFAULT_MAX_REQUEST_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Buffered bodies above this size move from memory to a temp file.
+const SPILL_THRESHOLD_BYTES: usize = 1024 * 1024;
+
+pub fn max_request_body_bytes(policy: &PolicyRow) -> usize {
+    policy
+        .max_request_body_bytes
+        .and_then(|n| usize::try_from(n).ok())
+        .unwrap_or(DEFAULT_MAX_REQUEST_BODY_BYTES)
+}
+
+/// Declared Content-Length already over the cap: reject before reading anything.
+pub fn declared_too_large(headers: &HeaderMap, max: usize) -> bool {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .is_some_and(|len| len > max as u64)
+}
+
+#[derive(Debug)]
+pub enum BodyError {
+    TooLarge,
+    Read(axum::Error),
+    Spill(io::Error),
+}
+
+impl std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BodyError::TooLarge => write!(f, "request body too large"),
+            BodyError::Read(e) => write!(f, "failed to read request body: {e}"),
+            BodyError::Spill(e) => write!(f, "failed to spill request body: {e}"),
+        }
+    }
+}
+
+/// A request body that can be sent more than once (retries, hedges).
+/// Small bodies stay in memory; larger ones live in a temp file removed on drop.
+#[derive(Debug, Clone)]
+pub enum ReplayableBody {
+    Memory(Bytes),
+    File { path: Arc<TempPath>, len: u64 },
+}
+
+impl ReplayableBody {
+    /// Fresh upstream body for one attempt.
+    pub async fn to_reqwest(&self) -> io::Result<reqwest::Body> {
+        match self {
+            ReplayableBody::Memory(b) => Ok(reqwest::Body::from(b.clone())),
+            ReplayableBody::File { path, .. } => {
+                let file = tokio::fs::File::open(path.as_ref()).await?;
+                Ok(reqwest::Body::from(file))
+            }
+        }
+    }
+
+    /// The whole body in memory (request signing needs it in one piece).
+    pub async fn to_bytes(&self) -> io::Result<Bytes> {
+        match self {
+            ReplayableBody::Memory(b) => Ok(b.clone()),
+            ReplayableBody::File { path, .. } => tokio::fs::read(path.as_ref()).await.map(Bytes::from),
+        }
+    }
+
+    /// Re-materialize as an axum body (for handing the request on down the stack).
+    pub async fn to_axum(&self) -> io::Result<Body> {
+        match self {
+            ReplayableBody::Memory(b) => Ok(Body::from(b.clone())),
+            ReplayableBody::File { path, .. } => {
+                let file = tokio::fs::File::open(path.as_ref()).await?;
+                Ok(Body::from_stream(ReaderStream::new(file)))
+            }
+        }
+    }
+}
+
+/// Fully read request body plus its request hash (see `x402::hash`).
+/// Shared through request extensions so the body is read once per request.
+#[derive(Debug, Clone)]
+pub struct BufferedRequest {
+    pub body: ReplayableBody,
+    pub request_hash: String,
+}
+
+/// Read the whole body (up to `max` bytes), hashing as it goes and spilling
+/// to a temp file once it outgrows the in-memory threshold.
+pub async fn buffer_request(
+    body: Body,
+    method: &Method,
+    path_and_query: &str,
+    max: usize,
+) -> Result<BufferedRequest, BodyError> {
+    let mut hasher = RequestHasher::new(method, path_and_query);
+    let mut stream = body.into_data_stream();
+
+    let mut mem: Vec<u8> = Vec::new();
+    let mut spill: Option<(tokio::fs::File, TempPath)> = None;
+    let mut total: usize = 0;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(BodyError::Read)?;
+        total += chunk.len();
+        if total > max {
+            return Err(BodyError::TooLarge);
+        }
+        hasher.update(&chunk);
+
+        if spill.is_none() && mem.len() + chunk.len() > SPILL_THRESHOLD_BYTES {
+            let (file, path) = tempfile::NamedTempFile::new()
+                .map_err(BodyError::Spill)?
+                .into_parts();
+            let mut file = tokio::fs::File::from_std(file);
+            file.write_all(&mem).await.map_err(BodyError::Spill)?;
+            mem = Vec::new();
+            spill = Some((file, path));
+        }
+
+        match spill.as_mut() {
+            Some((file, _)) => file.write_all(&chunk).await.map_err(BodyError::Spill)?,
+            None => mem.extend_from_slice(&chunk),
+        }
+    }
+
+    let body = match spill {
+        Some((mut file, path)) => {
+            file.flush().await.map_err(BodyError::Spill)?;
+            ReplayableBody::File {
+                path: Arc::new(path),
+                len: total as u64,
+            }
+        }
+        None => ReplayableBody::Memory(Bytes::from(mem)),
+    };
+
+    Ok(BufferedRequest {
+        body,
+        request_hash: hasher.finish(),
+    })
+}
+
+/// Forward the client body as it arrives (single attempt, nothing kept).
+/// Sets `overflowed` and aborts the upload once more than `max` bytes went through.
+pub fn streaming_body(body: Body, max: usize, overflowed: Arc<AtomicBool>) -> reqwest::Body {
+    let mut sent: usize = 0;
+    let stream = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk.map_err(io::Error::other)?;
+        sent += chunk.len();
+        if sent > max {
+            overflowed.store(true, Ordering::Relaxed);
+            return Err(io::Error::other("request body too large"));
+        }
+        Ok::<Bytes, io::Error>(chunk)
+    });
+
+    reqwest::Body::wrap_stream(stream)
+}
+
+/// What the proxy sends upstream: a replayable buffered body, or the client
+/// stream itself (usable for exactly one attempt).
+pub enum ForwardBody {
+    Buffered(BufferedRequest),
+    Streaming {
+        body: Option<Body>,
+        max: usize,
+        overflowed: Arc<AtomicBool>,
+    },
+}
+
+impl ForwardBody {
+    pub fn streaming(body: Body, max: usize) -> Self {
+        ForwardBody::Streaming {
+            body: Some(body),
+            max,
+            overflowed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_replayable(&self) -> bool {
+        matches!(self, ForwardBody::Buffered(_))
+    }
+
+    pub fn request_hash(&self) -> Option<&str> {
+        match self {
+            ForwardBody::Buffered(b) => Some(&b.request_hash),
+            ForwardBody::Streaming { .. } => None,
+        }
+    }
+
+    /// Body for the next upstream attempt. None once a streamed body has been sent.
+    pub async fn next_attempt(&mut self) -> io::Result<Option<reqwest::Body>> {
+        match self {
+            ForwardBody::Buffered(b) => b.body.to_reqwest().await.map(Some),
+            ForwardBody::Streaming {
+                body,
+                max,
+                overflowed,
+            } => Ok(body
+                .take()
+                .map(|b| streaming_body(b, *max, overflowed.clone()))),
+        }
+    }
+
+    /// The buffered body, for extra copies (hedged attempts). None for streamed bodies.
+    pub fn replayable(&self) -> Option<&ReplayableBody> {
+        match self {
+            ForwardBody::Buffered(b) => Some(&b.body),
+            ForwardBody::Streaming { .. } => None,
+        }
+    }
+
+    /// Send `body` upstream instead of what the client sent (route transforms). The request
+    /// hash still identifies the client's body. No-op for streamed bodies.
+    pub fn replace_body(&mut self, body: Bytes) {
+        if let ForwardBody::Buffered(b) = self {
+            b.body = ReplayableBody::Memory(body);
+        }
+    }
+
+    /// The client sent more than the policy allows while we were streaming.
+    pub fn overflowed(&self) -> bool {
+        match self {
+            ForwardBody::Buffered(_) => false,
+            ForwardBody::Streaming { overflowed, .. } => overflowed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+use relaykey_db::{models::RouteRule, queries::policies::PolicyRow};
+
+use crate::usage::BlockedReason;
+
+/// Violations reported back to the client; the rest are dropped.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// One schema violation, as returned in the 422 body.
+#[derive(Debug, Serialize)]
+pub struct SchemaViolation {
+    /// JSON pointer into the request body ("" = the whole body).
+    pub path: String,
+    pub message: String,
+}
+
+/// Compile a route's schema. Used by the admin API to reject bad schemas up front.
+pub fn compile(schema: &Value) -> Result<Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| e.to_string())
+}
+
+/// The first route rule whose `request_schema` does not compile, with the error.
+pub fn first_invalid_schema(routes: &[RouteRule]) -> Option<(usize, String)> {
+    routes.iter().enumerate().find_map(|(i, route)| {
+        let schema = route.request_schema.as_ref()?;
+        compile(schema).err().map(|e| (i, e))
+    })
+}
+
+/// Validators for one version of a policy's routes, by route index.
+struct CompiledRoutes {
+    version: i32,
+    validators: Vec<Option<Arc<Validator>>>,
+}
+
+/// Compiled request schemas, per policy. A policy's schemas are compiled the first time
+/// one of them is needed and again only after its version changes.
+#[derive(Default)]
+pub struct SchemaCache {
+    policies: Mutex<HashMap<Uuid, Arc<CompiledRoutes>>>,
+}
+
+impl SchemaCache {
+    /// Validator for `policy.routes[index]`, if that rule has a (valid) schema.
+    pub fn validator(&self, policy: &PolicyRow, index: usize) -> Option<Arc<Validator>> {
+        policy.routes.get(index)?.request_schema.as_ref()?;
+        self.compiled(policy).validators.get(index)?.clone()
+    }
+
+    fn compiled(&self, policy: &PolicyRow) -> Arc<CompiledRoutes> {
+        if let Ok(policies) = self.policies.lock() {
+            if let Some(compiled) = policies.get(&policy.id).filter(|c| c.version == policy.version) {
+                return compiled.clone();
+            }
+        }
+
+        // Compiled outside the lock; a concurrent miss just compiles twice.
+        let validators = policy
+            .routes
+            .iter()
+            .map(|route| {
+                let schema = route.request_schema.as_ref()?;
+                match compile(schema) {
+                    Ok(v) => Some(Arc::new(v)),
+                    Err(e) => {
+                        // Stored without going through the admin API; don't block on it.
+                        tracing::warn!(policy_id = %policy.id, path = %route.path, error = %e, "invalid route request schema ignored");
+                        None
+                    }
+                }
+            })
+            .collect();
+        let compiled = Arc::new(CompiledRoutes {
+            version: policy.version,
+            validators,
+        });
+
+        if let Ok(mut policies) = self.policies.lock() {
+            policies.insert(policy.id, compiled.clone());
+        }
+        compiled
+    }
+}
+
+/// Check a request body against a route's schema.
+pub fn validate(validator: &Validator, body: &[u8]) -> Result<(), Vec<SchemaViolation>> {
+    let instance: Value = serde_json::from_slice(body).map_err(|e| {
+        vec![SchemaViolation {
+            path: String::new(),
+            message: format!("body is not json: {e}"),
+        }]
+    })?;
+
+    let violations: Vec<SchemaViolation> = validator
+        .iter_errors(&instance)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|e| SchemaViolation {
+            path: e.instance_path.to_string(),
+            message: e.to_string(),
+        })
+        .collect();
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+pub fn invalid_response(violations: &[SchemaViolation]) -> Response {
+    let body = json!({
+        "code": BlockedReason::RequestSchemaInvalid.code(),
+        "errors": violations,
+    });
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+}
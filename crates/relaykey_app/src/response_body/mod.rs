@@ -0,0 +1,247 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap,
+    },
+};
+use futures_util::{stream, StreamExt};
+use sqlx::PgPool;
+use std::{io, pin::Pin, time::Duration};
+use tokio::time::{sleep, Sleep};
+use uuid::Uuid;
+
+use relaykey_db::queries::policies::PolicyRow;
+
+use crate::usage::{insert_usage_event_detailed, BlockedReason, UsageDetails, UsageEvent};
+
+/// Upper bound for an event stream when the policy sets no `max_stream_duration_ms`.
+pub const DEFAULT_STREAM_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+const EVENT_STREAM: &str = "text/event-stream";
+
+/// The client asked for server-sent events.
+pub fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.to_ascii_lowercase().contains(EVENT_STREAM))
+}
+
+/// The partner answered with server-sent events.
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().starts_with(EVENT_STREAM))
+}
+
+/// Per-policy guards on what comes back from the partner. None = unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseLimits {
+    pub max_bytes: Option<usize>,
+    pub max_duration: Option<Duration>,
+    /// SSE events per response (and WebSocket messages per connection).
+    pub max_messages: Option<u64>,
+}
+
+impl ResponseLimits {
+    pub fn from_policy(policy: &PolicyRow) -> Self {
+        Self {
+            max_bytes: policy
+                .max_response_body_bytes
+                .and_then(|n| usize::try_from(n).ok()),
+            max_duration: policy
+                .max_stream_duration_ms
+                .and_then(|ms| u64::try_from(ms).ok())
+                .map(Duration::from_millis),
+            max_messages: policy
+                .max_stream_messages
+                .and_then(|n| u64::try_from(n).ok()),
+        }
+    }
+}
+
+/// The usage event for a forwarded request, written once the response body is done
+/// (completed, cut off by a limit, or abandoned by the client) so it carries the
+/// actual number of bytes relayed.
+pub struct ResponseUsage {
+    pub db: PgPool,
+    pub virtual_key_id: Uuid,
+    pub customer_id: Uuid,
+    pub partner_name: String,
+    pub path: String,
+    pub status_code: u16,
+    pub latency_ms: i32,
+    pub details: UsageDetails,
+}
+
+impl ResponseUsage {
+    fn record(self, bytes: u64, events: Option<u64>, blocked_reason: Option<BlockedReason>) {
+        let details = UsageDetails {
+            response_bytes: Some(bytes.min(i64::MAX as u64) as i64),
+            response_messages: events.map(|n| n.min(i32::MAX as u64) as i32),
+            ..self.details
+        };
+
+        // Dropped during shutdown: nothing left to write with.
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        runtime.spawn(async move {
+            let _ = insert_usage_event_detailed(
+                &self.db,
+                UsageEvent {
+                    virtual_key_id: self.virtual_key_id,
+                    customer_id: self.customer_id,
+                    partner_name: &self.partner_name,
+                    path: &self.path,
+                    forwarded: true,
+                    blocked_reason,
+                    status_code: Some(self.status_code),
+                    latency_ms: self.latency_ms,
+                },
+                details,
+            )
+            .await;
+        });
+    }
+}
+
+/// Writes the usage event exactly once; dropping it unrecorded (client went away)
+/// records what was relayed so far.
+struct Recorder {
+    usage: Option<ResponseUsage>,
+    bytes: u64,
+    /// Some when counting SSE events.
+    events: Option<u64>,
+}
+
+impl Recorder {
+    fn finish(&mut self, blocked_reason: Option<BlockedReason>) {
+        if let Some(usage) = self.usage.take() {
+            usage.record(self.bytes, self.events, blocked_reason);
+        }
+    }
+}
+
+/// Counts completed SSE events (a blank line ends an event), across chunk boundaries.
+#[derive(Default)]
+struct SseEventCounter {
+    at_line_start: bool,
+}
+
+impl SseEventCounter {
+    fn count(&mut self, chunk: &[u8]) -> u64 {
+        let mut events = 0;
+        for &b in chunk {
+            match b {
+                b'\n' => {
+                    if self.at_line_start {
+                        events += 1;
+                    }
+                    self.at_line_start = true;
+                }
+                b'\r' => {}
+                _ => self.at_line_start = false,
+            }
+        }
+        events
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.finish(None);
+    }
+}
+
+struct Guarded {
+    inner: axum::body::BodyDataStream,
+    deadline: Option<Pin<Box<Sleep>>>,
+    max_bytes: Option<usize>,
+    max_messages: Option<u64>,
+    sse: Option<SseEventCounter>,
+    recorder: Recorder,
+    done: bool,
+}
+
+/// Relay `body` while enforcing `limits`. On a breach the stream ends with an
+/// error (the client sees a truncated response) and the usage event is tagged
+/// `response_too_large` / `response_stream_timeout` / `stream_message_limit`.
+/// With `sse`, events are counted (and capped by `max_messages`).
+pub fn guard_response(body: Body, limits: ResponseLimits, usage: ResponseUsage, sse: bool) -> Body {
+    let state = Guarded {
+        inner: body.into_data_stream(),
+        deadline: limits.max_duration.map(|d| Box::pin(sleep(d))),
+        max_bytes: limits.max_bytes,
+        max_messages: limits.max_messages,
+        sse: sse.then(SseEventCounter::default),
+        recorder: Recorder {
+            usage: Some(usage),
+            bytes: 0,
+            events: sse.then_some(0),
+        },
+        done: false,
+    };
+
+    let guarded = stream::unfold(state, |mut st| async move {
+        if st.done {
+            return None;
+        }
+
+        // None = streaming deadline hit before the next chunk arrived
+        let next = match st.deadline.as_mut() {
+            Some(deadline) => tokio::select! {
+                chunk = st.inner.next() => Some(chunk),
+                _ = deadline => None,
+            },
+            None => Some(st.inner.next().await),
+        };
+
+        match next {
+            None => {
+                st.done = true;
+                st.recorder.finish(Some(BlockedReason::ResponseStreamTimeout));
+                tracing::warn!(bytes = st.recorder.bytes, "response stream exceeded max duration; aborting");
+                Some((Err(io::Error::other("response stream exceeded max duration")), st))
+            }
+            Some(None) => {
+                st.recorder.finish(None);
+                None
+            }
+            Some(Some(Ok(chunk))) => {
+                let total = st.recorder.bytes + chunk.len() as u64;
+                if st.max_bytes.is_some_and(|max| total > max as u64) {
+                    st.done = true;
+                    st.recorder.finish(Some(BlockedReason::ResponseTooLarge));
+                    tracing::warn!(bytes = st.recorder.bytes, "response exceeded max size; aborting");
+                    return Some((Err(io::Error::other("upstream response too large")), st));
+                }
+
+                st.recorder.bytes = total;
+
+                if let (Some(counter), Some(events)) = (st.sse.as_mut(), st.recorder.events.as_mut()) {
+                    *events += counter.count(&chunk);
+                    if st.max_messages.is_some_and(|max| *events > max) {
+                        st.done = true;
+                        st.recorder.finish(Some(BlockedReason::StreamMessageLimit));
+                        tracing::warn!(bytes = st.recorder.bytes, "event stream exceeded max messages; closing");
+                        return Some((Err(io::Error::other("event stream exceeded max messages")), st));
+                    }
+                }
+
+                Some((Ok::<Bytes, io::Error>(chunk), st))
+            }
+            Some(Some(Err(e))) => {
+                st.done = true;
+                st.recorder.finish(None);
+                Some((Err(io::Error::other(e)), st))
+            }
+        }
+    });
+
+    Body::from_stream(guarded)
+}
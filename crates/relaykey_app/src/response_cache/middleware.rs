@@ -0,0 +1,130 @@
+use axum::{
+    body::Body,
+    http::{HeaderValue, Method, Request},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use std::{sync::Arc, time::Instant};
+
+use relaykey_db::queries::policies::PolicyRow;
+
+use crate::auth::VirtualKeyCtx;
+use crate::canary;
+use crate::idempotency::capture_body;
+use crate::policies::routes::{route_for, split_proxy_path};
+use crate::sandbox::{mode_for, SandboxMode};
+use crate::state::AppState;
+use crate::transform::{self, TemplateVars};
+use crate::usage::{insert_usage_event_detailed, UsageDetails, UsageEvent};
+
+use super::{cache_key, lookup, response_ttl, store, CacheControl, CACHE_HEADER};
+
+/// Answers GET requests on cached routes from Redis. Runs before rate limits, quota
+/// and x402, so a hit never consumes any of them; a miss is forwarded as usual and
+/// the response stored when it is cacheable.
+pub async fn serve_cached(
+    Extension(state): Extension<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+
+    let (Some(vk), Some(policy)) = (
+        req.extensions().get::<VirtualKeyCtx>(),
+        req.extensions().get::<PolicyRow>(),
+    ) else {
+        return next.run(req).await;
+    };
+
+    // Sandboxed keys neither use nor feed shared responses: fixtures must never reach a
+    // live caller, nor partner data a mock one.
+    if mode_for(vk, &state.mock_environments) != SandboxMode::Live {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path().to_string();
+    let (partner, upstream_path) = split_proxy_path(&path);
+    let Some(rule) = route_for(policy, partner, Method::GET.as_str(), &upstream_path) else {
+        return next.run(req).await;
+    };
+    let Some(cfg) = rule.cache.clone() else {
+        return next.run(req).await;
+    };
+
+    // Keyed (and metered) by the partner that serves this caller, canaries included.
+    let partner = canary::route(policy, partner, vk)
+        .map_or(partner, |c| c.alternate.as_str())
+        .to_string();
+    let (vk_id, customer_id) = (vk.id, vk.customer_id);
+    let mut key = cache_key(
+        &cfg,
+        &partner,
+        &upstream_path,
+        req.uri().query(),
+        req.headers(),
+        customer_id,
+    );
+    // Redacted and full responses never share an entry.
+    if policy.redaction.is_active() {
+        key.push_str(&format!(":{}", policy.id));
+    }
+    // Templated rewrites make the partner's answer specific to the caller.
+    if let Some(transform) = &rule.transform {
+        let vars = TemplateVars::from_key(vk);
+        key.push_str(&format!(":{}", transform::fingerprint(transform, &vars)));
+    }
+    let request_cc = CacheControl::parse(req.headers());
+
+    if !request_cc.wants_fresh() {
+        if let Some((hit, size)) = lookup(&state.redis, &key).await {
+            let latency_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+            let details = UsageDetails {
+                cache_hit: true,
+                response_bytes: Some(size as i64),
+                ..Default::default()
+            };
+            let _ = insert_usage_event_detailed(
+                &state.db,
+                UsageEvent {
+                    virtual_key_id: vk_id,
+                    customer_id,
+                    partner_name: &partner,
+                    path: &path,
+                    forwarded: false,
+                    blocked_reason: None,
+                    status_code: Some(hit.status().as_u16()),
+                    latency_ms,
+                },
+                details,
+            )
+            .await;
+            return hit;
+        }
+    }
+
+    let resp = next.run(req).await;
+    let (mut parts, body) = resp.into_parts();
+    parts
+        .headers
+        .insert(CACHE_HEADER, HeaderValue::from_static("MISS"));
+
+    let ttl = if request_cc.no_store {
+        None
+    } else {
+        response_ttl(&cfg, parts.status, &parts.headers)
+    };
+    let Some(ttl) = ttl else {
+        return Response::from_parts(parts, body);
+    };
+
+    let (body, captured) = capture_body(body, cfg.max_body_bytes).await;
+    if let Some(bytes) = captured {
+        store(&state.redis, &key, ttl, parts.status, &parts.headers, &bytes).await;
+    }
+    Response::from_parts(parts, body)
+}
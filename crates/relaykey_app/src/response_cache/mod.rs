@@ -0,0 +1,206 @@
+pub mod middleware;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{
+        header::{AGE, CACHE_CONTROL, CONTENT_LENGTH, SET_COOKIE},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use relaykey_db::models::RouteCache;
+
+use crate::{
+    idempotency::REPLAYED_HEADER, policies::routes::request_fingerprint,
+    response_body::is_event_stream,
+};
+
+/// Set on every response for a cached route: `HIT` or `MISS`.
+pub const CACHE_HEADER: &str = "x-relay-cache";
+
+/// Per-request headers that must not be replayed to other callers.
+const NOT_STORED: &[&str] = &["x-request-id", REPLAYED_HEADER, "age"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body_b64: String,
+    stored_at_ms: u64,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The `Cache-Control` directives we act on.
+#[derive(Debug, Default)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    pub fn parse(headers: &HeaderMap) -> Self {
+        let mut cc = Self::default();
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((n, v)) => (n, Some(v.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = value.and_then(|v| v.parse::<u64>().ok());
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "max-age" => cc.max_age = seconds,
+                "s-maxage" => cc.s_maxage = seconds,
+                _ => {}
+            }
+        }
+        cc
+    }
+
+    /// The client asked for a fresh answer.
+    pub fn wants_fresh(&self) -> bool {
+        self.no_cache || self.no_store || self.max_age == Some(0)
+    }
+}
+
+/// Key: rk:cache:{partner}:{request fingerprint}
+pub fn cache_key(
+    cfg: &RouteCache,
+    partner: &str,
+    upstream_path: &str,
+    query: Option<&str>,
+    request_headers: &HeaderMap,
+    customer_id: Uuid,
+) -> String {
+    let fingerprint = request_fingerprint(
+        upstream_path,
+        query,
+        &cfg.vary_headers,
+        request_headers,
+        cfg.per_customer.then_some(customer_id),
+    );
+    format!("rk:cache:{partner}:{fingerprint}")
+}
+
+/// How long a partner response may be cached, or None when it must not be.
+pub fn response_ttl(cfg: &RouteCache, status: StatusCode, headers: &HeaderMap) -> Option<u64> {
+    if status != StatusCode::OK || headers.contains_key(SET_COOKIE) || is_event_stream(headers) {
+        return None;
+    }
+
+    let too_large = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > cfg.max_body_bytes);
+    if too_large {
+        return None;
+    }
+
+    let ttl = if cfg.ignore_cache_control {
+        cfg.ttl_secs
+    } else {
+        let cc = CacheControl::parse(headers);
+        if cc.no_store || cc.no_cache || cc.private {
+            return None;
+        }
+        cc.s_maxage.or(cc.max_age).or(cfg.ttl_secs)
+    };
+
+    ttl.filter(|&secs| secs > 0)
+}
+
+/// A fresh cached response, with `Age` and `X-Relay-Cache: HIT`. Fail-open: Redis
+/// errors and unreadable entries are treated as a miss.
+pub async fn lookup(redis_client: &redis::Client, key: &str) -> Option<(Response, usize)> {
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, "response cache: redis unavailable (fail-open)");
+            return None;
+        }
+    };
+
+    let raw: Option<String> = match conn.get(key).await {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!(error = %e, "response cache: lookup failed (fail-open)");
+            return None;
+        }
+    };
+    let entry: CachedResponse = serde_json::from_str(&raw?).ok()?;
+    let body = STANDARD.decode(entry.body_b64).ok()?;
+    let size = body.len();
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &entry.headers {
+        if let (Ok(n), Ok(v)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(n, v);
+        }
+    }
+    let age_secs = now_ms().saturating_sub(entry.stored_at_ms) / 1000;
+    headers.insert(AGE, HeaderValue::from(age_secs));
+    headers.insert(CACHE_HEADER, HeaderValue::from_static("HIT"));
+
+    let status = StatusCode::from_u16(entry.status).unwrap_or(StatusCode::OK);
+    Some(((status, headers, Body::from(body)).into_response(), size))
+}
+
+/// Keep a response for `ttl_secs`. Best effort.
+pub async fn store(
+    redis_client: &redis::Client,
+    key: &str,
+    ttl_secs: u64,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &Bytes,
+) {
+    let entry = CachedResponse {
+        status: status.as_u16(),
+        headers: headers
+            .iter()
+            .filter(|(n, _)| !NOT_STORED.contains(&n.as_str()) && n.as_str() != CACHE_HEADER)
+            .filter_map(|(n, v)| v.to_str().ok().map(|v| (n.as_str().to_string(), v.to_string())))
+            .collect(),
+        body_b64: STANDARD.encode(body),
+        stored_at_ms: now_ms(),
+    };
+
+    let Ok(json) = serde_json::to_string(&entry) else {
+        return;
+    };
+
+    match redis_client.get_multiplexed_async_connection().await {
+        Ok(mut conn) => {
+            if let Err(e) = conn.set_ex::<_, _, ()>(key, json, ttl_secs).await {
+                tracing::warn!(error = %e, "response cache: failed to store response");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "response cache: redis unavailable; response not stored"),
+    }
+}
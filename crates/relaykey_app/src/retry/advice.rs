@@ -0,0 +1,115 @@
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Values above this are treated as unix timestamps rather than delta-seconds
+/// when parsing `X-RateLimit-Reset` (vendors disagree on the format).
+const EPOCH_SECONDS_THRESHOLD: u64 = 1_000_000_000;
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// `Retry-After` is either delta-seconds ("120") or an HTTP-date
+/// ("Wed, 21 Oct 2015 07:28:00 GMT"). A zero or past wait is no advice: the caller
+/// falls back to its own backoff instead of resending right away.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let wait = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+            (at - now).to_std().ok()?
+        }
+    };
+    Some(wait).filter(|w| !w.is_zero())
+}
+
+/// `X-RateLimit-Reset` is either seconds-until-reset or an epoch timestamp.
+/// Like `Retry-After`, a reset that is now or past is no advice.
+fn parse_rate_limit_reset(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let raw = value.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)?;
+
+    let wait = if raw as u64 >= EPOCH_SECONDS_THRESHOLD {
+        let reset_ms = (raw * 1000.0) as i64;
+        let wait_ms = reset_ms - now.timestamp_millis();
+        Duration::from_millis(wait_ms.max(0) as u64)
+    } else {
+        Duration::from_secs_f64(raw)
+    };
+    Some(wait).filter(|w| !w.is_zero())
+}
+
+/// How long the partner asked us to wait before sending again, if it said so.
+/// `Retry-After` wins over `X-RateLimit-Reset` when both are present.
+pub fn upstream_retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    if let Some(d) = header_str(headers, "retry-after").and_then(|v| parse_retry_after(v, now)) {
+        return Some(d);
+    }
+
+    header_str(headers, "x-ratelimit-reset").and_then(|v| parse_rate_limit_reset(v, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderMap, HeaderValue};
+    use chrono::{DateTime, TimeZone, Utc};
+    use std::time::Duration;
+
+    use super::{parse_rate_limit_reset, parse_retry_after, upstream_retry_after};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap()
+    }
+
+    #[test]
+    fn retry_after_delta_seconds() {
+        assert_eq!(parse_retry_after("120", now()), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_date() {
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now()),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn zero_or_past_retry_after_is_no_advice() {
+        assert_eq!(parse_retry_after("0", now()), None);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now()), None);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now()), None);
+    }
+
+    #[test]
+    fn garbage_retry_after_is_ignored() {
+        for value in ["soon", "-5", "1.5", "Someday, 99 Foo 2015"] {
+            assert_eq!(parse_retry_after(value, now()), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn rate_limit_reset_seconds_or_epoch() {
+        assert_eq!(parse_rate_limit_reset("30", now()), Some(Duration::from_secs(30)));
+        let epoch = (now().timestamp() + 60).to_string();
+        assert_eq!(parse_rate_limit_reset(&epoch, now()), Some(Duration::from_secs(60)));
+        let past = (now().timestamp() - 60).to_string();
+        assert_eq!(parse_rate_limit_reset(&past, now()), None);
+        assert_eq!(parse_rate_limit_reset("0", now()), None);
+    }
+
+    #[test]
+    fn zero_retry_after_falls_back_to_rate_limit_reset() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("5"));
+        assert_eq!(upstream_retry_after(&headers, now()), Some(Duration::from_secs(5)));
+
+        headers.remove("x-ratelimit-reset");
+        assert_eq!(upstream_retry_after(&headers, now()), None);
+    }
+}
@@ -0,0 +1,57 @@
+use redis::AsyncCommands;
+use std::time::Duration;
+
+/// Upper bound on how long a single upstream hint can park a partner.
+/// Protects against a vendor sending a bogus far-future reset.
+const MAX_COOLDOWN: Duration = Duration::from_secs(15 * 60);
+
+fn cooldown_key(partner_name: &str) -> String {
+    format!("rk:partner_cooldown:{partner_name}")
+}
+
+/// Mark a partner as "cooling down" for every gateway instance.
+/// Only ever extends an existing cooldown; best-effort (Redis errors are logged).
+pub async fn set_partner_cooldown(redis_client: &redis::Client, partner_name: &str, wait: Duration) {
+    let wait = wait.min(MAX_COOLDOWN);
+    let wait_ms = wait.as_millis() as u64;
+    if wait_ms == 0 {
+        return;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, partner = %partner_name, "cooldown: redis unavailable");
+            return;
+        }
+    };
+
+    let key = cooldown_key(partner_name);
+
+    // Keep whichever reset is further in the future.
+    let current_ms: i64 = conn.pttl(&key).await.unwrap_or(-2);
+    if current_ms >= wait_ms as i64 {
+        return;
+    }
+
+    if let Err(e) = conn.pset_ex::<_, _, ()>(&key, wait_ms, wait_ms).await {
+        tracing::warn!(error = %e, partner = %partner_name, "cooldown: failed to set flag");
+    }
+}
+
+/// Remaining cooldown for a partner, if one is active.
+/// Fail-open: Redis errors report no cooldown.
+pub async fn partner_cooldown_remaining(
+    redis_client: &redis::Client,
+    partner_name: &str,
+) -> Option<Duration> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await.ok()?;
+    let remaining_ms: i64 = conn.pttl(cooldown_key(partner_name)).await.ok()?;
+
+    // -2: no key, -1: no TTL (should not happen; ignore rather than block forever)
+    if remaining_ms <= 0 {
+        return None;
+    }
+
+    Some(Duration::from_millis(remaining_ms as u64))
+}
@@ -1,4 +1,6 @@
+pub mod advice;
 pub mod budget;
 pub mod classify;
+pub mod cooldown;
 pub mod partner;
 pub mod policy;
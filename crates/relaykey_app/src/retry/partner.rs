@@ -1,16 +1,10 @@
 use axum::http::StatusCode;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PartnerRetryProfile {
     pub retry_429: bool,
 }
 
-impl Default for PartnerRetryProfile {
-    fn default() -> Self {
-        Self { retry_429: false }
-    }
-}
-
 pub fn profile_for_partner(_partner_name: &str) -> PartnerRetryProfile {
     // Later: load from DB. For now: safe default.
     PartnerRetryProfile::default()
@@ -0,0 +1,358 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock},
+};
+
+use relaykey_db::{
+    models::RedactionProfile,
+    queries::fixtures::{upsert_recorded_fixture, FixtureRow, NewFixture},
+};
+
+use crate::auth::VirtualKeyCtx;
+use crate::policies::routes::path_matches;
+use crate::redaction::{compile, Redactor};
+use crate::state::AppState;
+use crate::transform::TemplateVars;
+
+/// Set on every response served from a fixture: `recorded`, `canned` or `miss`.
+pub const MOCK_HEADER: &str = "x-relay-mock";
+
+/// Larger responses are relayed but not recorded.
+pub const MAX_RECORDED_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Requests reach the partner.
+    Live,
+    /// Answered from the partner's fixtures; the partner is never called.
+    Mock,
+    /// Requests reach the partner and the responses are stored as fixtures.
+    Record,
+}
+
+impl SandboxMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "live" => Some(Self::Live),
+            "mock" => Some(Self::Mock),
+            "record" => Some(Self::Record),
+            _ => None,
+        }
+    }
+}
+
+/// The key's own mode if it has one; otherwise mock for the configured environments.
+pub fn mode_for(vk: &VirtualKeyCtx, mock_environments: &[String]) -> SandboxMode {
+    match vk.sandbox_mode.as_deref().and_then(SandboxMode::parse) {
+        Some(mode) => mode,
+        None if mock_environments.contains(&vk.environment) => SandboxMode::Mock,
+        None => SandboxMode::Live,
+    }
+}
+
+/// Query string with its parameters sorted, so `b=2&a=1` matches `a=1&b=2`. Empty = no query.
+pub fn normalize_query(query: Option<&str>) -> String {
+    let mut pairs: Vec<&str> = query
+        .unwrap_or("")
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .collect();
+    pairs.sort_unstable();
+    pairs.join("&")
+}
+
+/// sha256 of a request body. JSON is compared by value (key order and whitespace don't
+/// matter), anything else byte for byte. None for an empty body.
+pub fn body_fingerprint(body: &[u8]) -> Option<String> {
+    if body.is_empty() {
+        return None;
+    }
+    // serde_json's maps are sorted, so re-serializing gives a canonical form.
+    let canonical = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| serde_json::to_vec(&v).ok());
+    let digest = Sha256::digest(canonical.as_deref().unwrap_or(body));
+    Some(hex::encode(digest))
+}
+
+/// The most specific fixture for a request: one pinning the query, then the body, then an
+/// exact path, then the longest path; the newest among equals. `fixtures` is newest first.
+pub fn find_fixture<'a>(
+    fixtures: &'a [FixtureRow],
+    path: &str,
+    query: &str,
+    body_sha256: Option<&str>,
+) -> Option<&'a FixtureRow> {
+    fixtures
+        .iter()
+        .rev()
+        .filter(|f| path_matches(&f.path, path))
+        .filter(|f| f.query.as_deref().is_none_or(|q| normalize_query(Some(q)) == query))
+        .filter(|f| f.body_sha256.as_deref().is_none_or(|h| Some(h) == body_sha256))
+        .max_by_key(|f| {
+            (
+                f.query.is_some(),
+                f.body_sha256.is_some(),
+                !f.path.contains('*'),
+                f.path.len(),
+            )
+        })
+}
+
+/// Answer from a fixture. Templated fixtures have their body and header values rendered.
+pub fn fixture_response(fixture: &FixtureRow, vars: &TemplateVars) -> Response {
+    let status = u16::try_from(fixture.status)
+        .ok()
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .unwrap_or(StatusCode::OK);
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in fixture.headers.iter() {
+        let value = if fixture.template {
+            vars.render(value)
+        } else {
+            value.clone()
+        };
+        match (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value)) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => tracing::warn!(fixture_id = %fixture.id, header = %name, "fixture header skipped"),
+        }
+    }
+    if let Ok(source) = HeaderValue::try_from(fixture.source.as_str()) {
+        headers.insert(MOCK_HEADER, source);
+    }
+
+    let body = if fixture.template {
+        Body::from(vars.render(&String::from_utf8_lossy(&fixture.body)))
+    } else {
+        Body::from(fixture.body.clone())
+    };
+
+    (status, headers, body).into_response()
+}
+
+/// Mock-mode answer when no fixture matches.
+pub fn miss_response() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        [(MOCK_HEADER, "miss")],
+        axum::Json(serde_json::json!({ "code": "mock_fixture_not_found" })),
+    )
+        .into_response()
+}
+
+/// Credentials masked in recorded bodies, whatever the policy's own redaction.
+fn secrets_redactor() -> &'static Redactor {
+    static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+    REDACTOR.get_or_init(|| {
+        let profile = RedactionProfile {
+            json_paths: [
+                "$..access_token",
+                "$..refresh_token",
+                "$..id_token",
+                "$..client_secret",
+                "$..api_key",
+                "$..apiKey",
+                "$..password",
+                "$..secret",
+                "$..token",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            patterns: vec![r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]+".to_string()],
+            max_body_bytes: MAX_RECORDED_BYTES,
+            ..Default::default()
+        };
+        compile(&profile).expect("built-in secrets profile")
+    })
+}
+
+/// Response headers worth replaying: no cookies, credentials or transport framing.
+fn recorded_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    const DROPPED: &[&str] = &[
+        "set-cookie",
+        "content-length",
+        "content-encoding",
+        "transfer-encoding",
+        "connection",
+        "date",
+        MOCK_HEADER,
+    ];
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            !DROPPED.contains(&name)
+                && !["auth", "token", "secret", "cookie", "key"]
+                    .iter()
+                    .any(|s| name.contains(s))
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Store a relayed response as a fixture, in the background. Bodies that can't be checked
+/// for secrets (binary, not UTF-8) are not recorded.
+pub fn record(state: Arc<AppState>, mut fixture: NewFixture, headers: HeaderMap, body: Bytes) {
+    tokio::spawn(async move {
+        match secrets_redactor().redact_response(&headers, Body::from(body)).await {
+            Ok(masked) => fixture.body = masked.to_vec(),
+            Err(e) => {
+                tracing::warn!(error = %e, partner = %fixture.partner_name, path = %fixture.path, "sandbox: response not recorded");
+                return;
+            }
+        }
+        fixture.headers = recorded_headers(&headers);
+
+        if let Err(e) = upsert_recorded_fixture(&state.db, &fixture).await {
+            tracing::error!(error = %e, partner = %fixture.partner_name, "sandbox: recording fixture failed");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        extract::Request,
+        middleware,
+        response::Response,
+        routing::get,
+        Extension, Router,
+    };
+    use std::{sync::Arc, time::Duration};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use relaykey_db::queries::policies::PolicyRow;
+
+    use super::{mode_for, SandboxMode, MOCK_HEADER};
+    use crate::auth::VirtualKeyCtx;
+    use crate::coalesce::middleware::coalesce_requests;
+    use crate::response_cache::middleware::serve_cached;
+    use crate::state::AppState;
+
+    fn state() -> Arc<AppState> {
+        // Nothing listens on these; both stores fail open.
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://relaykey@127.0.0.1:1/relaykey")
+            .unwrap();
+        Arc::new(AppState {
+            db,
+            redis: redis::Client::open("redis://127.0.0.1:1/").unwrap(),
+            http: reqwest::Client::new(),
+            internal_http: reqwest::Client::new(),
+            key_salt: String::new(),
+            mock_environments: vec!["ci".to_string()],
+            latency: Default::default(),
+            clients: Default::default(),
+            coalescer: Default::default(),
+            schemas: Default::default(),
+            redactors: Default::default(),
+        })
+    }
+
+    /// A cached, coalesced route for every partner.
+    fn policy() -> PolicyRow {
+        serde_json::from_value(serde_json::json!({
+            "id": Uuid::nil(),
+            "name": "sandbox",
+            "endpoint_allowlist": ["/*"],
+            "rps_limit": null,
+            "rps_burst": null,
+            "monthly_quota": null,
+            "timeout_ms": 1000,
+            "routes": [{
+                "path": "/v1/*",
+                "cache": { "ttl_secs": 60, "ignore_cache_control": true },
+                "coalesce": {}
+            }]
+        }))
+        .unwrap()
+    }
+
+    /// Same customer, so the two keys share cache and coalesce entries when live.
+    fn key(environment: &str) -> VirtualKeyCtx {
+        VirtualKeyCtx {
+            id: Uuid::new_v4(),
+            name: environment.to_string(),
+            environment: environment.to_string(),
+            tags: Vec::new(),
+            rps_limit: None,
+            rps_burst: None,
+            monthly_quota: None,
+            policy_id: Uuid::nil(),
+            policy: policy(),
+            customer_id: Uuid::nil(),
+            sandbox_mode: None,
+        }
+    }
+
+    /// Stands in for the proxy: mock keys get a fixture, live keys the partner's answer.
+    async fn upstream(Extension(state): Extension<Arc<AppState>>, req: Request) -> Response {
+        let vk = req.extensions().get::<VirtualKeyCtx>().unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        match mode_for(vk, &state.mock_environments) {
+            SandboxMode::Mock => Response::builder()
+                .header(MOCK_HEADER, "canned")
+                .body(Body::from("fixture"))
+                .unwrap(),
+            _ => Response::new(Body::from("partner")),
+        }
+    }
+
+    fn app(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/proxy/:partner/*tail", get(upstream))
+            .route_layer(middleware::from_fn(coalesce_requests))
+            .route_layer(middleware::from_fn(serve_cached))
+            .layer(Extension(state))
+    }
+
+    async fn send(app: Router, vk: VirtualKeyCtx) -> (Option<String>, String) {
+        let mut req = Request::get("/proxy/vendor/v1/accounts").body(Body::empty()).unwrap();
+        req.extensions_mut().insert(policy());
+        req.extensions_mut().insert(vk);
+        let resp = app.oneshot(req).await.unwrap();
+        let mock = resp
+            .headers()
+            .get(MOCK_HEADER)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (mock, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Sends `first`, then `second` while the first is still upstream.
+    async fn overlapping(first: VirtualKeyCtx, second: VirtualKeyCtx) -> [(Option<String>, String); 2] {
+        let app = app(state());
+        let leader = tokio::spawn(send(app.clone(), first));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let follower = send(app, second).await;
+        [leader.await.unwrap(), follower]
+    }
+
+    #[tokio::test]
+    async fn mock_response_is_not_shared_with_live_key() {
+        let [mock, live] = overlapping(key("ci"), key("production")).await;
+        assert_eq!(mock, (Some("canned".to_string()), "fixture".to_string()));
+        assert_eq!(live, (None, "partner".to_string()));
+    }
+
+    #[tokio::test]
+    async fn live_response_is_not_shared_with_mock_key() {
+        let [live, mock] = overlapping(key("production"), key("ci")).await;
+        assert_eq!(live, (None, "partner".to_string()));
+        assert_eq!(mock, (Some("canned".to_string()), "fixture".to_string()));
+    }
+}
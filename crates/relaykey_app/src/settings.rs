@@ -7,6 +7,8 @@ pub struct Settings {
     pub redis_url: String,
     pub log_filter: String,
     pub key_salt: String,
+    /// Key environments that default to sandbox mock mode.
+    pub mock_environments: Vec<String>,
 }
 
 impl Settings {
@@ -25,6 +27,13 @@ impl Settings {
         let log_filter = std::env::var("RELAYKEY_LOG").unwrap_or_else(|_| "info".to_string());
         let key_salt = std::env::var("RELAYKEY_KEY_SALT")
             .map_err(|_| "RELAYKEY_KEY_SALT is required".to_string())?;
+        let mock_environments = std::env::var("RELAYKEY_MOCK_ENVIRONMENTS")
+            .unwrap_or_else(|_| "ci".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(str::to_string)
+            .collect();
 
         Ok(Self {
             bind_addr,
@@ -32,6 +41,7 @@ impl Settings {
             redis_url,
             log_filter,
             key_salt,
+            mock_environments,
         })
     }
 }
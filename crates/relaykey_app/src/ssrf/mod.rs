@@ -0,0 +1,176 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use url::{Host, Url};
+
+/// Addresses an external partner may never resolve to: loopback, private (RFC 1918 /
+/// unique local), link-local (incl. cloud metadata 169.254.169.254), CGNAT (incl.
+/// 100.100.100.200), unspecified, multicast and reserved ranges.
+pub fn is_forbidden_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_forbidden_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped().or_else(|| embedded_v4(v6)) {
+            Some(v4) => is_forbidden_v4(v4),
+            None => is_forbidden_v6(v6),
+        },
+    }
+}
+
+/// The IPv4 address carried by a NAT64 (64:ff9b::/96), IPv4-compatible (::a.b.c.d) or
+/// 6to4 (2002::/16) address; such addresses reach it through a translator or relay.
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let s = ip.segments();
+    let low = Ipv4Addr::new((s[6] >> 8) as u8, s[6] as u8, (s[7] >> 8) as u8, s[7] as u8);
+    match s {
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(low),
+        // :: and ::1 are left to the IPv6 checks.
+        [0, 0, 0, 0, 0, 0, hi, lo] if hi != 0 || lo > 1 => Some(low),
+        [0x2002, hi, lo, ..] => Some(Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8)),
+        _ => None,
+    }
+}
+
+fn is_forbidden_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || a == 0
+        // 100.64.0.0/10 shared address space (carrier NAT, some metadata services)
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24 IETF protocol assignments
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // 198.18.0.0/15 benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // 240.0.0.0/4 reserved
+        || a >= 240
+}
+
+fn is_forbidden_v6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+}
+
+/// The resolver refused every address for a host.
+#[derive(Debug)]
+pub struct SsrfBlocked {
+    pub host: String,
+}
+
+impl std::fmt::Display for SsrfBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} resolves only to blocked addresses", self.host)
+    }
+}
+
+impl std::error::Error for SsrfBlocked {}
+
+/// Resolve `host:port`, dropping forbidden addresses unless `internal`.
+/// Every connection is checked at connect time, so a DNS rebind can't slip through.
+pub async fn resolve(host: &str, port: u16, internal: bool) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if internal {
+        return Ok(addrs);
+    }
+
+    let allowed: Vec<SocketAddr> = addrs
+        .iter()
+        .copied()
+        .filter(|a| !is_forbidden_ip(a.ip()))
+        .collect();
+
+    if allowed.is_empty() && !addrs.is_empty() {
+        tracing::warn!(host = %host, "ssrf guard: host resolves only to blocked addresses");
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            SsrfBlocked {
+                host: host.to_string(),
+            },
+        ));
+    }
+    Ok(allowed)
+}
+
+/// DNS resolver for the shared upstream client: external partners never reach
+/// internal address space.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuardedResolver;
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolve(&host, 0, false).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// IP-literal hosts never go through DNS, so they are checked directly.
+pub fn literal_host_forbidden(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => is_forbidden_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_forbidden_ip(IpAddr::V6(ip)),
+        _ => false,
+    }
+}
+
+/// An upstream failure caused by the guard (somewhere in the error's source chain).
+pub fn is_ssrf_blocked(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(err);
+    while let Some(e) = current {
+        if e.is::<SsrfBlocked>() {
+            return true;
+        }
+        if let Some(io) = e.downcast_ref::<io::Error>() {
+            if io.get_ref().is_some_and(|inner| inner.is::<SsrfBlocked>()) {
+                return true;
+            }
+        }
+        current = e.source();
+    }
+    false
+}
+
+/// Admin-time check for a partner base URL: http(s), a host, no embedded credentials,
+/// and (for external partners) no literal or resolved address in blocked space.
+pub async fn validate_base_url(raw: &str, internal: bool) -> Result<Url, String> {
+    let url = Url::parse(raw).map_err(|e| format!("invalid url {raw}: {e}"))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{raw}: scheme must be http or https"));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(format!("{raw}: credentials in the url are not allowed"));
+    }
+    let Some(host) = url.host_str() else {
+        return Err(format!("{raw}: missing host"));
+    };
+
+    if internal {
+        return Ok(url);
+    }
+
+    if literal_host_forbidden(&url) {
+        return Err(format!("{raw}: address is private, loopback, link-local or reserved"));
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    // url keeps IPv6 literals bracketed; lookup wants them bare
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match resolve(host, port, false).await {
+        Ok(_) => Ok(url),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Err(format!(
+            "{raw}: host resolves to private, loopback, link-local or reserved addresses"
+        )),
+        Err(e) => Err(format!("{raw}: cannot resolve host: {e}")),
+    }
+}
@@ -1,8 +1,29 @@
 use relaykey_db::{Db, RedisConn};
 
+use crate::coalesce::Coalescer;
+use crate::hedge::LatencyTracker;
+use crate::redaction::RedactorCache;
+use crate::request_schema::SchemaCache;
+use crate::tls::ClientPool;
+
 pub struct AppState {
     pub db: Db,
     pub redis: RedisConn,
+    /// Shared upstream client; its resolver refuses private/loopback/link-local addresses.
     pub http: reqwest::Client,
+    /// Same, without the SSRF resolver guard (partners marked `internal`).
+    pub internal_http: reqwest::Client,
     pub key_salt: String,
+    /// Key environments that default to sandbox mock mode.
+    pub mock_environments: Vec<String>,
+    /// Upstream latency samples per partner (drives p95-based hedging).
+    pub latency: LatencyTracker,
+    /// Per-partner clients for partners with their own TLS settings (mTLS, pinning).
+    pub clients: ClientPool,
+    /// Identical in-flight requests on coalesced routes.
+    pub coalescer: Coalescer,
+    /// Compiled route request schemas, per policy version.
+    pub schemas: SchemaCache,
+    /// Compiled response redaction profiles, per policy version.
+    pub redactors: RedactorCache,
 }
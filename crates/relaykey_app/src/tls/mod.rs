@@ -0,0 +1,303 @@
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    client::WebPkiServerVerifier,
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use relaykey_db::{
+    models::{PartnerTls, PemSource},
+    queries::virtual_keys::{CredentialRow, PartnerRow},
+};
+
+use crate::ssrf::GuardedResolver;
+
+/// Rebuild pooled clients this often so rotated cert/key files are picked up.
+const CLIENT_REFRESH: Duration = Duration::from_secs(10 * 60);
+
+/// Upstream HTTP client settings shared by the default client and per-partner clients.
+/// Only clients for `internal` partners may resolve to private address space.
+/// Redirects are returned to the caller, never followed: the next hop would skip the origin
+/// and address checks and receive the credential headers.
+pub fn upstream_client_builder(internal: bool) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none());
+
+    if internal {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(GuardedResolver))
+    }
+}
+
+/// Settings that can't be used, with the reason. Checked when settings are saved and again
+/// when a client is built, since they may also be written to the database directly.
+pub fn validate(cfg: &PartnerTls) -> Result<(), String> {
+    if matches!(cfg.client_key, Some(PemSource::Inline { .. })) {
+        return Err("client_key must be read from env or file, not stored inline".to_string());
+    }
+    if cfg.client_cert.is_some() != cfg.client_key.is_some() {
+        return Err("client_cert and client_key must be set together".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    /// rejected by `validate`
+    Config(String),
+    /// env var / file missing or unreadable
+    Source(String),
+    Pem(String),
+    Rustls(rustls::Error),
+    Verifier(String),
+    Client(reqwest::Error),
+}
+
+impl std::fmt::Display for TlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsError::Config(e) => write!(f, "invalid tls settings: {e}"),
+            TlsError::Source(e) => write!(f, "failed to load tls material: {e}"),
+            TlsError::Pem(e) => write!(f, "invalid pem: {e}"),
+            TlsError::Rustls(e) => write!(f, "invalid tls configuration: {e}"),
+            TlsError::Verifier(e) => write!(f, "failed to build certificate verifier: {e}"),
+            TlsError::Client(e) => write!(f, "failed to build http client: {e}"),
+        }
+    }
+}
+
+/// A partner's own client. `tls` is reused for WebSocket handshakes.
+#[derive(Clone)]
+pub struct PartnerClient {
+    pub http: reqwest::Client,
+    pub tls: Arc<ClientConfig>,
+}
+
+struct Pooled {
+    cfg: PartnerTls,
+    internal: bool,
+    built_at: Instant,
+    client: PartnerClient,
+}
+
+/// One client per partner with TLS settings (and per credential with its own identity), so
+/// client identities and their connection pools are never shared.
+#[derive(Default)]
+pub struct ClientPool {
+    clients: Mutex<HashMap<Uuid, Pooled>>,
+}
+
+impl ClientPool {
+    /// The client for requests sent to `partner` with `cred`, or None for the shared client.
+    /// A credential with its own client identity gets a client of its own, built from the
+    /// partner's trust settings and that identity.
+    pub fn for_credential(
+        &self,
+        partner: &PartnerRow,
+        cred: &CredentialRow,
+    ) -> Result<Option<PartnerClient>, TlsError> {
+        match &cred.client_identity {
+            Some(identity) => {
+                let cfg = PartnerTls {
+                    client_cert: Some(identity.client_cert.clone()),
+                    client_key: Some(identity.client_key.clone()),
+                    ..partner.tls.0.clone()
+                };
+                self.get(cred.id, &cfg, partner.internal)
+            }
+            None => self.get(partner.id, &partner.tls.0, partner.internal),
+        }
+    }
+
+    /// The client for `owner_id` (a partner, or a credential with its own identity), or None
+    /// when `cfg` has no TLS settings (use the shared client).
+    pub fn get(
+        &self,
+        owner_id: Uuid,
+        cfg: &PartnerTls,
+        internal: bool,
+    ) -> Result<Option<PartnerClient>, TlsError> {
+        if cfg.is_default() {
+            return Ok(None);
+        }
+
+        if let Ok(clients) = self.clients.lock() {
+            if let Some(p) = clients.get(&owner_id) {
+                if p.cfg == *cfg && p.internal == internal && p.built_at.elapsed() < CLIENT_REFRESH {
+                    return Ok(Some(p.client.clone()));
+                }
+            }
+        }
+
+        let client = build_client(cfg, internal)?;
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(
+                owner_id,
+                Pooled {
+                    cfg: cfg.clone(),
+                    internal,
+                    built_at: Instant::now(),
+                    client: client.clone(),
+                },
+            );
+        }
+        Ok(Some(client))
+    }
+}
+
+fn load_pem(source: &PemSource) -> Result<Vec<u8>, TlsError> {
+    match source {
+        PemSource::Inline { pem } => Ok(pem.as_bytes().to_vec()),
+        PemSource::Env { var } => std::env::var(var)
+            .map(String::into_bytes)
+            .map_err(|_| TlsError::Source(format!("env var {var} not set"))),
+        PemSource::File { path } => {
+            std::fs::read(path).map_err(|e| TlsError::Source(format!("{path}: {e}")))
+        }
+    }
+}
+
+fn load_certs(source: &PemSource) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let pem = load_pem(source)?;
+    let certs = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Pem(e.to_string()))?;
+    if certs.is_empty() {
+        return Err(TlsError::Pem("no certificates found".to_string()));
+    }
+    Ok(certs)
+}
+
+fn build_client(cfg: &PartnerTls, internal: bool) -> Result<PartnerClient, TlsError> {
+    validate(cfg).map_err(TlsError::Config)?;
+    let provider = Arc::new(ring::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    if !cfg.ca_only {
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    }
+    if let Some(ca) = &cfg.ca_bundle {
+        for cert in load_certs(ca)? {
+            roots.add(cert).map_err(TlsError::Rustls)?;
+        }
+    }
+
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| TlsError::Verifier(e.to_string()))?;
+
+    let pins = cfg
+        .pinned_sha256
+        .iter()
+        .map(|p| normalize_fingerprint(p))
+        .collect::<Vec<_>>();
+
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(TlsError::Rustls)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier { inner: webpki, pins }));
+
+    let mut tls = match (&cfg.client_cert, &cfg.client_key) {
+        (Some(cert), Some(key)) => {
+            let chain = load_certs(cert)?;
+            let key = PrivateKeyDer::from_pem_slice(&load_pem(key)?)
+                .map_err(|e| TlsError::Pem(e.to_string()))?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(TlsError::Rustls)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    // WebSocket handshakes are HTTP/1.1; the HTTP client may negotiate h2.
+    let mut ws_tls = tls.clone();
+    ws_tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+    tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let http = upstream_client_builder(internal)
+        .use_preconfigured_tls(tls)
+        .build()
+        .map_err(TlsError::Client)?;
+
+    Ok(PartnerClient {
+        http,
+        tls: Arc::new(ws_tls),
+    })
+}
+
+/// Lowercase hex without separators ("AB:CD:.." and "abcd.." both work).
+fn normalize_fingerprint(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Regular chain validation, then (when pins are configured) the leaf certificate's
+/// SHA-256 must match one of them.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<String>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified =
+            self.inner
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+
+        if !self.pins.is_empty() {
+            let fingerprint = hex::encode(Sha256::digest(end_entity.as_ref()));
+            if !self.pins.contains(&fingerprint) {
+                tracing::warn!(fingerprint = %fingerprint, "partner certificate does not match pinned fingerprint");
+                return Err(rustls::Error::General(
+                    "server certificate does not match pinned fingerprint".to_string(),
+                ));
+            }
+        }
+
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
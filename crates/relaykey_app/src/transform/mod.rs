@@ -0,0 +1,226 @@
+use axum::{
+    body::Bytes,
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use relaykey_db::models::{JsonOp, RouteTransform};
+
+use crate::auth::VirtualKeyCtx;
+
+#[derive(Debug)]
+pub enum TransformError {
+    NotJson,
+    InvalidPointer(String),
+    /// The pointer runs through a value that can't hold it (a string, a missing index).
+    Unreachable(String),
+}
+
+impl std::fmt::Display for TransformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformError::NotJson => write!(f, "body is not json"),
+            TransformError::InvalidPointer(p) => write!(f, "invalid json pointer: {p}"),
+            TransformError::Unreachable(p) => write!(f, "cannot set {p}"),
+        }
+    }
+}
+
+/// Values of the virtual key that templates may reference.
+pub struct TemplateVars {
+    customer_id: String,
+    virtual_key_id: String,
+    virtual_key_name: String,
+    environment: String,
+    tags: String,
+}
+
+impl TemplateVars {
+    pub fn from_key(vk: &VirtualKeyCtx) -> Self {
+        Self {
+            customer_id: vk.customer_id.to_string(),
+            virtual_key_id: vk.id.to_string(),
+            virtual_key_name: vk.name.clone(),
+            environment: vk.environment.clone(),
+            tags: vk.tags.join(","),
+        }
+    }
+
+    /// Unknown placeholders are left as they are.
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{customer_id}", &self.customer_id)
+            .replace("{virtual_key_id}", &self.virtual_key_id)
+            .replace("{virtual_key_name}", &self.virtual_key_name)
+            .replace("{environment}", &self.environment)
+            .replace("{tags}", &self.tags)
+    }
+
+    fn render_value(&self, value: &Value) -> Value {
+        match value {
+            Value::String(s) => Value::String(self.render(s)),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.render_value(v)).collect()),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.render_value(v)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+/// `application/json` or any `+json` media type.
+pub fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .is_some_and(|v| v == "application/json" || v.ends_with("+json"))
+}
+
+/// The route's templated upstream headers. Invalid entries are skipped with a warning.
+pub fn request_headers(cfg: &RouteTransform, vars: &TemplateVars) -> Vec<(HeaderName, HeaderValue)> {
+    cfg.request_headers
+        .iter()
+        .filter_map(|(name, template)| {
+            let parsed = HeaderName::from_bytes(name.trim().as_bytes())
+                .ok()
+                .zip(HeaderValue::from_str(&vars.render(template)).ok());
+            if parsed.is_none() {
+                tracing::warn!(header = %name, "invalid templated header; skipped");
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Hash of the route's transform as rendered for one key. Cached and coalesced responses are
+/// only shared between callers whose upstream requests and response rewrites come out the
+/// same; a transform without placeholders renders identically for everyone.
+pub fn fingerprint(cfg: &RouteTransform, vars: &TemplateVars) -> String {
+    let render_ops = |ops: &[JsonOp]| -> Vec<JsonOp> {
+        ops.iter()
+            .map(|op| match op {
+                JsonOp::Set { path, value } => JsonOp::Set {
+                    path: path.clone(),
+                    value: vars.render_value(value),
+                },
+                other => other.clone(),
+            })
+            .collect()
+    };
+    let rendered = RouteTransform {
+        request: render_ops(&cfg.request),
+        response: render_ops(&cfg.response),
+        request_headers: cfg
+            .request_headers
+            .iter()
+            .map(|(name, template)| (name.clone(), vars.render(template)))
+            .collect(),
+        max_response_bytes: cfg.max_response_bytes,
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&rendered).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+/// Apply `ops` to a JSON body.
+pub fn apply(ops: &[JsonOp], vars: &TemplateVars, body: &[u8]) -> Result<Bytes, TransformError> {
+    let mut doc: Value = serde_json::from_slice(body).map_err(|_| TransformError::NotJson)?;
+
+    for op in ops {
+        match op {
+            JsonOp::Set { path, value } => set(&mut doc, &tokens(path)?, vars.render_value(value), path)?,
+            JsonOp::Remove { path } => {
+                take(&mut doc, &tokens(path)?);
+            }
+            JsonOp::Rename { from, to } => {
+                if let Some(value) = take(&mut doc, &tokens(from)?) {
+                    set(&mut doc, &tokens(to)?, value, to)?;
+                }
+            }
+        }
+    }
+
+    serde_json::to_vec(&doc)
+        .map(Bytes::from)
+        .map_err(|_| TransformError::NotJson)
+}
+
+/// RFC 6901 reference tokens. "" is the whole document.
+fn tokens(pointer: &str) -> Result<Vec<String>, TransformError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| TransformError::InvalidPointer(pointer.to_string()))?;
+    Ok(rest
+        .split('/')
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn array_index(token: &str, len: usize) -> Option<usize> {
+    token.parse::<usize>().ok().filter(|&i| i < len)
+}
+
+fn set(doc: &mut Value, tokens: &[String], value: Value, pointer: &str) -> Result<(), TransformError> {
+    let unreachable = || TransformError::Unreachable(pointer.to_string());
+    let Some((last, parents)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+
+    let mut target = doc;
+    for token in parents {
+        target = match target {
+            Value::Object(map) => map
+                .entry(token.clone())
+                .or_insert_with(|| Value::Object(Default::default())),
+            Value::Array(items) => {
+                let i = array_index(token, items.len()).ok_or_else(unreachable)?;
+                &mut items[i]
+            }
+            _ => return Err(unreachable()),
+        };
+    }
+
+    match target {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) if last == "-" => items.push(value),
+        Value::Array(items) => {
+            let i = array_index(last, items.len()).ok_or_else(unreachable)?;
+            items[i] = value;
+        }
+        _ => return Err(unreachable()),
+    }
+    Ok(())
+}
+
+fn take(doc: &mut Value, tokens: &[String]) -> Option<Value> {
+    let (last, parents) = tokens.split_last()?;
+    let parent = parents.iter().try_fold(doc, |v, token| match v {
+        Value::Object(map) => map.get_mut(token),
+        Value::Array(items) => {
+            let len = items.len();
+            items.get_mut(array_index(token, len)?)
+        }
+        _ => None,
+    })?;
+
+    match parent {
+        Value::Object(map) => map.remove(last),
+        Value::Array(items) => {
+            let i = array_index(last, items.len())?;
+            Some(items.remove(i))
+        }
+        _ => None,
+    }
+}
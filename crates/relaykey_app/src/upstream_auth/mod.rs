@@ -0,0 +1,278 @@
+use axum::http::{header::AUTHORIZATION, HeaderName, HeaderValue, Method};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use url::Url;
+use uuid::Uuid;
+
+use relaykey_db::models::{OAuth2ClientCredentials, SignatureEncoding, UpstreamAuth};
+use relaykey_db::queries::virtual_keys::CredentialRow;
+
+use crate::oauth::{access_token, TokenError};
+use crate::ssrf::literal_host_forbidden;
+
+/// The credential row can't be turned into a valid request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialError {
+    HeaderName,
+    HeaderValue,
+}
+
+impl CredentialError {
+    pub fn message(self) -> &'static str {
+        match self {
+            CredentialError::HeaderName => "invalid credential header_name",
+            CredentialError::HeaderValue => "invalid credential header_value",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct HmacSigner {
+    secret: Vec<u8>,
+    api_key: Option<(HeaderName, HeaderValue)>,
+    signature_header: HeaderName,
+    timestamp_header: HeaderName,
+    timestamp_millis: bool,
+    encoding: SignatureEncoding,
+}
+
+#[derive(Clone)]
+enum Scheme {
+    Header(HeaderName, HeaderValue),
+    /// Bearer and Basic, encoded once up front.
+    Authorization(HeaderValue),
+    QueryParam { param: String, value: String },
+    Hmac(Box<HmacSigner>),
+    OAuth2 {
+        cfg: Box<OAuth2ClientCredentials>,
+        client_secret: String,
+        /// Set by `authorize`; the raw token is kept to recognize it as stale later.
+        token: Option<(String, HeaderValue)>,
+    },
+}
+
+/// An upstream credential parsed once per request and applied to every attempt
+/// (retries, hedges, WebSocket handshakes).
+#[derive(Clone)]
+pub struct UpstreamCredential {
+    pub id: Uuid,
+    scheme: Scheme,
+}
+
+/// Shown in place of secrets by the `Debug` impls below, so credentials can be logged.
+const REDACTED: &str = "<redacted>";
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("secret", &REDACTED)
+            .field("api_key", &self.api_key.as_ref().map(|(name, _)| (name, REDACTED)))
+            .field("signature_header", &self.signature_header)
+            .field("timestamp_header", &self.timestamp_header)
+            .field("timestamp_millis", &self.timestamp_millis)
+            .field("encoding", &self.encoding)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheme::Header(name, _) => f.debug_tuple("Header").field(name).field(&REDACTED).finish(),
+            Scheme::Authorization(_) => f.debug_tuple("Authorization").field(&REDACTED).finish(),
+            Scheme::QueryParam { param, .. } => f
+                .debug_struct("QueryParam")
+                .field("param", param)
+                .field("value", &REDACTED)
+                .finish(),
+            Scheme::Hmac(signer) => f.debug_tuple("Hmac").field(signer).finish(),
+            Scheme::OAuth2 { cfg, token, .. } => f
+                .debug_struct("OAuth2")
+                .field("cfg", cfg)
+                .field("client_secret", &REDACTED)
+                .field("token", &token.as_ref().map(|_| REDACTED))
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Debug for UpstreamCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamCredential")
+            .field("id", &self.id)
+            .field("scheme", &self.scheme)
+            .finish()
+    }
+}
+
+fn header_name(raw: &str) -> Result<HeaderName, CredentialError> {
+    HeaderName::from_bytes(raw.as_bytes()).map_err(|_| CredentialError::HeaderName)
+}
+
+fn secret_value(raw: &str) -> Result<HeaderValue, CredentialError> {
+    let mut value = HeaderValue::from_str(raw).map_err(|_| CredentialError::HeaderValue)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+impl UpstreamCredential {
+    pub fn from_row(row: &CredentialRow) -> Result<Self, CredentialError> {
+        let scheme = match &row.auth.0 {
+            UpstreamAuth::Header => {
+                Scheme::Header(header_name(&row.header_name)?, secret_value(&row.header_value)?)
+            }
+            UpstreamAuth::Bearer => {
+                Scheme::Authorization(secret_value(&format!("Bearer {}", row.header_value))?)
+            }
+            UpstreamAuth::Basic { username } => {
+                let encoded = STANDARD.encode(format!("{username}:{}", row.header_value));
+                Scheme::Authorization(secret_value(&format!("Basic {encoded}"))?)
+            }
+            UpstreamAuth::QueryParam { param } => Scheme::QueryParam {
+                param: param.clone(),
+                value: row.header_value.clone(),
+            },
+            UpstreamAuth::Hmac(cfg) => {
+                let api_key = match &cfg.api_key {
+                    Some(key) => Some((header_name(&cfg.api_key_header)?, secret_value(key)?)),
+                    None => None,
+                };
+                Scheme::Hmac(Box::new(HmacSigner {
+                    secret: row.header_value.as_bytes().to_vec(),
+                    api_key,
+                    signature_header: header_name(&cfg.signature_header)?,
+                    timestamp_header: header_name(&cfg.timestamp_header)?,
+                    timestamp_millis: cfg.timestamp_millis,
+                    encoding: cfg.encoding,
+                }))
+            }
+            UpstreamAuth::Oauth2ClientCredentials(cfg) => Scheme::OAuth2 {
+                cfg: Box::new(cfg.clone()),
+                client_secret: row.header_value.clone(),
+                token: None,
+            },
+        };
+
+        Ok(Self { id: row.id, scheme })
+    }
+
+    /// Signing covers the request body, so it must be buffered.
+    pub fn signs_body(&self) -> bool {
+        matches!(self.scheme, Scheme::Hmac(_))
+    }
+
+    /// The partner accepts a short-lived token (OAuth2); a 401 may just mean it expired.
+    pub fn uses_token(&self) -> bool {
+        matches!(self.scheme, Scheme::OAuth2 { .. })
+    }
+
+    /// Obtain the access token for token-based schemes (no-op otherwise).
+    /// Unless `internal`, the token URL may not be an address in blocked space.
+    /// `force` discards the current token, after the partner rejected it.
+    pub async fn authorize(
+        &mut self,
+        redis_client: &redis::Client,
+        http: &reqwest::Client,
+        internal: bool,
+        force: bool,
+    ) -> Result<(), TokenError> {
+        let Scheme::OAuth2 {
+            cfg,
+            client_secret,
+            token,
+        } = &mut self.scheme
+        else {
+            return Ok(());
+        };
+
+        // Hostnames are checked by the guarded client's resolver; IP literals never reach it.
+        if !internal && Url::parse(&cfg.token_url).is_ok_and(|url| literal_host_forbidden(&url)) {
+            return Err(TokenError::Blocked);
+        }
+
+        let stale = if force {
+            token.as_ref().map(|(raw, _)| raw.as_str())
+        } else {
+            None
+        };
+
+        let fresh = access_token(redis_client, http, self.id, cfg, client_secret, stale).await?;
+        let value = secret_value(&format!("Bearer {fresh}")).map_err(|_| TokenError::InvalidToken)?;
+        *token = Some((fresh, value));
+        Ok(())
+    }
+
+    /// Upstream URL with the secret query parameter added, if the scheme uses one.
+    /// The result must never be logged; usage events only ever see the client path.
+    pub fn url(&self, url: &Url) -> Url {
+        match &self.scheme {
+            Scheme::QueryParam { param, value } => {
+                let mut out = url.clone();
+                out.query_pairs_mut().append_pair(param, value);
+                out
+            }
+            _ => url.clone(),
+        }
+    }
+
+    /// Headers to add to one upstream attempt. HMAC signatures are fresh per call
+    /// (new timestamp), over `{timestamp}{METHOD}{path?query}{body}`.
+    pub fn headers(&self, method: &Method, url: &Url, body: &[u8]) -> Vec<(HeaderName, HeaderValue)> {
+        match &self.scheme {
+            Scheme::Header(name, value) => vec![(name.clone(), value.clone())],
+            Scheme::Authorization(value) => vec![(AUTHORIZATION, value.clone())],
+            Scheme::QueryParam { .. } => Vec::new(),
+            Scheme::Hmac(signer) => signer.sign(method, url, body),
+            Scheme::OAuth2 { token, .. } => token
+                .iter()
+                .map(|(_, value)| (AUTHORIZATION, value.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl HmacSigner {
+    fn sign(&self, method: &Method, url: &Url, body: &[u8]) -> Vec<(HeaderName, HeaderValue)> {
+        let now = Utc::now();
+        let timestamp = if self.timestamp_millis {
+            now.timestamp_millis().to_string()
+        } else {
+            now.timestamp().to_string()
+        };
+
+        let path_and_query = match url.query() {
+            Some(q) => format!("{}?{q}", url.path()),
+            None => url.path().to_string(),
+        };
+
+        // HMAC accepts keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("hmac key");
+        mac.update(timestamp.as_bytes());
+        mac.update(method.as_str().as_bytes());
+        mac.update(path_and_query.as_bytes());
+        mac.update(body);
+        let digest = mac.finalize().into_bytes();
+
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex::encode(digest),
+            SignatureEncoding::Base64 => STANDARD.encode(digest),
+        };
+
+        let mut out = Vec::with_capacity(3);
+        if let Some((name, value)) = &self.api_key {
+            out.push((name.clone(), value.clone()));
+        }
+        // both are plain ASCII
+        if let Ok(v) = HeaderValue::from_str(&timestamp) {
+            out.push((self.timestamp_header.clone(), v));
+        }
+        if let Ok(mut v) = HeaderValue::from_str(&signature) {
+            v.set_sensitive(true);
+            out.push((self.signature_header.clone(), v));
+        }
+        out
+    }
+}
@@ -1,6 +1,9 @@
 use sqlx::PgPool;
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::jsonrpc::RpcCall;
+
 #[derive(Clone, Copy, Debug)]
 pub enum BlockedReason {
     RateLimitExceeded,
@@ -16,10 +19,28 @@ pub enum BlockedReason {
     InvalidCredentialHeaderValue,
     UpstreamRequestFailed,
     EndpointNotAllowed,
+    PartnerCoolingDown,
+    CircuitOpen,
+    IdempotencyKeyInFlight,
+    IdempotencyKeyMismatch,
+    IdempotencyResponseUnavailable,
+    RequestBodyTooLarge,
+    ResponseTooLarge,
+    ResponseStreamTimeout,
+    StreamMessageLimit,
+    UpstreamAuthFailed,
+    InvalidRpcRequest,
+    RpcMethodNotAllowed,
+    GraphqlOperationRejected,
+    RequestSchemaInvalid,
+    RequestTransformFailed,
+    ResponseTransformFailed,
+    ResponseRedactionFailed,
+    MockFixtureNotFound,
 }
 
 impl BlockedReason {
-    pub fn code(self) -> &'static str {
+    pub const fn code(self) -> &'static str {
         match self {
             BlockedReason::RateLimitExceeded => "rate_limit_exceeded",
             BlockedReason::MonthlyQuotaExceeded => "monthly_quota_exceeded",
@@ -34,23 +55,101 @@ impl BlockedReason {
             BlockedReason::InvalidCredentialHeaderValue => "invalid_credential_header_value",
             BlockedReason::UpstreamRequestFailed => "upstream_request_failed",
             BlockedReason::EndpointNotAllowed => "endpoint_not_allowed",
+            BlockedReason::PartnerCoolingDown => "partner_cooling_down",
+            BlockedReason::CircuitOpen => "circuit_open",
+            BlockedReason::IdempotencyKeyInFlight => "idempotency_key_in_flight",
+            BlockedReason::IdempotencyKeyMismatch => "idempotency_key_mismatch",
+            BlockedReason::IdempotencyResponseUnavailable => "idempotency_response_unavailable",
+            BlockedReason::RequestBodyTooLarge => "request_body_too_large",
+            BlockedReason::ResponseTooLarge => "response_too_large",
+            BlockedReason::ResponseStreamTimeout => "response_stream_timeout",
+            BlockedReason::StreamMessageLimit => "stream_message_limit",
+            BlockedReason::UpstreamAuthFailed => "upstream_auth_failed",
+            BlockedReason::InvalidRpcRequest => "invalid_rpc_request",
+            BlockedReason::RpcMethodNotAllowed => "rpc_method_not_allowed",
+            BlockedReason::GraphqlOperationRejected => "graphql_operation_rejected",
+            BlockedReason::RequestSchemaInvalid => "request_schema_invalid",
+            BlockedReason::RequestTransformFailed => "request_transform_failed",
+            BlockedReason::ResponseTransformFailed => "response_transform_failed",
+            BlockedReason::ResponseRedactionFailed => "response_redaction_failed",
+            BlockedReason::MockFixtureNotFound => "mock_fixture_not_found",
         }
     }
 }
 
-pub async fn insert_usage_event(
+/// Optional per-event detail beyond the core columns. Defaults to "nothing to report".
+#[derive(Clone, Debug, Default)]
+pub struct UsageDetails {
+    /// A hedged second attempt was sent.
+    pub hedged: bool,
+    /// The response came from the hedged attempt.
+    pub hedge_won: bool,
+    /// Response body bytes relayed to the client.
+    pub response_bytes: Option<i64>,
+    /// Client -> partner bytes (WebSocket connections).
+    pub request_bytes: Option<i64>,
+    /// WebSocket messages sent by the client.
+    pub request_messages: Option<i32>,
+    /// SSE events / WebSocket messages sent by the partner.
+    pub response_messages: Option<i32>,
+    /// Answered from the response cache; the partner was not called.
+    pub cache_hit: bool,
+    /// Shared another identical request's upstream call; the partner was not called again.
+    pub coalesced: bool,
+    /// Answered from a sandbox fixture; the partner was not called.
+    pub mocked: bool,
+    /// JSON-RPC calls in the request (JSON-RPC partners only).
+    pub rpc_calls: Option<Arc<[RpcCall]>>,
+    /// GraphQL operation name (GraphQL partners only).
+    pub graphql_operation: Option<Arc<str>>,
+    /// Partner operation of the matched route rule (`operation_id`).
+    pub operation_id: Option<Arc<str>>,
+}
+
+/// The core columns of a `usage_events` row.
+#[derive(Clone, Copy, Debug)]
+pub struct UsageEvent<'a> {
+    pub virtual_key_id: Uuid,
+    pub customer_id: Uuid,
+    pub partner_name: &'a str,
+    pub path: &'a str,
+    pub forwarded: bool,
+    pub blocked_reason: Option<BlockedReason>,
+    pub status_code: Option<u16>,
+    pub latency_ms: i32,
+}
+
+pub async fn insert_usage_event(db: &PgPool, event: UsageEvent<'_>) -> Result<(), sqlx::Error> {
+    insert_usage_event_detailed(db, event, UsageDetails::default()).await
+}
+
+pub async fn insert_usage_event_detailed(
     db: &PgPool,
-    virtual_key_id: Uuid,
-    customer_id: Uuid,
-    partner_name: &str,
-    path: &str,
-    forwarded: bool,
-    blocked_reason: Option<BlockedReason>,
-    status_code: Option<u16>,
-    latency_ms: i32,
+    event: UsageEvent<'_>,
+    details: UsageDetails,
 ) -> Result<(), sqlx::Error> {
+    let UsageEvent {
+        virtual_key_id,
+        customer_id,
+        partner_name,
+        path,
+        forwarded,
+        blocked_reason,
+        status_code,
+        latency_ms,
+    } = event;
     let blocked_reason_str = blocked_reason.map(|r| r.code().to_string());
     let status_code_i32 = status_code.map(|s| s as i32);
+    let calls = details.rpc_calls.as_deref().unwrap_or_default();
+    let (rpc_methods, rpc_method_costs, cost_units) = if calls.is_empty() {
+        (None, None, None)
+    } else {
+        (
+            Some(calls.iter().map(|c| c.method.clone()).collect::<Vec<_>>()),
+            Some(calls.iter().map(|c| c.cost.min(i32::MAX as u32) as i32).collect::<Vec<_>>()),
+            Some(crate::jsonrpc::total_cost(calls).min(i32::MAX as u64) as i32),
+        )
+    };
 
     sqlx::query!(
         r#"
@@ -62,9 +161,23 @@ pub async fn insert_usage_event(
             forwarded,
             blocked_reason,
             status_code,
-            latency_ms
+            latency_ms,
+            hedged,
+            hedge_won,
+            response_bytes,
+            request_bytes,
+            request_messages,
+            response_messages,
+            cache_hit,
+            coalesced,
+            mocked,
+            rpc_methods,
+            rpc_method_costs,
+            cost_units,
+            graphql_operation,
+            operation_id
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22)
         "#,
         virtual_key_id,
         customer_id,
@@ -73,10 +186,24 @@ pub async fn insert_usage_event(
         forwarded,
         blocked_reason_str,
         status_code_i32,
-        latency_ms
+        latency_ms,
+        details.hedged,
+        details.hedge_won,
+        details.response_bytes,
+        details.request_bytes,
+        details.request_messages,
+        details.response_messages,
+        details.cache_hit,
+        details.coalesced,
+        details.mocked,
+        rpc_methods.as_deref(),
+        rpc_method_costs.as_deref(),
+        cost_units,
+        details.graphql_operation.as_deref(),
+        details.operation_id.as_deref()
     )
     .execute(db)
     .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
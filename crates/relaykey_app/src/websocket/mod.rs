@@ -0,0 +1,249 @@
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use std::{borrow::Cow, io, sync::Arc, time::Duration};
+use tokio::{net::TcpStream, time::sleep};
+use tokio_tungstenite::{
+    tungstenite::{
+        self,
+        client::IntoClientRequest,
+        protocol::{frame::coding::CloseCode, CloseFrame as UpstreamCloseFrame},
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+use url::Url;
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::response_body::{ResponseLimits, ResponseUsage};
+use crate::ssrf::{literal_host_forbidden, resolve, SsrfBlocked};
+use crate::usage::{insert_usage_event_detailed, BlockedReason, UsageDetails, UsageEvent};
+
+pub type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Handshake headers owned by each hop; never copied from the client.
+static WS_HANDSHAKE: &[&str] = &[
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "sec-websocket-extensions",
+    "sec-websocket-accept",
+];
+
+pub fn is_handshake_header(name: &str) -> bool {
+    WS_HANDSHAKE.contains(&name)
+}
+
+/// Policy violation (RFC 6455 §7.4.1).
+const CLOSE_POLICY: u16 = 1008;
+
+/// http(s) partner URL -> ws(s) URL for the upstream handshake.
+pub fn websocket_url(url: &Url) -> Option<Url> {
+    let mut out = url.clone();
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        "http" => "ws",
+        _ => return None,
+    };
+    out.set_scheme(scheme).ok()?;
+    Some(out)
+}
+
+/// Open the upstream WebSocket with the client's (already filtered) headers plus
+/// the partner credential. `tls` is the partner's own TLS config (mTLS), if any.
+/// The host is resolved through the SSRF guard unless the partner is `internal`.
+/// Returns the socket and the subprotocol the partner picked.
+pub async fn connect_upstream(
+    url: &Url,
+    headers: &[(HeaderName, HeaderValue)],
+    tls: Option<Arc<rustls::ClientConfig>>,
+    internal: bool,
+) -> Result<(UpstreamSocket, Option<String>), tungstenite::Error> {
+    let mut request = url.as_str().into_client_request()?;
+    for (name, value) in headers {
+        request.headers_mut().append(name.clone(), value.clone());
+    }
+
+    let host = url
+        .host_str()
+        .ok_or(tungstenite::Error::Url(tungstenite::error::UrlError::NoHostName))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    if !internal && literal_host_forbidden(url) {
+        return Err(tungstenite::Error::Io(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            SsrfBlocked {
+                host: host.to_string(),
+            },
+        )));
+    }
+
+    let addrs = resolve(host, port, internal).await?;
+    let stream = TcpStream::connect(addrs.as_slice()).await?;
+
+    let (socket, response) =
+        tokio_tungstenite::client_async_tls_with_config(request, stream, None, tls.map(Connector::Rustls))
+            .await?;
+    let protocol = protocol_of(response.headers());
+    Ok((socket, protocol))
+}
+
+fn protocol_of(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("sec-websocket-protocol")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn to_upstream(msg: Message) -> tungstenite::Message {
+    match msg {
+        Message::Text(t) => tungstenite::Message::Text(t),
+        Message::Binary(b) => tungstenite::Message::Binary(b),
+        Message::Ping(p) => tungstenite::Message::Ping(p),
+        Message::Pong(p) => tungstenite::Message::Pong(p),
+        Message::Close(frame) => tungstenite::Message::Close(frame.map(|f| UpstreamCloseFrame {
+            code: CloseCode::from(f.code),
+            reason: f.reason,
+        })),
+    }
+}
+
+fn to_client(msg: tungstenite::Message) -> Option<Message> {
+    Some(match msg {
+        tungstenite::Message::Text(t) => Message::Text(t),
+        tungstenite::Message::Binary(b) => Message::Binary(b),
+        tungstenite::Message::Ping(p) => Message::Ping(p),
+        tungstenite::Message::Pong(p) => Message::Pong(p),
+        tungstenite::Message::Close(frame) => Message::Close(frame.map(|f| CloseFrame {
+            code: f.code.into(),
+            reason: f.reason,
+        })),
+        // raw frames only show up when writing; nothing to relay
+        tungstenite::Message::Frame(_) => return None,
+    })
+}
+
+/// Payload size of data messages; control frames don't count toward usage.
+fn data_len(msg: &Message) -> Option<u64> {
+    match msg {
+        Message::Text(t) => Some(t.len() as u64),
+        Message::Binary(b) => Some(b.len() as u64),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    client_messages: u64,
+    client_bytes: u64,
+    upstream_messages: u64,
+    upstream_bytes: u64,
+}
+
+impl Counters {
+    fn messages(&self) -> u64 {
+        self.client_messages + self.upstream_messages
+    }
+}
+
+/// Relay messages both ways until either side closes or a limit trips,
+/// then record one usage event for the whole connection.
+pub async fn relay(
+    client: WebSocket,
+    upstream: UpstreamSocket,
+    limits: ResponseLimits,
+    usage: ResponseUsage,
+) {
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+    let deadline = sleep(limits.max_duration.unwrap_or(Duration::MAX));
+    tokio::pin!(deadline);
+
+    let mut counters = Counters::default();
+    let mut blocked_reason: Option<BlockedReason> = None;
+
+    loop {
+        tokio::select! {
+            msg = client_rx.next() => {
+                let Some(Ok(msg)) = msg else {
+                    let _ = upstream_tx.close().await;
+                    break;
+                };
+                if let Some(len) = data_len(&msg) {
+                    counters.client_messages += 1;
+                    counters.client_bytes += len;
+                }
+                let closing = matches!(msg, Message::Close(_));
+                if upstream_tx.send(to_upstream(msg)).await.is_err() || closing {
+                    break;
+                }
+            }
+            msg = upstream_rx.next() => {
+                let Some(Ok(msg)) = msg else {
+                    let _ = client_tx.close().await;
+                    break;
+                };
+                let Some(msg) = to_client(msg) else {
+                    continue;
+                };
+                if let Some(len) = data_len(&msg) {
+                    counters.upstream_messages += 1;
+                    counters.upstream_bytes += len;
+                }
+                let closing = matches!(msg, Message::Close(_));
+                if client_tx.send(msg).await.is_err() || closing {
+                    break;
+                }
+            }
+            _ = &mut deadline, if limits.max_duration.is_some() => {
+                blocked_reason = Some(BlockedReason::ResponseStreamTimeout);
+            }
+        }
+
+        if blocked_reason.is_none() && limits.max_messages.is_some_and(|max| counters.messages() > max) {
+            blocked_reason = Some(BlockedReason::StreamMessageLimit);
+        }
+
+        if let Some(reason) = blocked_reason {
+            tracing::warn!(
+                partner = %usage.partner_name,
+                vk_id = %usage.virtual_key_id,
+                reason = reason.code(),
+                "websocket limit reached; closing"
+            );
+            let _ = client_tx
+                .send(Message::Close(Some(CloseFrame {
+                    code: CLOSE_POLICY,
+                    reason: Cow::Borrowed(reason.code()),
+                })))
+                .await;
+            let _ = upstream_tx.close().await;
+            break;
+        }
+    }
+
+    let details = UsageDetails {
+        request_bytes: Some(counters.client_bytes.min(i64::MAX as u64) as i64),
+        request_messages: Some(counters.client_messages.min(i32::MAX as u64) as i32),
+        response_bytes: Some(counters.upstream_bytes.min(i64::MAX as u64) as i64),
+        response_messages: Some(counters.upstream_messages.min(i32::MAX as u64) as i32),
+        ..usage.details
+    };
+
+    let _ = insert_usage_event_detailed(
+        &usage.db,
+        UsageEvent {
+            virtual_key_id: usage.virtual_key_id,
+            customer_id: usage.customer_id,
+            partner_name: &usage.partner_name,
+            path: &usage.path,
+            forwarded: true,
+            blocked_reason,
+            status_code: Some(usage.status_code),
+            latency_ms: usage.latency_ms,
+        },
+        details,
+    )
+    .await;
+}
@@ -2,13 +2,31 @@ use axum::http::Method;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use sha2::{Digest, Sha256};
 
+/// Incremental form of `compute_request_hash`, for bodies that arrive in chunks.
+pub struct RequestHasher(Sha256);
+
+impl RequestHasher {
+    pub fn new(method: &Method, path_and_query: &str) -> Self {
+        let mut h = Sha256::new();
+        h.update(method.as_str().as_bytes());
+        h.update(b"\n");
+        h.update(path_and_query.as_bytes());
+        h.update(b"\n");
+        Self(h)
+    }
+
+    pub fn update(&mut self, body_chunk: &[u8]) {
+        self.0.update(body_chunk);
+    }
+
+    pub fn finish(self) -> String {
+        let out = self.0.finalize();
+        base64::Engine::encode(&URL_SAFE_NO_PAD, out)
+    }
+}
+
 pub fn compute_request_hash(method: &Method, path_and_query: &str, body: &[u8]) -> String {
-    let mut h = Sha256::new();
-    h.update(method.as_str().as_bytes());
-    h.update(b"\n");
-    h.update(path_and_query.as_bytes());
-    h.update(b"\n");
+    let mut h = RequestHasher::new(method, path_and_query);
     h.update(body);
-    let out = h.finalize();
-    base64::Engine::encode(&URL_SAFE_NO_PAD, &out)
+    h.finish()
 }
@@ -9,10 +9,15 @@ use serde::Serialize;
 use std::sync::Arc;
 
 use crate::auth::VirtualKeyCtx;
+use crate::request_body::{
+    buffer_request, declared_too_large, max_request_body_bytes, BodyError,
+    DEFAULT_MAX_REQUEST_BODY_BYTES,
+};
 use crate::state::AppState;
 use crate::x402::{config::resolve_x402_config, registry::ProviderRegistry};
 
 use relaykey_db::queries::{
+    policies::PolicyRow,
     payment_intents::{
         expire_stale_payment_intents,
         find_latest_pending_intent_by_request_hash,
@@ -25,7 +30,7 @@ use relaykey_db::queries::{
     x402_metrics::insert_x402_event,
 };
 
-use super::{hash::compute_request_hash, provider::VerifyInput};
+use super::provider::VerifyInput;
 
 #[derive(Serialize)]
 struct PaymentInstructions<'a> {
@@ -134,10 +139,30 @@ pub async fn enforce_x402(
     // - run replay checks
     // - look up matching pending intent
     // - rebuild request for upstream if verified
-    let (parts, body) = req.into_parts();
+    // Bounded by the policy's request body cap; large bodies spill to disk.
+    let (mut parts, body) = req.into_parts();
+
+    let max_body = parts
+        .extensions
+        .get::<PolicyRow>()
+        .map(max_request_body_bytes)
+        .unwrap_or(DEFAULT_MAX_REQUEST_BODY_BYTES);
+
+    if declared_too_large(&parts.headers, max_body) {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "request body too large").into_response();
+    }
+
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str().to_string())
+        .unwrap_or_else(|| parts.uri.path().to_string());
 
-    let body_bytes = match axum::body::to_bytes(body, usize::MAX).await {
+    let buffered = match buffer_request(body, &parts.method, &path_and_query, max_body).await {
         Ok(b) => b,
+        Err(BodyError::TooLarge) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "request body too large").into_response();
+        }
         Err(e) => {
             tracing::error!(
                 error = %e,
@@ -154,13 +179,7 @@ pub async fn enforce_x402(
         }
     };
 
-    let path_and_query = parts
-        .uri
-        .path_and_query()
-        .map(|pq| pq.as_str())
-        .unwrap_or(parts.uri.path());
-
-    let request_hash = compute_request_hash(&parts.method, path_and_query, &body_bytes);
+    let request_hash = buffered.request_hash.clone();
 
     // If payment proof was supplied, verify it and reconcile the matching pending intent.
     if payment_id.is_some() || payment_token.is_some() {
@@ -328,7 +347,21 @@ pub async fn enforce_x402(
                     );
                 }
 
-                let req = Request::from_parts(parts, Body::from(body_bytes));
+                let body = match buffered.body.to_axum().await {
+                    Ok(b) => b,
+                    Err(e) => {
+                        tracing::error!(error = %e, vk_id = %vk.id, "failed to reopen buffered request body");
+                        return (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "failed to read request body",
+                        )
+                            .into_response();
+                    }
+                };
+
+                // The proxy reuses the buffered copy instead of reading the body again.
+                parts.extensions.insert(buffered);
+                let req = Request::from_parts(parts, body);
                 return next.run(req).await;
             }
             Ok(out) => {
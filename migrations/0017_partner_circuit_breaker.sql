@@ -0,0 +1,4 @@
+-- Per-partner circuit breaker thresholds.
+-- Empty object = built-in defaults (see relaykey_db::models::CircuitBreakerConfig).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS circuit_breaker jsonb NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Header name the partner accepts for idempotency keys (e.g. 'Idempotency-Key').
+-- NULL = partner does not support idempotency keys; non-idempotent requests are never retried.
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS idempotency_header text NULL;
@@ -0,0 +1,10 @@
+-- Per-partner request hedging (opt-in).
+-- Empty object = disabled (see relaykey_db::models::HedgingConfig).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS hedging jsonb NOT NULL DEFAULT '{}';
+
+-- hedged: a second attempt was sent while the first was still pending.
+-- hedge_won: the response returned to the client came from that second attempt.
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS hedged boolean NOT NULL DEFAULT false,
+ADD COLUMN IF NOT EXISTS hedge_won boolean NOT NULL DEFAULT false;
@@ -0,0 +1,4 @@
+-- Multiple upstream origins per partner (ordered or weighted failover).
+-- Empty object = just partners.base_url (see relaykey_db::models::UpstreamEndpoints).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS upstream_endpoints jsonb NOT NULL DEFAULT '{}';
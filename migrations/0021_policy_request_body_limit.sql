@@ -0,0 +1,3 @@
+-- Per-policy request body cap. NULL = gateway default (2 MiB).
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS max_request_body_bytes BIGINT NULL;
@@ -0,0 +1,11 @@
+-- Per-policy response guards. NULL = unlimited.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS max_response_body_bytes BIGINT NULL,
+ADD COLUMN IF NOT EXISTS max_stream_duration_ms INTEGER NULL;
+
+-- Bytes of response body actually relayed to the client (bandwidth attribution).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS response_bytes BIGINT NULL;
+
+ALTER TABLE usage_rollup_daily
+ADD COLUMN IF NOT EXISTS response_bytes BIGINT NOT NULL DEFAULT 0;
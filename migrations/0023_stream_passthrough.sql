@@ -0,0 +1,10 @@
+-- Long-lived connections (SSE / WebSocket): per-connection message cap. NULL = unlimited.
+-- Duration reuses policies.max_stream_duration_ms.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS max_stream_messages INTEGER NULL;
+
+-- Client -> partner traffic, and message counts for SSE / WebSocket connections.
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS request_bytes BIGINT NULL,
+ADD COLUMN IF NOT EXISTS request_messages INTEGER NULL,
+ADD COLUMN IF NOT EXISTS response_messages INTEGER NULL;
@@ -0,0 +1,4 @@
+-- How each credential is presented upstream (header, bearer, basic, query_param, hmac).
+-- header_value remains the secret for every scheme.
+ALTER TABLE upstream_credentials
+ADD COLUMN IF NOT EXISTS auth JSONB NOT NULL DEFAULT '{"scheme": "header"}'::jsonb;
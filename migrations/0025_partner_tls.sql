@@ -0,0 +1,4 @@
+-- Mutual TLS / trust settings per partner: client identity, custom CA, pinned server cert.
+-- Empty object = the shared default client (see relaykey_db::models::PartnerTls).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS tls jsonb NOT NULL DEFAULT '{}';
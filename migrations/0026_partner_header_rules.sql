@@ -0,0 +1,4 @@
+-- Per-partner header allow/deny, injection and response rewrite rules.
+-- Empty object = built-in filtering only (see relaykey_db::models::HeaderRules).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS header_rules jsonb NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Forwarded / X-Forwarded-*, request-id and traceparent emission towards each partner.
+-- Empty object = none of them (see relaykey_db::models::ForwardingHeaders).
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS forwarding jsonb NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Internal partners may resolve to private / loopback / link-local addresses.
+-- Everyone else is held to the SSRF guard's resolver.
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS internal boolean NOT NULL DEFAULT false;
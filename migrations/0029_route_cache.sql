@@ -0,0 +1,10 @@
+-- Per-route rules on a policy (see relaykey_db::models::RouteRule), e.g. response caching.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS routes jsonb NOT NULL DEFAULT '[]';
+
+-- Requests answered from the response cache (not forwarded, no quota consumed).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS cache_hit boolean NOT NULL DEFAULT false;
+
+ALTER TABLE usage_rollup_daily
+ADD COLUMN IF NOT EXISTS cache_hits BIGINT NOT NULL DEFAULT 0;
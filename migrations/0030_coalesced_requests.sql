@@ -0,0 +1,3 @@
+-- Requests that shared another identical request's upstream call (single-flight).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS coalesced boolean NOT NULL DEFAULT false;
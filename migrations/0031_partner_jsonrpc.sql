@@ -0,0 +1,29 @@
+-- JSON-RPC mode per partner (see relaykey_db::models::JsonRpcConfig). Empty = disabled.
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS jsonrpc jsonb NOT NULL DEFAULT '{}';
+
+-- JSON-RPC calls in the request (one entry per call in a batch) and their cost units.
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS rpc_methods text[] NULL,
+ADD COLUMN IF NOT EXISTS rpc_method_costs integer[] NULL,
+ADD COLUMN IF NOT EXISTS cost_units integer NULL;
+
+ALTER TABLE usage_rollup_daily
+ADD COLUMN IF NOT EXISTS cost_units BIGINT NOT NULL DEFAULT 0;
+
+-- Forwarded JSON-RPC calls per method.
+CREATE TABLE IF NOT EXISTS rpc_method_rollup_daily (
+    day date NOT NULL,
+    customer_id uuid NOT NULL,
+    virtual_key_id uuid NOT NULL,
+    partner_name text NOT NULL,
+    method text NOT NULL,
+
+    calls bigint NOT NULL,
+    cost_units bigint NOT NULL,
+
+    PRIMARY KEY (day, customer_id, virtual_key_id, partner_name, method)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rpc_method_rollup_daily_day
+ON rpc_method_rollup_daily(day);
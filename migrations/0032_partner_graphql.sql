@@ -0,0 +1,7 @@
+-- GraphQL mode per partner (see relaykey_db::models::GraphQlConfig). Empty = disabled.
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS graphql jsonb NOT NULL DEFAULT '{}';
+
+-- GraphQL operation name (GraphQL partners only; NULL for anonymous operations).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS graphql_operation text NULL;
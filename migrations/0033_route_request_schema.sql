@@ -0,0 +1,4 @@
+-- Bumped on every change to a policy's route rules; compiled request schemas
+-- (RouteRule.request_schema) are cached per policy version.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,29 @@
+-- Partner OpenAPI 3 documents; the latest import replaces the previous one.
+CREATE TABLE IF NOT EXISTS partner_openapi_specs (
+    partner_id uuid PRIMARY KEY REFERENCES partners(id) ON DELETE CASCADE,
+    spec jsonb NOT NULL,
+    title text NULL,
+    spec_version text NULL,
+    operations integer NOT NULL DEFAULT 0,
+    imported_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- Partner operation the request matched (RouteRule.operation_id).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS operation_id text NULL;
+
+-- Forwarded requests per partner operation.
+CREATE TABLE IF NOT EXISTS operation_rollup_daily (
+    day date NOT NULL,
+    customer_id uuid NOT NULL,
+    virtual_key_id uuid NOT NULL,
+    partner_name text NOT NULL,
+    operation_id text NOT NULL,
+
+    calls bigint NOT NULL,
+
+    PRIMARY KEY (day, customer_id, virtual_key_id, partner_name, operation_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_operation_rollup_daily_day
+ON operation_rollup_daily(day);
@@ -0,0 +1,3 @@
+-- Response redaction profile per policy (see relaykey_db::models::RedactionProfile). Empty = off.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS redaction jsonb NOT NULL DEFAULT '{}';
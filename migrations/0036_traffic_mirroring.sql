@@ -0,0 +1,33 @@
+-- Per-partner traffic mirroring to a shadow partner (see relaykey_db::models::MirrorConfig).
+-- Empty object = disabled.
+ALTER TABLE partners
+ADD COLUMN IF NOT EXISTS mirror jsonb NOT NULL DEFAULT '{}';
+
+-- One row per mirrored request: how the shadow's answer compared to the primary's.
+-- Mirrored calls are not usage events and never count toward quotas.
+CREATE TABLE IF NOT EXISTS mirror_comparisons (
+    id bigserial PRIMARY KEY,
+    ts timestamptz NOT NULL DEFAULT now(),
+    partner_name text NOT NULL,
+    shadow_partner text NOT NULL,
+    virtual_key_id uuid NOT NULL,
+    customer_id uuid NOT NULL,
+    method text NOT NULL,
+    path text NOT NULL,
+
+    primary_status integer NOT NULL,
+    primary_latency_ms integer NOT NULL,
+    primary_body_sha256 text NULL,
+
+    -- NULL when the shadow call failed (see error).
+    shadow_status integer NULL,
+    shadow_latency_ms integer NULL,
+    shadow_body_sha256 text NULL,
+
+    status_match boolean NOT NULL,
+    body_match boolean NOT NULL,
+    error text NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_mirror_comparisons_partner_ts
+ON mirror_comparisons(partner_name, ts);
@@ -0,0 +1,3 @@
+-- Canary routing rules per policy (see relaykey_db::models::CanaryRule). Empty = off.
+ALTER TABLE policies
+ADD COLUMN IF NOT EXISTS canaries jsonb NOT NULL DEFAULT '[]';
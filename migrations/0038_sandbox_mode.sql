@@ -0,0 +1,37 @@
+-- Sandbox mode per virtual key: 'live' calls the partner, 'mock' answers from fixtures,
+-- 'record' calls the partner and stores its answers as fixtures.
+-- NULL = the default for the key's environment.
+ALTER TABLE virtual_keys
+ADD COLUMN IF NOT EXISTS sandbox_mode text NULL
+CHECK (sandbox_mode IN ('live', 'mock', 'record'));
+
+-- Responses served in mock mode, per partner. 'recorded' fixtures are captured in record
+-- mode; 'canned' ones are written by operators and may be templates.
+CREATE TABLE IF NOT EXISTS partner_fixtures (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    partner_name text NOT NULL,
+    method text NOT NULL,
+    -- Forwarded path; canned fixtures may use `*` segments.
+    path text NOT NULL,
+    -- Normalized query string; NULL matches any query.
+    query text NULL,
+    -- Fingerprint of the request body; NULL matches any body.
+    body_sha256 text NULL,
+
+    status integer NOT NULL,
+    headers jsonb NOT NULL DEFAULT '{}',
+    body bytea NOT NULL,
+    template boolean NOT NULL DEFAULT false,
+    source text NOT NULL CHECK (source IN ('recorded', 'canned')),
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_partner_fixtures_lookup
+ON partner_fixtures(partner_name, method);
+
+-- Requests answered from fixtures (the partner was not called).
+ALTER TABLE usage_events
+ADD COLUMN IF NOT EXISTS mocked boolean NOT NULL DEFAULT false;
+
+ALTER TABLE usage_rollup_daily
+ADD COLUMN IF NOT EXISTS mocked_requests BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Per-credential mTLS identity: requests sent with this credential present its certificate
+-- instead of the partner's (see relaykey_db::models::ClientIdentity). NULL = the partner's.
+ALTER TABLE upstream_credentials
+ADD COLUMN IF NOT EXISTS client_identity jsonb NULL;